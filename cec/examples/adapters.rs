@@ -0,0 +1,31 @@
+//! Lists the HDMI-CEC adapters libcec finds, like `owl adapters`. Errors are
+//! handled with `std` alone, as the crate's errors need nothing else.
+
+use std::error::Error;
+
+use cec::{Adapter, Connection, DeviceKind};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let adapters = Connection::builder()
+        .name("cec-example".to_owned())
+        .kind(DeviceKind::RecordingDevice)
+        .adapters()?;
+
+    if adapters.is_empty() {
+        println!("no adapters found");
+    }
+    let preferred = Adapter::preferred(&adapters);
+    for adapter in &adapters {
+        let marker = if preferred.is_some_and(|x| x.port == adapter.port) {
+            "*"
+        } else {
+            " "
+        };
+        println!(
+            "{marker} {} ({:?}, {:04x}:{:04x})",
+            adapter.port, adapter.kind, adapter.vendor_id, adapter.product_id
+        );
+    }
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::mem;
+use std::{mem, str::FromStr};
 
 use arrayvec::ArrayVec;
 use num_traits::ToPrimitive;
@@ -67,6 +67,46 @@ impl From<Cmd> for cec_command {
     }
 }
 
+impl From<UserControlCode> for u8 {
+    fn from(code: UserControlCode) -> Self {
+        code.repr() as Self
+    }
+}
+
+impl TryFrom<&Cmd> for Keypress {
+    type Error = Error;
+
+    fn try_from(command: &Cmd) -> Result<Self> {
+        if command.opcode != Opcode::UserControlPressed {
+            return Err(TryFromKeypressError::NotUserControlPressed.into());
+        }
+
+        let keycode = command
+            .parameters
+            .0
+            .first()
+            .ok_or(TryFromKeypressError::UnknownKeycode)?;
+        Ok(Self {
+            keycode: UserControlCode::try_from(*keycode)?,
+            duration: Duration::ZERO,
+        })
+    }
+}
+
+impl From<u8> for VolumeStatus {
+    fn from(status: u8) -> Self {
+        let mute_mask = AudioStatus::MuteStatusMask.repr() as u8;
+        let volume_mask = AudioStatus::VolumeStatusMask.repr() as u8;
+        let volume = status & volume_mask;
+
+        Self {
+            muted: status & mute_mask != 0,
+            // libcec reports an unknown volume with every volume bit set.
+            volume: (volume <= AudioStatus::VolumeMax.repr() as u8).then_some(volume),
+        }
+    }
+}
+
 impl From<LogicalAddresses> for cec_logical_addresses {
     fn from(addresses: LogicalAddresses) -> Self {
         // cec_logical_addresses.addresses is a 'mask'
@@ -88,10 +128,10 @@ impl From<LogicalAddresses> for cec_logical_addresses {
 impl From<DeviceKinds> for cec_device_type_list {
     fn from(device_types: DeviceKinds) -> Self {
         let mut devices = Self {
-            types: [DeviceKind::Reserved.repr(); 5],
+            types: [DeviceKind::Reserved.repr(); DeviceKinds::MAX],
         };
-        for (i, type_id) in device_types.0.iter().enumerate() {
-            devices.types[i] = (*type_id).repr();
+        for (dst, kind) in devices.types.iter_mut().zip(device_types.iter()) {
+            *dst = kind.repr();
         }
         devices
     }
@@ -105,10 +145,16 @@ impl From<&Cfg> for libcec_configuration {
             libcec_clear_configuration(&mut cfg);
         }
         cfg.clientVersion = libcec_version::CURRENT as _;
-        cfg.strDeviceName = first_n::<{ LIBCEC_OSD_NAME_SIZE as usize }>(&config.name);
+        // The builder checked the name is ASCII, and warned if it's too long.
+        let name = config.name.get(..OSD_NAME_LEN).unwrap_or(&config.name);
+        cfg.strDeviceName = first_n::<{ LIBCEC_OSD_NAME_SIZE as usize }>(name);
         cfg.deviceTypes = DeviceKinds::new(config.kind).into();
+        if let Some(v) = config.detect_physical_address {
+            cfg.bAutodetectAddress = v.into();
+        }
         if let Some(v) = config.physical_address {
-            cfg.iPhysicalAddress = v;
+            cfg.iPhysicalAddress = v.into();
+            cfg.bAutodetectAddress = 0;
         }
         if let Some(v) = config.base_device {
             cfg.baseDevice = v.repr();
@@ -205,6 +251,154 @@ impl TryFrom<libcec_configuration> for Cfg {
     }
 }
 
+impl From<u16> for PhysicalAddress {
+    fn from(address: u16) -> Self {
+        Self(address)
+    }
+}
+
+impl From<PhysicalAddress> for u16 {
+    fn from(address: PhysicalAddress) -> Self {
+        address.0
+    }
+}
+
+impl Display for PhysicalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [first, second, third, fourth] = self.ports();
+        write!(f, "{first:x}.{second:x}.{third:x}.{fourth:x}")
+    }
+}
+
+impl FromStr for PhysicalAddress {
+    type Err = ParsePhysicalAddressError;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let mut address = 0u16;
+        let mut ports = 0;
+        for port in s.split('.') {
+            let port = u8::from_str_radix(port, 16)
+                .ok()
+                .filter(|x| *x <= 0xF && port.len() == 1)
+                .ok_or(ParsePhysicalAddressError::InvalidFormat)?;
+            address = (address << 4) | u16::from(port);
+            ports += 1;
+        }
+
+        if ports != 4 {
+            return Err(ParsePhysicalAddressError::InvalidFormat);
+        }
+
+        Ok(Self(address))
+    }
+}
+
+impl From<cec_adapter_descriptor> for Adapter {
+    fn from(adapter: cec_adapter_descriptor) -> Self {
+        Self {
+            port: c_string(&adapter.strComName),
+            path: c_string(&adapter.strComPath),
+            vendor_id: adapter.iVendorId,
+            product_id: adapter.iProductId,
+            firmware_version: adapter.iFirmwareVersion,
+            kind: AdapterType::from_repr(adapter.adapterType).unwrap_or(AdapterType::Unknown),
+        }
+    }
+}
+
+impl Display for Version {
+    /// Writes the version number, like `1.3a`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::VersionUnknown => "unknown",
+            Self::Version12 => "1.2",
+            Self::Version12a => "1.2a",
+            Self::Version13 => "1.3",
+            Self::Version13a => "1.3a",
+            Self::Version14 => "1.4",
+            Self::Version20 => "2.0",
+        })
+    }
+}
+
+impl Display for Adapter {
+    /// Writes the port, kind and USB id, like
+    /// `/dev/ttyACM0 (P8External, 2548:1002)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?}, {:04x}:{:04x})",
+            self.port, self.kind, self.vendor_id, self.product_id
+        )
+    }
+}
+
+/// Reads a nul terminated string from a fixed size buffer.
+#[allow(clippy::cast_sign_loss)]
+fn c_string(chars: &[std::os::raw::c_char]) -> String {
+    let bytes: Vec<_> = chars
+        .iter()
+        .map(|x| *x as u8)
+        .take_while(|x| *x != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl Display for Keypress {
+    /// Writes the key, and how long it was held if released, like
+    /// `VolumeUp released after 250ms`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.duration.is_zero() {
+            write!(f, "{:?} pressed", self.keycode)
+        } else {
+            write!(
+                f,
+                "{:?} released after {}ms",
+                self.keycode,
+                self.duration.as_millis()
+            )
+        }
+    }
+}
+
+impl Display for DataPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DataPacket {
+    type Err = ParseDataPacketError;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let mut packet = Self(ArrayVec::new());
+        let bytes: Vec<_> = s.split_whitespace().collect();
+        if bytes.len() > packet.0.capacity() {
+            return Err(ParseDataPacketError::TooLong(
+                bytes.len(),
+                packet.0.capacity(),
+            ));
+        }
+
+        for byte in bytes {
+            // `from_str_radix` allows a sign, so the digits are checked first.
+            let value = Some(byte)
+                .filter(|x| x.len() == 2 && x.bytes().all(|x| x.is_ascii_hexdigit()))
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .ok_or_else(|| ParseDataPacketError::InvalidByte(byte.to_owned()))?;
+            packet.0.push(value);
+        }
+
+        Ok(packet)
+    }
+}
+
 impl From<String> for CfgBuilderError {
     fn from(s: String) -> Self {
         Self::ValidationError(s)
@@ -275,6 +469,7 @@ impl TryFrom<cec_log_message> for LogMsg {
             message,
             level,
             time: Duration::from_millis(time),
+            timestamp: SystemTime::now(),
         })
     }
 }
@@ -607,6 +802,38 @@ mod tests {
             expected.data[1] = 50;
             assert_eq_ffi_packet(ffi_packet, expected);
         }
+
+        #[test]
+        fn test_round_trip() -> result::Result<(), ParseDataPacketError> {
+            let packet: DataPacket = "40 04".parse()?;
+            assert_eq!(packet.0.as_slice(), &[0x40, 0x04]);
+            assert_eq!(packet.to_string(), "40 04");
+            assert_eq!("  0a   ff ".parse::<DataPacket>()?.to_string(), "0A FF");
+            Ok(())
+        }
+
+        #[test]
+        fn test_empty() -> result::Result<(), ParseDataPacketError> {
+            let packet: DataPacket = "".parse()?;
+            assert!(packet.0.is_empty());
+            assert_eq!(packet.to_string(), "");
+            Ok(())
+        }
+
+        #[test]
+        fn test_invalid() {
+            for s in ["4", "400", "zz", "+4", "40:04"] {
+                assert_eq!(
+                    s.parse::<DataPacket>(),
+                    Err(ParseDataPacketError::InvalidByte(s.to_owned())),
+                    "{s}"
+                );
+            }
+            assert_eq!(
+                vec!["00"; 65].join(" ").parse::<DataPacket>(),
+                Err(ParseDataPacketError::TooLong(65, 64))
+            );
+        }
     }
 
     #[cfg(test)]
@@ -706,6 +933,401 @@ mod tests {
     }
 
     #[cfg(test)]
+    mod adapter {
+        use super::*;
+
+        fn adapter(port: &str, kind: AdapterType) -> Adapter {
+            Adapter {
+                port: port.to_owned(),
+                path: String::new(),
+                vendor_id: 0x2548,
+                product_id: 0x1002,
+                firmware_version: 12,
+                kind,
+            }
+        }
+
+        #[test]
+        fn test_from_descriptor() {
+            let mut descriptor: cec_adapter_descriptor = unsafe { mem::zeroed() };
+            descriptor.strComName = first_n("/dev/ttyACM0");
+            descriptor.strComPath = first_n("/sys/devices/usb1/1-1");
+            descriptor.iVendorId = 0x2548;
+            descriptor.iProductId = 0x1002;
+            descriptor.iFirmwareVersion = 12;
+            descriptor.adapterType = cec_adapter_type::P8_EXTERNAL;
+
+            let adapter = Adapter::from(descriptor);
+            assert_eq!(adapter.port, "/dev/ttyACM0");
+            assert_eq!(adapter.path, "/sys/devices/usb1/1-1");
+            assert_eq!(adapter.kind, AdapterType::P8External);
+            assert_eq!(adapter.to_string(), "/dev/ttyACM0 (P8External, 2548:1002)");
+        }
+
+        #[test]
+        fn test_preferred() {
+            let adapters = [
+                adapter("/dev/cec0", AdapterType::Linux),
+                adapter("/dev/ttyACM1", AdapterType::P8External),
+                adapter("/dev/ttyACM0", AdapterType::P8External),
+            ];
+            assert_eq!(Adapter::preferred(&adapters), Some(&adapters[2]));
+            assert_eq!(Adapter::preferred(&adapters[..1]), Some(&adapters[0]));
+            assert_eq!(Adapter::preferred(&[]), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod cfg {
+        use super::*;
+
+        fn cfg() -> CfgBuilder {
+            Connection::builder()
+                .name("owl".to_owned())
+                .kind(DeviceKind::RecordingDevice)
+        }
+
+        fn cleared() -> libcec_configuration {
+            let mut cfg: libcec_configuration = unsafe { mem::zeroed() };
+            unsafe { libcec_clear_configuration(&mut cfg) };
+            cfg
+        }
+
+        #[test]
+        fn test_to_ffi() {
+            let cfg = cfg().hdmi_port(3).activate_source(false).build().unwrap();
+            let ffi_cfg: libcec_configuration = (&cfg).into();
+            assert_eq!(ffi_cfg.iHDMIPort, 3);
+            assert_eq!(ffi_cfg.bActivateSource, 0);
+            assert_eq!(
+                ffi_cfg.deviceTypes.types[0],
+                DeviceKind::RecordingDevice.repr()
+            );
+            assert_eq!(
+                ffi_cfg.strDeviceName[..4],
+                [b'o', b'w', b'l', 0].map(|x| x as std::ffi::c_char)
+            );
+        }
+
+        #[test]
+        fn test_to_ffi_keeps_cleared_defaults() {
+            let cfg = cfg().build().unwrap();
+            let ffi_cfg: libcec_configuration = (&cfg).into();
+            let cleared = cleared();
+            assert_eq!(ffi_cfg.iHDMIPort, cleared.iHDMIPort);
+            assert_eq!(ffi_cfg.iPhysicalAddress, cleared.iPhysicalAddress);
+            assert_eq!(ffi_cfg.bActivateSource, cleared.bActivateSource);
+            assert_eq!(ffi_cfg.baseDevice, cleared.baseDevice);
+            assert!(ffi_cfg.callbacks.is_null());
+        }
+
+        #[test]
+        fn test_to_ffi_physical_address() {
+            let cfg = cfg()
+                .physical_address(PhysicalAddress(0x1200))
+                .build()
+                .unwrap();
+            let ffi_cfg: libcec_configuration = (&cfg).into();
+            assert_eq!(ffi_cfg.iPhysicalAddress, 0x1200);
+            assert_eq!(ffi_cfg.bAutodetectAddress, 0);
+        }
+
+        #[test]
+        fn test_summary() {
+            let detected = cfg().build().unwrap();
+            assert_eq!(
+                detected.summary(),
+                r#"name "owl", kind RecordingDevice, device auto"#
+            );
+
+            let cfg = cfg()
+                .device(Some("COM3".to_owned()))
+                .hdmi_port(2)
+                .physical_address(PhysicalAddress(0x2000))
+                .monitor_only(true)
+                .on_alert(Box::new(|_| {}))
+                .build()
+                .unwrap();
+            assert_eq!(
+                cfg.summary(),
+                r#"name "owl", kind RecordingDevice, device COM3, hdmi port 2, physical address 2.0.0.0, monitor only true"#
+            );
+        }
+
+        #[test]
+        fn test_physical_address_exclusive() {
+            let detected = cfg()
+                .physical_address(PhysicalAddress(0x1000))
+                .detect_physical_address(true)
+                .build();
+            assert!(matches!(detected, Err(CfgBuilderError::ValidationError(_))));
+
+            let forced = cfg()
+                .physical_address(PhysicalAddress(0x1000))
+                .detect_physical_address(false)
+                .build();
+            assert!(forced.is_ok());
+        }
+    }
+
+    #[cfg(test)]
+    mod physical_address {
+        use super::*;
+
+        #[test]
+        fn test_parse() {
+            assert_eq!("1.2.0.f".parse(), Ok(PhysicalAddress(0x120F)));
+            assert_eq!("0.0.0.0".parse(), Ok(PhysicalAddress(0)));
+        }
+
+        #[test]
+        fn test_parse_invalid() {
+            for s in ["", "1.0.0", "1.0.0.0.0", "10.0.0.0", "g.0.0.0", "1..0.0"] {
+                assert_eq!(
+                    s.parse::<PhysicalAddress>(),
+                    Err(ParsePhysicalAddressError::InvalidFormat),
+                    "{s}"
+                );
+            }
+        }
+
+        #[test]
+        fn test_display() {
+            assert_eq!(PhysicalAddress(0x120F).to_string(), "1.2.0.f");
+        }
+
+        #[test]
+        fn test_nibble_packing() {
+            let address = PhysicalAddress(0x2100);
+            assert_eq!(address.ports(), [2, 1, 0, 0]);
+            assert_eq!(address.to_be_bytes(), [0x21, 0x00]);
+            assert_eq!(PhysicalAddress::from_be_bytes([0x21, 0x00]), address);
+            assert_eq!(PhysicalAddress::from(0x2100), address);
+            assert_eq!(u16::from(address), 0x2100);
+        }
+
+        #[test]
+        fn test_tree_position() {
+            assert_eq!(PhysicalAddress::ROOT.depth(), Some(0));
+            assert_eq!(PhysicalAddress::ROOT.port(), None);
+            assert_eq!(PhysicalAddress::ROOT.parent(), None);
+
+            let address = PhysicalAddress(0x1200);
+            assert_eq!(address.depth(), Some(2));
+            assert_eq!(address.port(), Some(2));
+            assert_eq!(address.parent(), Some(PhysicalAddress(0x1000)));
+            assert_eq!(
+                PhysicalAddress(0x1000).parent(),
+                Some(PhysicalAddress::ROOT)
+            );
+            assert_eq!(
+                PhysicalAddress(0x1234).parent(),
+                Some(PhysicalAddress(0x1230))
+            );
+        }
+
+        #[test]
+        fn test_invalid_tree_position() {
+            for address in [PhysicalAddress::UNKNOWN, PhysicalAddress(0x1020)] {
+                assert!(!address.is_valid(), "{address}");
+                assert_eq!(address.depth(), None);
+                assert_eq!(address.port(), None);
+                assert_eq!(address.parent(), None);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod vendor_command {
+        use super::*;
+
+        #[test]
+        fn test_without_vendor_id() {
+            let command = Cmd::vendor_command(
+                LogicalAddress::Recordingdevice1,
+                LogicalAddress::Tv,
+                None,
+                &[0x01, 0x02],
+            )
+            .unwrap();
+            assert_eq!(command.opcode, Opcode::VendorCommand);
+            assert_eq!(command.parameters.0.as_slice(), [0x01, 0x02]);
+        }
+
+        #[test]
+        fn test_with_vendor_id() {
+            let command = Cmd::vendor_command(
+                LogicalAddress::Recordingdevice1,
+                LogicalAddress::Tv,
+                Some(VendorId::Samsung),
+                &[0x01, 0x02],
+            )
+            .unwrap();
+            assert_eq!(command.opcode, Opcode::VendorCommandWithId);
+            assert_eq!(
+                command.parameters.0.as_slice(),
+                [0x00, 0x00, 0xF0, 0x01, 0x02]
+            );
+        }
+
+        #[test]
+        fn test_transmit_timeout_to_ffi() {
+            let command = Cmd::vendor_command(
+                LogicalAddress::Recordingdevice1,
+                LogicalAddress::Tv,
+                None,
+                &[],
+            )
+            .unwrap()
+            .with_transmit_timeout(Duration::from_millis(250));
+            let ffi_command: cec_command = command.into();
+            assert_eq!(ffi_command.transmit_timeout, 250);
+        }
+
+        #[test]
+        fn test_payload_too_long() {
+            let command = Cmd::vendor_command(
+                LogicalAddress::Recordingdevice1,
+                LogicalAddress::Tv,
+                Some(VendorId::Samsung),
+                &[0; 62],
+            );
+            assert_eq!(command.err(), Some(CmdError::PayloadTooLong(65, 64).into()));
+        }
+    }
+
+    #[cfg(test)]
+    mod broadcast {
+        use super::*;
+
+        #[test]
+        fn test_is_broadcast() {
+            assert!(LogicalAddress::BROADCAST.is_broadcast());
+            assert!(LogicalAddress::Unregistered.is_broadcast());
+            assert!(!LogicalAddress::Tv.is_broadcast());
+            assert!(!LogicalAddress::Unknown.is_broadcast());
+        }
+
+        #[test]
+        fn test_known_not_registered() {
+            assert_eq!(LogicalAddress::BROADCAST.repr() as u8, 15);
+            assert!(KnownLogicalAddress::new(LogicalAddress::BROADCAST).is_some());
+            assert!(RegisteredLogicalAddress::new(LogicalAddress::BROADCAST).is_none());
+        }
+
+        #[test]
+        fn test_standby() {
+            let broadcast =
+                Cmd::standby(LogicalAddress::Playbackdevice1, LogicalAddress::BROADCAST);
+            assert!(broadcast.is_broadcast());
+            assert_eq!(broadcast.to_frame(), [0x4F, 0x36]);
+
+            let unicast = Cmd::standby(LogicalAddress::Playbackdevice1, LogicalAddress::Tv);
+            assert!(!unicast.is_broadcast());
+            assert_eq!(unicast.to_frame(), [0x40, 0x36]);
+        }
+
+        #[test]
+        fn test_involves_directed() {
+            let cmd = Cmd::standby(LogicalAddress::Playbackdevice1, LogicalAddress::Tv);
+            assert!(cmd.is_from(LogicalAddress::Playbackdevice1));
+            assert!(!cmd.is_from(LogicalAddress::Tv));
+            assert!(cmd.is_to(LogicalAddress::Tv));
+            assert!(!cmd.is_to(LogicalAddress::Playbackdevice1));
+            assert!(cmd.involves(LogicalAddress::Tv));
+            assert!(cmd.involves(LogicalAddress::Playbackdevice1));
+            assert!(!cmd.involves(LogicalAddress::Audiosystem));
+        }
+
+        #[test]
+        fn test_involves_broadcast() {
+            let cmd = Cmd::standby(LogicalAddress::Playbackdevice1, LogicalAddress::BROADCAST);
+            assert!(cmd.is_to(LogicalAddress::Tv));
+            assert!(cmd.is_to(LogicalAddress::Audiosystem));
+            assert!(cmd.is_to(LogicalAddress::BROADCAST));
+            assert!(cmd.involves(LogicalAddress::Audiosystem));
+            assert!(!cmd.is_from(LogicalAddress::Tv));
+        }
+    }
+
+    #[cfg(test)]
+    mod raw_frame {
+        use super::*;
+
+        #[test]
+        fn test_from_frame() {
+            let command = Cmd::from_frame(&[0x40, 0x04]).unwrap();
+            assert_eq!(command.initiator, LogicalAddress::Playbackdevice1);
+            assert_eq!(command.destination, LogicalAddress::Tv);
+            assert_eq!(command.opcode, Opcode::ImageViewOn);
+            assert!(command.opcode_set);
+            assert!(command.parameters.0.is_empty());
+
+            let command = Cmd::from_frame(&[0x4F, 0x82, 0x20, 0x00]).unwrap();
+            assert_eq!(command.destination, LogicalAddress::Unregistered);
+            assert_eq!(command.opcode, Opcode::ActiveSource);
+            assert_eq!(command.parameters.0.as_slice(), [0x20, 0x00]);
+        }
+
+        #[test]
+        fn test_physical_address() {
+            let command = Cmd::from_frame(&[0x4F, 0x82, 0x20, 0x00]).unwrap();
+            assert_eq!(command.physical_address(), Some(PhysicalAddress(0x2000)));
+            let command = Cmd::from_frame(&[0x0F, 0x86, 0x12]).unwrap();
+            assert_eq!(command.physical_address(), None);
+            let command = Cmd::from_frame(&[0x40, 0x04]).unwrap();
+            assert_eq!(command.physical_address(), None);
+        }
+
+        #[test]
+        fn test_poll() {
+            let command = Cmd::from_frame(&[0x45]).unwrap();
+            assert_eq!(command.destination, LogicalAddress::Audiosystem);
+            assert!(!command.opcode_set);
+            assert_eq!(command.to_frame(), [0x45]);
+        }
+
+        #[test]
+        fn test_to_frame() {
+            let frame = [0x4F, 0x82, 0x20, 0x00];
+            assert_eq!(Cmd::from_frame(&frame).unwrap().to_frame(), frame);
+        }
+
+        #[test]
+        fn test_invalid_frame() {
+            assert_eq!(Cmd::from_frame(&[]).err(), Some(Error::EmptyFrame));
+            assert_eq!(
+                Cmd::from_frame(&[0x40; 17]).err(),
+                Some(Error::FrameTooLong(17, 16))
+            );
+            assert_eq!(
+                Cmd::from_frame(&[0x40, 0xFD]).err(),
+                Some(Error::UnknownOpcode)
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod dry_run {
+        use super::*;
+
+        #[test]
+        fn test_dry_run() {
+            let command = Cmd::new(
+                LogicalAddress::Playbackdevice1,
+                LogicalAddress::Tv,
+                Opcode::ImageViewOn,
+                &[],
+            );
+            assert_eq!(command.dry_run().unwrap(), [0x40, 0x04]);
+
+            let frame = [0x4F, 0x82, 0x20, 0x00];
+            let command = Cmd::from_frame(&frame).unwrap();
+            assert_eq!(command.clone().dry_run().unwrap(), command.to_frame());
+            assert_eq!(command.dry_run().unwrap(), frame);
+        }
+    }
+
     mod device {
         use super::*;
 
@@ -726,12 +1348,352 @@ mod tests {
             assert_eq!(ffi_devices.types[1], DeviceKind::RecordingDevice.repr());
             assert_eq!(ffi_devices.types[2..], [DeviceKind::Reserved.repr(); 3]);
         }
+
+        #[test]
+        fn test_push_up_to_max() {
+            let mut devices = DeviceKinds::new(DeviceKind::PlaybackDevice);
+            for _ in 1..DeviceKinds::MAX {
+                devices.push(DeviceKind::RecordingDevice).unwrap();
+            }
+            assert_eq!(devices.len(), DeviceKinds::MAX);
+            assert_eq!(
+                devices.push(DeviceKind::Tuner),
+                Err(DeviceKindsError::TooMany(DeviceKinds::MAX))
+            );
+            assert!(!devices.contains(DeviceKind::Tuner));
+
+            let ffi_devices: cec_device_type_list = devices.into();
+            assert_eq!(ffi_devices.types[0], DeviceKind::PlaybackDevice.repr());
+            assert_eq!(
+                ffi_devices.types[1..],
+                [DeviceKind::RecordingDevice.repr(); 4]
+            );
+        }
+
+        #[test]
+        fn test_contains() {
+            let mut devices = DeviceKinds::new(DeviceKind::PlaybackDevice);
+            devices.push(DeviceKind::AudioSystem).unwrap();
+            assert!(devices.contains(DeviceKind::PlaybackDevice));
+            assert!(devices.contains(DeviceKind::AudioSystem));
+            assert!(!devices.contains(DeviceKind::Tv));
+            assert_eq!(
+                devices.iter().copied().collect::<Vec<_>>(),
+                [DeviceKind::PlaybackDevice, DeviceKind::AudioSystem]
+            );
+            assert!(!devices.is_empty());
+        }
+    }
+
+    #[cfg(test)]
+    mod log_msg {
+        use super::*;
+
+        fn ffi_log_message(message: &CStr, time: i64) -> cec_log_message {
+            cec_log_message {
+                message: message.as_ptr(),
+                level: LogLevel::Warning.repr(),
+                time,
+            }
+        }
+
+        #[test]
+        fn test_from_ffi() {
+            let message = CString::new("hello").unwrap();
+            let before = SystemTime::now();
+            let log_msg: LogMsg = ffi_log_message(&message, 1500).try_into().unwrap();
+            assert_eq!(log_msg.message, "hello");
+            assert_eq!(log_msg.level, LogLevel::Warning);
+            assert_eq!(log_msg.time, Duration::from_millis(1500));
+            assert!(log_msg.timestamp >= before);
+            assert!(log_msg.timestamp <= SystemTime::now());
+        }
+
+        #[test]
+        fn test_from_ffi_zero_time() {
+            let message = CString::new("hello").unwrap();
+            let log_msg: LogMsg = ffi_log_message(&message, 0).try_into().unwrap();
+            assert_eq!(log_msg.time, Duration::ZERO);
+        }
+
+        #[test]
+        fn test_from_ffi_negative_time() {
+            let message = CString::new("hello").unwrap();
+            let log_msg: Result<LogMsg> = ffi_log_message(&message, -1).try_into();
+            assert_eq!(
+                log_msg.err(),
+                Some(TryFromLogMsgError::TimestampParseError.into())
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod volume_status {
+        use super::*;
+
+        #[test]
+        fn test_from_ffi_volume() {
+            assert_eq!(
+                VolumeStatus::from(42),
+                VolumeStatus {
+                    muted: false,
+                    volume: Some(42)
+                }
+            );
+        }
+
+        #[test]
+        fn test_from_ffi_muted() {
+            assert_eq!(
+                VolumeStatus::from(0x80 | 100),
+                VolumeStatus {
+                    muted: true,
+                    volume: Some(100)
+                }
+            );
+        }
+
+        #[test]
+        fn test_from_ffi_unknown() {
+            assert_eq!(
+                VolumeStatus::from(0x7F),
+                VolumeStatus {
+                    muted: false,
+                    volume: None
+                }
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod osd_name {
+        use super::*;
+
+        fn set_osd_name(name: &[u8]) -> Cmd {
+            Cmd::new(
+                LogicalAddress::Tv,
+                LogicalAddress::Playbackdevice1,
+                Opcode::SetOsdName,
+                name,
+            )
+        }
+
+        #[test]
+        fn test_osd_name() {
+            assert_eq!(set_osd_name(b"LG TV").osd_name().as_deref(), Some("LG TV"));
+            assert_eq!(set_osd_name(b"TV\0\0").osd_name().as_deref(), Some("TV"));
+            assert_eq!(set_osd_name(b"").osd_name().as_deref(), Some(""));
+        }
+
+        #[test]
+        fn test_non_ascii() {
+            assert_eq!(
+                set_osd_name("Télé".as_bytes()).osd_name().as_deref(),
+                Some("T??l??")
+            );
+        }
+
+        #[test]
+        fn test_other_opcode() {
+            let cmd = Cmd::standby(LogicalAddress::Tv, LogicalAddress::Playbackdevice1);
+            assert_eq!(cmd.osd_name(), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod cec_ascii {
+        use super::*;
+
+        #[test]
+        fn test_boundary() {
+            assert_eq!(to_cec_ascii("", OSD_STRING_LEN).unwrap(), "");
+            assert_eq!(
+                to_cec_ascii("thirteen char", OSD_STRING_LEN).unwrap(),
+                "thirteen char"
+            );
+            assert_eq!(
+                to_cec_ascii("fourteen chars", OSD_STRING_LEN).unwrap(),
+                "fourteen char"
+            );
+            assert_eq!(
+                to_cec_ascii("a very long device name", OSD_NAME_LEN).unwrap(),
+                "a very long de"
+            );
+        }
+
+        #[test]
+        fn test_non_ascii() {
+            assert!(matches!(
+                to_cec_ascii("Télé", OSD_NAME_LEN),
+                Err(Error::NonAscii('é'))
+            ));
+            assert!(matches!(
+                to_cec_ascii("tv\n", OSD_NAME_LEN),
+                Err(Error::NonAscii('\n'))
+            ));
+            // Rejected even past where it would be truncated.
+            assert!(matches!(
+                to_cec_ascii("living room tv ✓", OSD_NAME_LEN),
+                Err(Error::NonAscii('✓'))
+            ));
+        }
+
+        #[test]
+        fn test_name() {
+            let cfg = Connection::builder()
+                .name("a very long device name".to_owned())
+                .kind(DeviceKind::RecordingDevice)
+                .build()
+                .unwrap();
+            let ffi_cfg: libcec_configuration = (&cfg).into();
+            let name: Vec<u8> = ffi_cfg.strDeviceName.iter().map(|x| *x as u8).collect();
+            assert_eq!(name, b"a very long de\0");
+
+            assert!(Connection::builder()
+                .name("Télé".to_owned())
+                .kind(DeviceKind::RecordingDevice)
+                .build()
+                .is_err());
+        }
+    }
+
+    #[cfg(test)]
+    mod feature_abort {
+        use super::*;
+
+        fn feature_abort(parameters: &[u8]) -> Cmd {
+            Cmd::new(
+                LogicalAddress::Tv,
+                LogicalAddress::Recordingdevice1,
+                Opcode::FeatureAbort,
+                parameters,
+            )
+        }
+
+        #[test]
+        fn test_feature_abort() {
+            assert_eq!(
+                feature_abort(&[0x72, 0x01]).feature_abort(),
+                Some((
+                    Opcode::SetSystemAudioMode,
+                    AbortReason::NotInCorrectModeToRespond
+                ))
+            );
+            assert_eq!(
+                feature_abort(&[0x44, 0x04]).feature_abort(),
+                Some((Opcode::UserControlPressed, AbortReason::Refused))
+            );
+        }
+
+        #[test]
+        fn test_invalid() {
+            assert_eq!(feature_abort(&[0x72]).feature_abort(), None);
+            assert_eq!(feature_abort(&[0x72, 0x05]).feature_abort(), None);
+            assert_eq!(feature_abort(&[0x01, 0x00]).feature_abort(), None);
+
+            let cmd = Cmd::standby(LogicalAddress::Tv, LogicalAddress::Recordingdevice1);
+            assert_eq!(cmd.feature_abort(), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod cec_version {
+        use super::*;
+
+        #[test]
+        fn test_from_operand() {
+            for (operand, version, display) in [
+                (1, Version::Version12, "1.2"),
+                (2, Version::Version12a, "1.2a"),
+                (3, Version::Version13, "1.3"),
+                (4, Version::Version13a, "1.3a"),
+                (5, Version::Version14, "1.4"),
+                (6, Version::Version20, "2.0"),
+            ] {
+                assert_eq!(Version::from_operand(operand), Some(version));
+                assert_eq!(version.repr() as u8, operand);
+                assert_eq!(version.to_string(), display);
+            }
+            assert_eq!(Version::from_operand(0), None);
+            assert_eq!(Version::from_operand(7), None);
+        }
+
+        #[test]
+        fn test_cmd() {
+            let reply = |parameters: &[u8]| {
+                Cmd::new(
+                    LogicalAddress::Tv,
+                    LogicalAddress::Playbackdevice1,
+                    Opcode::CecVersion,
+                    parameters,
+                )
+                .cec_version()
+            };
+            assert_eq!(reply(&[0x06]), Some(Version::Version20));
+            assert_eq!(reply(&[0xFF]), Some(Version::VersionUnknown));
+            assert_eq!(reply(&[]), Some(Version::VersionUnknown));
+
+            let cmd = Cmd::standby(LogicalAddress::Tv, LogicalAddress::Playbackdevice1);
+            assert_eq!(cmd.cec_version(), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod replies {
+        use super::*;
+
+        #[test]
+        fn test_offer() {
+            let replies = Replies::default();
+            let reply_rx = replies.expect(LogicalAddress::Tv, Opcode::SetOsdName);
+
+            let other = Cmd::new(
+                LogicalAddress::Audiosystem,
+                LogicalAddress::Playbackdevice1,
+                Opcode::SetOsdName,
+                b"AVR",
+            );
+            assert!(!replies.offer(&other));
+
+            let reply = Cmd::new(
+                LogicalAddress::Tv,
+                LogicalAddress::Playbackdevice1,
+                Opcode::SetOsdName,
+                b"TV",
+            );
+            assert!(replies.offer(&reply));
+            assert_eq!(
+                reply_rx
+                    .try_recv()
+                    .ok()
+                    .and_then(|x| x.osd_name())
+                    .as_deref(),
+                Some("TV")
+            );
+            // Each request gets a single reply.
+            assert!(!replies.offer(&reply));
+        }
     }
 
     #[cfg(test)]
     mod keypress {
         use super::*;
 
+        #[test]
+        fn test_display() {
+            let press = Keypress {
+                keycode: UserControlCode::VolumeUp,
+                duration: Duration::ZERO,
+            };
+            assert_eq!(press.to_string(), "VolumeUp pressed");
+
+            let release = Keypress {
+                duration: Duration::from_millis(250),
+                ..press
+            };
+            assert_eq!(release.to_string(), "VolumeUp released after 250ms");
+        }
+
         #[test]
         fn test_keypress_from_ffi_known_code() {
             let keypress: Keypress = cec_keypress {
@@ -753,5 +1715,213 @@ mod tests {
             .try_into();
             assert_eq!(keypress, Err(TryFromKeypressError::UnknownKeycode.into()));
         }
+
+        #[test]
+        fn test_keypress_volume_up_round_trip() {
+            let press = Keypress {
+                keycode: UserControlCode::VolumeUp,
+                duration: Duration::ZERO,
+            };
+            let ffi_command: cec_command = press
+                .to_cmd(
+                    LogicalAddress::Recordingdevice1,
+                    LogicalAddress::Audiosystem,
+                )
+                .into();
+            assert_eq!(ffi_command.opcode, cec_opcode::USER_CONTROL_PRESSED);
+            assert_eq!(ffi_command.parameters.size, 1);
+            assert_eq!(ffi_command.parameters.data[0], 0x41);
+
+            let command: Cmd = ffi_command.try_into().unwrap();
+            let keypress = Keypress::try_from(&command).unwrap();
+            assert_eq!(keypress, press);
+        }
+
+        #[test]
+        fn test_keypress_release_to_cmd() {
+            let release = Keypress {
+                keycode: UserControlCode::VolumeUp,
+                duration: Duration::from_millis(300),
+            };
+            let command = release.to_cmd(
+                LogicalAddress::Recordingdevice1,
+                LogicalAddress::Audiosystem,
+            );
+            assert_eq!(command.opcode, Opcode::UserControlRelease);
+            assert!(command.parameters.0.is_empty());
+            assert_eq!(
+                Keypress::try_from(&command),
+                Err(TryFromKeypressError::NotUserControlPressed.into())
+            );
+        }
+
+        #[test]
+        fn test_keypress_releases() {
+            let press = Keypress {
+                keycode: UserControlCode::VolumeUp,
+                duration: Duration::ZERO,
+            };
+            let release = Keypress {
+                keycode: UserControlCode::VolumeUp,
+                duration: Duration::from_millis(300),
+            };
+            let other = Keypress {
+                keycode: UserControlCode::VolumeDown,
+                ..release
+            };
+            assert!(release.releases(&press));
+            assert!(!press.releases(&release));
+            assert!(!other.releases(&press));
+        }
+
+        #[test]
+        fn test_user_control_code_from_unknown_byte() {
+            assert_eq!(
+                UserControlCode::try_from(0xFE),
+                Err(TryFromKeypressError::UnknownKeycode)
+            );
+        }
+    }
+
+    mod error {
+        use super::*;
+
+        #[test]
+        fn test_flattened() {
+            assert_eq!(
+                Error::from(TryFromCmdError::UnknownOpcode),
+                Error::UnknownOpcode
+            );
+            assert_eq!(
+                Error::from(TryFromLogicalAddressesError::InvalidPrimaryAddress),
+                Error::InvalidPrimaryAddress
+            );
+            assert_eq!(
+                Error::from(CmdError::PayloadTooLong(20, 14)),
+                Error::PayloadTooLong(20, 14)
+            );
+        }
+
+        #[test]
+        fn test_display() {
+            assert_eq!(
+                Error::from(ConnectionError::AdapterBusy).to_string(),
+                "failed to connect: adapter busy, another process may be using it"
+            );
+            assert_eq!(Error::Transmit.to_string(), "failed to transmit command");
+            assert_eq!(
+                Error::Timeout(Duration::from_secs(1)).to_string(),
+                "no reply within 1s"
+            );
+            assert_eq!(
+                Error::from(TryFromLogMsgError::MessageParseError).to_string(),
+                "failed to parse log message"
+            );
+        }
+
+        /// Every public error is a plain `std` error, so callers need no error
+        /// handling crate of their own.
+        #[test]
+        fn test_std_error() {
+            const fn assert_std_error<E: std::error::Error + Send + Sync + 'static>() {}
+            assert_std_error::<Error>();
+            assert_std_error::<ConnectionError>();
+            assert_std_error::<TryFromCmdError>();
+            assert_std_error::<CmdError>();
+            assert_std_error::<TryFromLogMsgError>();
+            assert_std_error::<TryFromLogicalAddressesError>();
+            assert_std_error::<TryFromKeypressError>();
+            assert_std_error::<TryFromAlertError>();
+            assert_std_error::<TryFromMenuStateError>();
+            assert_std_error::<DeviceKindsError>();
+            assert_std_error::<ParsePhysicalAddressError>();
+            assert_std_error::<ParseDataPacketError>();
+            assert_std_error::<CfgBuilderError>();
+
+            let error: Box<dyn std::error::Error> = ConnectionError::AdapterBusy.into();
+            assert_eq!(
+                error.to_string(),
+                "adapter busy, another process may be using it"
+            );
+            let error = Error::from(ConnectionError::AdapterBusy);
+            assert!(std::error::Error::source(&error).is_some());
+        }
+    }
+
+    /// The enums are mapped to libcec's by hand, so check for copy-paste
+    /// mistakes: every value must round-trip and map to a distinct constant.
+    mod repr {
+        use super::*;
+
+        #[test]
+        fn test_user_control_code() {
+            let codes: Vec<_> = (0..=u8::MAX)
+                .filter_map(|x| UserControlCode::try_from(x).ok().map(|code| (x, code)))
+                .collect();
+            assert!(!codes.is_empty());
+            for (value, code) in &codes {
+                assert_eq!(u8::from(*code), *value, "{code:?}");
+                assert_eq!(UserControlCode::from_repr(code.repr()), Some(*code));
+            }
+            let distinct: HashSet<_> = codes.iter().map(|(_, x)| x.repr() as u8).collect();
+            assert_eq!(distinct.len(), codes.len());
+        }
+
+        #[test]
+        fn test_opcode() {
+            let opcodes: Vec<_> = (0..=u8::MAX)
+                .filter_map(|x| Opcode::try_from(x).ok().map(|opcode| (x, opcode)))
+                .collect();
+            assert!(!opcodes.is_empty());
+            for (value, opcode) in &opcodes {
+                assert_eq!(opcode.repr() as u8, *value, "{opcode:?}");
+                assert_eq!(Opcode::from_repr(opcode.repr()), Some(*opcode));
+            }
+            let distinct: HashSet<_> = opcodes.iter().map(|(_, x)| x.repr() as u8).collect();
+            assert_eq!(distinct.len(), opcodes.len());
+        }
+
+        #[test]
+        fn test_logical_address() {
+            let addresses: Vec<_> = (-1..16)
+                .map(|x| LogicalAddress::try_from(x).map(|address| (x, address)))
+                .collect::<std::result::Result<_, _>>()
+                .unwrap();
+            for (value, address) in &addresses {
+                assert_eq!(address.repr() as c_int, *value, "{address:?}");
+                assert_eq!(LogicalAddress::from_repr(address.repr()), Some(*address));
+            }
+            let distinct: HashSet<_> = addresses.iter().map(|(_, x)| x.repr() as c_int).collect();
+            assert_eq!(distinct.len(), addresses.len());
+        }
+    }
+
+    #[cfg(test)]
+    mod system_audio {
+        use super::*;
+
+        #[test]
+        fn test_status() {
+            let reply = |opcode, parameters: &[u8]| {
+                Cmd::new(
+                    LogicalAddress::Audiosystem,
+                    LogicalAddress::Playbackdevice1,
+                    opcode,
+                    parameters,
+                )
+                .system_audio_status()
+            };
+            assert_eq!(
+                reply(Opcode::SystemAudioModeStatus, &[0x01]),
+                Some(SystemAudioStatus::On)
+            );
+            assert_eq!(
+                reply(Opcode::SetSystemAudioMode, &[0x00]),
+                Some(SystemAudioStatus::Off)
+            );
+            assert_eq!(reply(Opcode::SetSystemAudioMode, &[0x02]), None);
+            assert_eq!(reply(Opcode::SetSystemAudioMode, &[]), None);
+            assert_eq!(reply(Opcode::ReportAudioStatus, &[0x01]), None);
+        }
     }
 }
@@ -5,44 +5,184 @@ pub(crate) mod convert;
 pub(crate) mod types;
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
     ffi::{c_int, CStr, CString},
     fmt::{self, Display},
+    ops::RangeInclusive,
     pin::Pin,
     ptr::addr_of_mut,
     result,
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
 };
 
 use arrayvec::ArrayVec;
+pub use cec_sys::LIBCEC_VERSION;
 use cec_sys::*;
 use derive_builder::{Builder, UninitializedFieldError};
+use log::{info, warn};
 
 pub use crate::types::*;
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// The HDMI ports libcec can be told the adapter is connected to.
+pub const HDMI_PORTS: RangeInclusive<u8> = 1..=15;
+
+/// The most characters an OSD name carries, libcec's buffer less its nul.
+pub const OSD_NAME_LEN: usize = LIBCEC_OSD_NAME_SIZE as usize - 1;
+
+/// The most characters an OSD string carries, the 14 byte operand less the
+/// display control byte.
+pub const OSD_STRING_LEN: usize = 13;
+
+/// Every failure the crate can return, flattened so callers can `match` on the
+/// cause directly. The narrower `TryFrom*Error` types convert into this.
 #[derive(Debug, PartialEq, thiserror::Error)]
 pub enum Error {
-    #[error("failed to convert cmd: {0}")]
-    TryFromCmdError(#[from] TryFromCmdError),
-    #[error("failed to convert log msg: {0}")]
-    TryFromLogMsgError(#[from] TryFromLogMsgError),
-    #[error("failed to convert logical address: {0}")]
-    TryFromLogicalAddressesError(#[from] TryFromLogicalAddressesError),
-    #[error("failed to convert keypress: {0}")]
-    TryFromKeypressError(#[from] TryFromKeypressError),
-    #[error("failed to convert alert: {0}")]
-    TryFromAlertError(#[from] TryFromAlertError),
-    #[error("failed to convert menu state: {0}")]
-    TryFromMenuStateError(#[from] TryFromMenuStateError),
+    /// A command carried an opcode libcec doesn't know.
+    #[error("unknown opcode")]
+    UnknownOpcode,
+    /// A command's initiator wasn't a known logical address.
+    #[error("unknown command initiator")]
+    UnknownInitiator,
+    /// A command's destination wasn't a known logical address.
+    #[error("unknown command destination")]
+    UnknownDestination,
+    /// A command's payload doesn't fit in a packet.
+    #[error("payload is {0} bytes, at most {1} fit in a packet")]
+    PayloadTooLong(usize, usize),
+    /// A raw frame had no header byte.
+    #[error("frame is empty, expected at least a header byte")]
+    EmptyFrame,
+    /// A raw frame is longer than the bus allows.
+    #[error("frame is {0} bytes, at most {1} fit on the bus")]
+    FrameTooLong(usize, usize),
+    /// A log message wasn't valid UTF-8.
+    #[error("failed to parse log message")]
+    MessageParseError,
+    /// A log message's level couldn't be read.
+    #[error("failed to parse log level")]
+    LogLevelParseError,
+    /// A log message's timestamp was out of range.
+    #[error("failed to parse log timestamp")]
+    TimestampParseError,
+    /// A log message had a level libcec doesn't document.
+    #[error("unknown log level")]
+    UnknownLogLevel,
+    /// A logical address list's primary address wasn't known.
+    #[error("unknown primary address")]
+    UnknownPrimaryAddress,
+    /// A logical address list's primary address was unregistered.
+    #[error("invalid primary address")]
+    InvalidPrimaryAddress,
+    /// A keypress carried a keycode libcec doesn't know.
+    #[error("unknown keycode")]
+    UnknownKeycode,
+    /// A command expected to be a keypress was something else.
+    #[error("not a user control pressed command")]
+    NotUserControlPressed,
+    /// An alert libcec doesn't document.
+    #[error("unknown alert")]
+    UnknownAlert,
+    /// A menu state libcec doesn't document.
+    #[error("unknown menu state")]
+    UnknownMenuState,
+    /// Opening the connection to the adapter failed.
     #[error("failed to connect: {0}")]
-    ConnectionError(#[from] ConnectionError),
-    #[error("builder error: {0}")]
-    BuilderError(#[from] CfgBuilderError),
-    #[error("nul byte found: {0}")]
-    NulError(#[from] std::ffi::NulError),
+    Connect(#[from] ConnectionError),
+    /// libcec couldn't deliver a command, or a reply was malformed.
+    #[error("failed to transmit command")]
+    Transmit,
+    /// A query was delivered, but no reply came in time, see
+    /// [`Connection::request`].
+    #[error("no reply within {0:?}")]
+    Timeout(Duration),
+    /// The connection config was incomplete or invalid.
+    #[error("invalid config: {0}")]
+    Builder(#[from] CfgBuilderError),
+    /// A string passed to libcec contained a nul byte.
+    #[error("string contains a nul byte: {0}")]
+    Nul(#[from] std::ffi::NulError),
+    /// An HDMI port outside [`HDMI_PORTS`].
+    #[error("hdmi port {0} is out of range, expected 1 to 15")]
+    InvalidHdmiPort(u8),
+    /// A string sent on the bus had a character HDMI-CEC can't carry, see
+    /// [`to_cec_ascii`].
+    #[error("{0:?} isn't printable ascii")]
+    NonAscii(char),
+    /// The adapter didn't answer a ping, like when it was unplugged.
+    #[error("adapter didn't respond")]
+    AdapterUnresponsive,
+}
+
+impl From<TryFromCmdError> for Error {
+    fn from(e: TryFromCmdError) -> Self {
+        match e {
+            TryFromCmdError::UnknownOpcode => Self::UnknownOpcode,
+            TryFromCmdError::UnknownInitiator => Self::UnknownInitiator,
+            TryFromCmdError::UnknownDestination => Self::UnknownDestination,
+        }
+    }
+}
+
+impl From<CmdError> for Error {
+    fn from(e: CmdError) -> Self {
+        match e {
+            CmdError::PayloadTooLong(len, max) => Self::PayloadTooLong(len, max),
+            CmdError::EmptyFrame => Self::EmptyFrame,
+            CmdError::FrameTooLong(len, max) => Self::FrameTooLong(len, max),
+        }
+    }
+}
+
+impl From<TryFromLogMsgError> for Error {
+    fn from(e: TryFromLogMsgError) -> Self {
+        match e {
+            TryFromLogMsgError::MessageParseError => Self::MessageParseError,
+            TryFromLogMsgError::LogLevelParseError => Self::LogLevelParseError,
+            TryFromLogMsgError::TimestampParseError => Self::TimestampParseError,
+            TryFromLogMsgError::UnknownLogLevel => Self::UnknownLogLevel,
+        }
+    }
+}
+
+impl From<TryFromLogicalAddressesError> for Error {
+    fn from(e: TryFromLogicalAddressesError) -> Self {
+        match e {
+            TryFromLogicalAddressesError::UnknownPrimaryAddress => Self::UnknownPrimaryAddress,
+            TryFromLogicalAddressesError::InvalidPrimaryAddress => Self::InvalidPrimaryAddress,
+        }
+    }
+}
+
+impl From<TryFromKeypressError> for Error {
+    fn from(e: TryFromKeypressError) -> Self {
+        match e {
+            TryFromKeypressError::UnknownKeycode => Self::UnknownKeycode,
+            TryFromKeypressError::NotUserControlPressed => Self::NotUserControlPressed,
+        }
+    }
+}
+
+impl From<TryFromAlertError> for Error {
+    fn from(e: TryFromAlertError) -> Self {
+        match e {
+            TryFromAlertError::UnknownAlert => Self::UnknownAlert,
+        }
+    }
+}
+
+impl From<TryFromMenuStateError> for Error {
+    fn from(e: TryFromMenuStateError) -> Self {
+        match e {
+            TryFromMenuStateError::UnknownMenuState => Self::UnknownMenuState,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
@@ -53,10 +193,10 @@ pub enum ConnectionError {
     NoAdapterFound,
     #[error("failed to open adapter")]
     AdapterOpenFailed,
+    #[error("adapter busy, another process may be using it")]
+    AdapterBusy,
     #[error("callback registration failed")]
     CallbackRegistrationFailed,
-    #[error("transmit failed")]
-    TransmitFailed,
     #[error("device missing")]
     DeviceMissing,
     #[error("ffi error: {0}")]
@@ -73,6 +213,16 @@ pub enum TryFromCmdError {
     UnknownDestination,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CmdError {
+    #[error("payload is {0} bytes, at most {1} fit in a packet")]
+    PayloadTooLong(usize, usize),
+    #[error("frame is empty, expected at least a header byte")]
+    EmptyFrame,
+    #[error("frame is {0} bytes, at most {1} fit on the bus")]
+    FrameTooLong(usize, usize),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum TryFromLogMsgError {
     #[error("message parse error")]
@@ -97,6 +247,8 @@ pub enum TryFromLogicalAddressesError {
 pub enum TryFromKeypressError {
     #[error("unknown keycode")]
     UnknownKeycode,
+    #[error("not a user control pressed cmd")]
+    NotUserControlPressed,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
@@ -111,6 +263,26 @@ pub enum TryFromMenuStateError {
     UnknownMenuState,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DeviceKindsError {
+    #[error("at most {0} device kinds are supported")]
+    TooMany(usize),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParsePhysicalAddressError {
+    #[error("expected four dot separated ports from 0 to f, like `1.0.0.0`")]
+    InvalidFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseDataPacketError {
+    #[error("invalid byte `{0}`, expected two hex digits like `0f`")]
+    InvalidByte(String),
+    #[error("packet is {0} bytes long, at most {1} are allowed")]
+    TooLong(usize, usize),
+}
+
 #[derive(Debug, Eq, PartialEq, thiserror::Error)]
 #[non_exhaustive]
 pub enum CfgBuilderError {
@@ -120,20 +292,29 @@ pub enum CfgBuilderError {
     ValidationError(String),
 }
 
-/// CecLogicalAddress which does not allow Unknown variant
+/// `CecLogicalAddress` which does not allow Unknown variant. The broadcast
+/// address is known, see [`LogicalAddress::BROADCAST`].
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct KnownLogicalAddress(types::LogicalAddress);
 
-/// CecLogicalAddress which does not allow Unknown and Unregistered variants
+/// `CecLogicalAddress` which does not allow Unknown and Unregistered variants,
+/// so it's never the broadcast address: no device registers as it.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct RegisteredLogicalAddress(LogicalAddress);
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct UnregisteredLogicalAddress {}
 
+/// The bytes of a HDMI-CEC message's parameters. Written as space separated
+/// hex bytes, like `40 04`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DataPacket(pub ArrayVec<u8, 64>);
 
+/// A HDMI physical address, like `1.0.0.0`. Each nibble is the input port
+/// along the path from the TV.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PhysicalAddress(pub u16);
+
 #[derive(Debug, Clone)]
 pub struct Cmd {
     /// The logical address of the initiator of this message.
@@ -160,8 +341,12 @@ pub struct LogMsg {
     pub message: String,
     /// Log level of the message.
     pub level: LogLevel,
-    /// Duration since connection was established.
+    /// Duration since libcec was initialised. libcec doesn't report a
+    /// wall-clock time, see [`LogMsg::timestamp`] for that.
     pub time: Duration,
+    /// Wall-clock time the message was logged. libcec invokes the log
+    /// callback as it logs, so this is taken when the message is converted.
+    pub timestamp: SystemTime,
 }
 
 /// Collection of logical addresses, with one primary address
@@ -179,10 +364,58 @@ pub struct Keypress {
     pub duration: Duration,
 }
 
+/// An adapter libcec found, which a connection can be opened to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adapter {
+    /// The com port a connection opens, like `/dev/ttyACM0` or `COM3`.
+    pub port: String,
+    /// Where the adapter sits in the OS's device tree.
+    pub path: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub firmware_version: u16,
+    pub kind: AdapterType,
+}
+
+/// A device found on the HDMI-CEC bus.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct DeviceKinds(pub ArrayVec<DeviceKind, 5>);
+pub struct DeviceInfo {
+    /// The device's logical address.
+    pub address: LogicalAddress,
+    /// The device's physical address, [`PhysicalAddress::UNKNOWN`] if it
+    /// didn't report one.
+    pub physical_address: PhysicalAddress,
+    /// The device's vendor, `None` if it didn't report one libcec knows.
+    pub vendor_id: Option<VendorId>,
+    /// The device's OSD name, empty if it didn't report one.
+    pub name: String,
+    /// The device's power status.
+    pub power_status: PowerStatus,
+}
+
+/// The audio status reported by an audio system.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct VolumeStatus {
+    /// Whether the audio is muted.
+    pub muted: bool,
+    /// The volume, from 0 to 100. `None` if the audio system didn't report it.
+    pub volume: Option<u8>,
+}
 
-#[derive(derive_more::Debug)]
+/// Whether an active source broadcast took effect.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ActiveSourceStatus {
+    /// The broadcast was transmitted.
+    Acknowledged,
+    /// The broadcast failed to transmit, e.g. the TV missed it while waking
+    /// or another device was talking.
+    NotAcknowledged,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceKinds(pub ArrayVec<DeviceKind, { DeviceKinds::MAX }>);
+
+#[derive(derive_more::Debug, Default)]
 pub struct Callbacks {
     #[debug(skip)]
     pub on_key_press: Option<Box<OnKeyPress>>,
@@ -227,7 +460,12 @@ static mut CALLBACKS: ICECCallbacks = ICECCallbacks {
 #[derive(Builder, derive_more::Debug)]
 #[builder(
     pattern = "owned",
-    build_fn(private, name = "build", error = "CfgBuilderError")
+    build_fn(
+        private,
+        name = "build",
+        error = "CfgBuilderError",
+        validate = "Self::validate"
+    )
 )]
 pub struct Cfg {
     #[debug(skip)]
@@ -267,6 +505,14 @@ pub struct Cfg {
     #[builder(default = "Duration::from_secs(5)")]
     timeout: Duration,
 
+    /// The transmit timeout for cmds built by the connection, like
+    /// [`Connection::send_vendor_command`]. libcec's helpers, like
+    /// [`Connection::send_keypress`], [`Connection::send_power_on_devices`] and
+    /// [`Connection::send_standby_devices`] to a single device, use libcec's
+    /// own timeout.
+    #[builder(default = "Cmd::DEFAULT_TRANSMIT_TIMEOUT")]
+    transmit_timeout: Duration,
+
     //
     // cec_configuration items follow up
     name: String,
@@ -275,9 +521,17 @@ pub struct Cfg {
     kind: DeviceKind,
 
     // optional cec_configuration items follow
-    ///< the physical address of the CEC adapter.
+    /// Forces the physical address of the CEC adapter, for switches which
+    /// confuse autodetection. Mutually exclusive with
+    /// `detect_physical_address(true)`.
+    #[builder(default, setter(strip_option))]
+    physical_address: Option<PhysicalAddress>,
+
+    /// Whether libcec detects the physical address, rather than deriving it
+    /// from `base_device` and `hdmi_port`. Mutually exclusive with
+    /// `physical_address`.
     #[builder(default, setter(strip_option))]
-    physical_address: Option<u16>,
+    detect_physical_address: Option<bool>,
 
     ///< the logical address of the device to which the adapter is connected.
     /// only used when iPhysicalAddress = 0 or when the adapter doesn't support
@@ -362,59 +616,337 @@ pub struct Cfg {
 }
 
 impl CfgBuilder {
+    fn validate(&self) -> result::Result<(), String> {
+        if let (Some(Some(_)), Some(Some(true))) =
+            (&self.physical_address, &self.detect_physical_address)
+        {
+            return Err(
+                "`physical_address` and `detect_physical_address(true)` are mutually exclusive"
+                    .to_owned(),
+            );
+        }
+        if let Some(name) = &self.name {
+            to_cec_ascii(name, OSD_NAME_LEN).map_err(|e| format!("invalid `name`: {e}"))?;
+        }
+
+        Ok(())
+    }
+
     pub fn connect(self) -> Result<Connection> {
         let cfg = self.build()?;
         cfg.connect()
     }
+
+    /// Lists the adapters libcec finds, see [`Cfg::adapters`].
+    pub fn adapters(self) -> Result<Vec<Adapter>> {
+        let cfg = self.build()?;
+        cfg.adapters()
+    }
+
+    /// Applies this configuration to an open connection, see
+    /// [`Connection::reconfigure`].
+    pub fn reconfigure(self, connection: Connection) -> Result<Connection> {
+        let cfg = self.build()?;
+        connection.reconfigure(cfg)
+    }
 }
 
 #[derive(Debug)]
-pub struct Connection(pub Cfg, pub libcec_connection_t, pub Pin<Box<Callbacks>>);
+pub struct Connection(
+    pub Cfg,
+    pub libcec_connection_t,
+    pub Pin<Box<Callbacks>>,
+    Replies,
+    Versions,
+);
 unsafe impl Send for Connection {}
 
+/// The replies awaited by [`Connection::request`], handed over as cmds are
+/// received.
+#[derive(Debug, Clone, Default)]
+struct Replies(Arc<Mutex<Vec<Reply>>>);
+
+/// Who a reply is awaited from, its opcode, and where to hand it over.
+type Reply = (LogicalAddress, Opcode, mpsc::Sender<Cmd>);
+
+/// The CEC versions devices reported to [`Connection::cec_version`], which
+/// don't change while connected.
+#[derive(Debug, Default)]
+struct Versions(Mutex<HashMap<LogicalAddress, Version>>);
+
 impl Connection {
+    /// How long [`Connection::request_osd_name`] and
+    /// [`Connection::cec_version`] wait for a reply.
+    pub const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
     pub fn builder() -> CfgBuilder {
         CfgBuilder::default()
     }
 
+    /// Applies a new configuration without closing the connection, letting the
+    /// device name, HDMI port, wake and power off devices, etc. change live.
+    /// The existing callbacks are kept, those in `cfg` only take effect if the
+    /// connection is reopened.
+    ///
+    /// If libcec rejects the configuration, the connection is reopened with
+    /// it instead.
+    pub fn reconfigure(mut self, cfg: Cfg) -> Result<Self> {
+        let mut ffi_cfg: libcec_configuration = (&cfg).into();
+        ffi_cfg.callbacks = addr_of_mut!(CALLBACKS);
+        ffi_cfg.callbackParam = std::ptr::from_ref(&*self.2) as *mut _;
+
+        if unsafe { libcec_set_configuration(self.1, &ffi_cfg) } != 0 {
+            self.0 = cfg;
+            return Ok(self);
+        }
+
+        // The adapter can only be opened once, so close it before reopening.
+        drop(self);
+        cfg.connect()
+    }
+
+    /// Closes the adapter and opens it again, like after it was unplugged and
+    /// plugged back in. The callbacks are kept, and the adapter is detected
+    /// again unless a device was configured.
+    pub fn reopen(&self) -> Result<()> {
+        unsafe { libcec_close(self.1) };
+
+        let device = match (self.0.detect_device, &self.0.device) {
+            (Some(true), _) => Cfg::detect_device(self)?,
+            (_, Some(x)) => CString::new(x.as_str())?,
+            (_, None) => return Err(ConnectionError::DeviceMissing.into()),
+        };
+        let open_timeout = self.0.timeout.as_millis() as u32;
+        if unsafe { libcec_open(self.1, device.as_ptr(), open_timeout) } == 0 {
+            return Err(ConnectionError::AdapterOpenFailed.into());
+        }
+
+        let callback_ret = unsafe {
+            cec_sys::libcec_set_callbacks(
+                self.1,
+                addr_of_mut!(CALLBACKS),
+                std::ptr::from_ref(&*self.2) as *mut _,
+            )
+        };
+        if callback_ret == 0 {
+            return Err(ConnectionError::CallbackRegistrationFailed.into());
+        }
+
+        Ok(())
+    }
+
     pub fn transmit(&self, command: Cmd) -> Result<()> {
         if unsafe { libcec_transmit(self.1, &command.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
     }
+
+    /// Sends `query` to `destination`, then waits up to `timeout` for
+    /// `destination` to answer with `reply`, failing with [`Error::Timeout`]
+    /// if it doesn't.
+    pub fn request(
+        &self,
+        destination: LogicalAddress,
+        query: Opcode,
+        reply: Opcode,
+        timeout: Duration,
+    ) -> Result<Cmd> {
+        let reply_rx = self.3.expect(destination, reply);
+        self.send(destination, query, &[])?;
+        reply_rx
+            .recv_timeout(timeout)
+            .map_err(|_| Error::Timeout(timeout))
+    }
+
+    /// Asks `address` for its OSD name, see [`Cmd::osd_name`].
+    pub fn request_osd_name(&self, address: LogicalAddress) -> Result<String> {
+        let reply = self.request(
+            address,
+            Opcode::GiveOsdName,
+            Opcode::SetOsdName,
+            Self::REPLY_TIMEOUT,
+        )?;
+        Ok(reply.osd_name().unwrap_or_default())
+    }
+
+    /// Returns the CEC version `address` reports, asking it only the first
+    /// time. Features like ARC depend on it, so it explains why they don't
+    /// work on older TVs.
+    pub fn cec_version(&self, address: LogicalAddress) -> Result<Version> {
+        if let Some(version) = self.4.get(address) {
+            return Ok(version);
+        }
+
+        let reply = self.request(
+            address,
+            Opcode::GetCecVersion,
+            Opcode::CecVersion,
+            Self::REPLY_TIMEOUT,
+        )?;
+        let version = reply.cec_version().unwrap_or(Version::VersionUnknown);
+        self.4.insert(address, version);
+        Ok(version)
+    }
+
+    /// Asks the audio system whether system audio mode is on, see
+    /// [`Cmd::system_audio_status`].
+    pub fn request_system_audio_mode_status(&self) -> Result<SystemAudioStatus> {
+        let reply = self.request(
+            LogicalAddress::Audiosystem,
+            Opcode::GiveSystemAudioModeStatus,
+            Opcode::SystemAudioModeStatus,
+            Self::REPLY_TIMEOUT,
+        )?;
+        reply.system_audio_status().ok_or(Error::Transmit)
+    }
+
+    /// Asks the audio system to turn system audio mode on or off, returning
+    /// the mode it then announces. Many audio systems ignore volume keys
+    /// unless it's on.
+    ///
+    /// See: HDMI-CEC 1.4, section 13.15.
+    pub fn set_system_audio_mode(&self, on: bool) -> Result<SystemAudioStatus> {
+        // The request carries the source's physical address to turn the mode
+        // on, and nothing to turn it off.
+        let parameters = if on {
+            let primary = self.get_logical_addresses()?.primary.into();
+            self.get_device_physical_address(primary)
+                .to_be_bytes()
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+
+        let reply_rx = self
+            .3
+            .expect(LogicalAddress::Audiosystem, Opcode::SetSystemAudioMode);
+        self.send(
+            LogicalAddress::Audiosystem,
+            Opcode::SystemAudioModeRequest,
+            &parameters,
+        )?;
+        let reply = reply_rx
+            .recv_timeout(Self::REPLY_TIMEOUT)
+            .map_err(|_| Error::Timeout(Self::REPLY_TIMEOUT))?;
+        reply.system_audio_status().ok_or(Error::Transmit)
+    }
+
+    /// Sends a vendor specific command to `destination`. If `vendor_id` is
+    /// given, [`Opcode::VendorCommandWithId`] is sent with the id prefixed to
+    /// the payload, otherwise [`Opcode::VendorCommand`] is sent.
+    pub fn send_vendor_command(
+        &self,
+        destination: LogicalAddress,
+        vendor_id: Option<VendorId>,
+        payload: &[u8],
+    ) -> Result<()> {
+        let initiator = self.get_logical_addresses()?.primary.into();
+        let command = Cmd::vendor_command(initiator, destination, vendor_id, payload)?;
+        self.transmit(command.with_transmit_timeout(self.transmit_timeout()))
+    }
+
+    /// Sends `opcode` with `parameters` to `destination`, from the primary
+    /// logical address.
+    pub fn send(
+        &self,
+        destination: LogicalAddress,
+        opcode: Opcode,
+        parameters: &[u8],
+    ) -> Result<()> {
+        let initiator = self.get_logical_addresses()?.primary.into();
+        let command = Cmd::new(initiator, destination, opcode, parameters);
+        self.transmit(command.with_transmit_timeout(self.transmit_timeout()))
+    }
+
+    /// Sends a raw frame, see [`Cmd::from_frame`] for its layout. Useful for
+    /// opcodes without a typed wrapper.
+    pub fn transmit_raw(&self, frame: &[u8]) -> Result<()> {
+        let command = Cmd::from_frame(frame)?;
+        self.transmit(command.with_transmit_timeout(self.transmit_timeout()))
+    }
+
+    /// Returns the transmit timeout for cmds built by the connection.
+    pub const fn transmit_timeout(&self) -> Duration {
+        self.0.transmit_timeout
+    }
+
+    /// Powers on the device at `address`. libcec builds and sends the frames,
+    /// so [`Connection::transmit_timeout`] doesn't apply.
     pub fn send_power_on_devices(&self, address: LogicalAddress) -> Result<()> {
         if unsafe { libcec_power_on_devices(self.1, address.repr()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
     }
+    /// Puts the device at `address` in standby. A broadcast address sends
+    /// `<Standby>` to every device on the bus, rather than to the devices in
+    /// libcec's power off list, which is what libcec does for a broadcast.
+    /// Only the broadcast uses [`Connection::transmit_timeout`], libcec sends
+    /// the rest.
     pub fn send_standby_devices(&self, address: LogicalAddress) -> Result<()> {
+        if address == LogicalAddress::Unknown {
+            return Err(Error::UnknownDestination);
+        }
+        if address.is_broadcast() {
+            let initiator = self.get_logical_addresses()?.primary.into();
+            let command = Cmd::standby(initiator, address);
+            return self.transmit(command.with_transmit_timeout(self.transmit_timeout()));
+        }
+
         if unsafe { libcec_standby_devices(self.1, address.repr()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
     }
 
-    pub fn set_active_source(&self, device_type: DeviceKind) -> Result<()> {
-        if unsafe { libcec_set_active_source(self.1, device_type.repr()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+    /// Asks the TV to switch to the device at `address`.
+    pub fn set_stream_path(&self, address: PhysicalAddress) -> Result<()> {
+        if unsafe { libcec_set_stream_path_physical(self.1, address.into()) } == 0 {
+            Err(Error::Transmit)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Changes the port of `base_device` the adapter is connected to on a live
+    /// connection, libcec deriving the physical address from it again.
+    pub fn set_hdmi_port(&self, base_device: LogicalAddress, port: u8) -> Result<()> {
+        if !HDMI_PORTS.contains(&port) {
+            return Err(Error::InvalidHdmiPort(port));
+        }
+
+        if unsafe { libcec_set_hdmi_port(self.1, base_device.repr(), port) } == 0 {
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
     }
 
-    pub fn get_active_source(&self) -> LogicalAddress {
+    /// Broadcasts that libcec is the active source, reporting whether the
+    /// broadcast went out. libcec marks itself active before transmitting, so
+    /// only the transmit result tells whether the bus took it.
+    pub fn set_active_source(&self, device_type: DeviceKind) -> Result<ActiveSourceStatus> {
+        if unsafe { libcec_set_active_source(self.1, device_type.repr()) } == 0 {
+            Ok(ActiveSourceStatus::NotAcknowledged)
+        } else {
+            Ok(ActiveSourceStatus::Acknowledged)
+        }
+    }
+
+    /// Returns the device libcec last saw become the active source, `None` if
+    /// it doesn't know.
+    pub fn get_active_source(&self) -> Option<LogicalAddress> {
         let active_raw: cec_logical_address = unsafe { libcec_get_active_source(self.1) };
-        LogicalAddress::from_repr(active_raw).unwrap()
+        LogicalAddress::from_repr(active_raw).filter(|x| *x != LogicalAddress::Unknown)
     }
 
     pub fn is_active_source(&self, address: LogicalAddress) -> Result<()> {
         if unsafe { libcec_is_active_source(self.1, address.repr()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -427,6 +959,8 @@ impl Connection {
         PowerStatus::from_repr(status_raw).unwrap()
     }
 
+    /// Presses `key` on the device at `address`. libcec builds and sends the
+    /// frame, so [`Connection::transmit_timeout`] doesn't apply.
     pub fn send_keypress(
         &self,
         address: LogicalAddress,
@@ -434,7 +968,7 @@ impl Connection {
         wait: bool,
     ) -> Result<()> {
         if unsafe { libcec_send_keypress(self.1, address.repr(), key.repr(), wait.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -442,7 +976,7 @@ impl Connection {
 
     pub fn send_key_release(&self, address: LogicalAddress, wait: bool) -> Result<()> {
         if unsafe { libcec_send_key_release(self.1, address.repr(), wait.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -450,7 +984,7 @@ impl Connection {
 
     pub fn volume_up(&self, send_release: bool) -> Result<()> {
         if unsafe { libcec_volume_up(self.1, send_release.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -458,7 +992,7 @@ impl Connection {
 
     pub fn volume_down(&self, send_release: bool) -> Result<()> {
         if unsafe { libcec_volume_down(self.1, send_release.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -466,7 +1000,7 @@ impl Connection {
 
     pub fn mute_audio(&self, send_release: bool) -> Result<()> {
         if unsafe { libcec_mute_audio(self.1, send_release.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -474,39 +1008,56 @@ impl Connection {
 
     pub fn audio_toggle_mute(&self) -> Result<()> {
         if unsafe { libcec_audio_toggle_mute(self.1) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
     }
 
-    pub fn audio_mute(&self) -> Result<()> {
-        if unsafe { libcec_audio_mute(self.1) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
-        } else {
-            Ok(())
+    /// Mutes the audio system, returning the resulting audio status.
+    ///
+    /// Not all audio systems honor discrete mute, some ignore it or treat it
+    /// as a toggle. Check [`VolumeStatus::muted`] and fall back to
+    /// [`Connection::audio_toggle_mute`] if it doesn't match.
+    pub fn audio_mute(&self) -> Result<VolumeStatus> {
+        let status = VolumeStatus::from(unsafe { libcec_audio_mute(self.1) });
+        match status.volume {
+            Some(_) => Ok(status),
+            None => Err(Error::Transmit),
         }
     }
 
-    pub fn audio_unmute(&self) -> Result<()> {
-        if unsafe { libcec_audio_unmute(self.1) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
-        } else {
-            Ok(())
+    /// Unmutes the audio system, returning the resulting audio status. See
+    /// [`Connection::audio_mute`] for caveats.
+    pub fn audio_unmute(&self) -> Result<VolumeStatus> {
+        let status = VolumeStatus::from(unsafe { libcec_audio_unmute(self.1) });
+        match status.volume {
+            Some(_) => Ok(status),
+            None => Err(Error::Transmit),
         }
     }
 
     pub fn audio_get_status(&self) -> Result<()> {
         if unsafe { libcec_audio_get_status(self.1) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
     }
 
+    /// Requests the audio status from the audio system. This goes over the bus
+    /// every call.
+    pub fn volume_status(&self) -> Result<VolumeStatus> {
+        let status = VolumeStatus::from(unsafe { libcec_audio_get_status(self.1) });
+        match status.volume {
+            Some(_) => Ok(status),
+            None => Err(Error::Transmit),
+        }
+    }
+
     pub fn set_inactive_view(&self) -> Result<()> {
         if unsafe { libcec_set_inactive_view(self.1) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -514,7 +1065,7 @@ impl Connection {
 
     pub fn set_logical_address(&self, address: LogicalAddress) -> Result<()> {
         if unsafe { libcec_set_logical_address(self.1, address.repr()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -522,7 +1073,7 @@ impl Connection {
 
     pub fn switch_monitoring(&self, enable: bool) -> Result<()> {
         if unsafe { libcec_switch_monitoring(self.1, enable.into()) } == 0 {
-            Err(ConnectionError::TransmitFailed.into())
+            Err(Error::Transmit)
         } else {
             Ok(())
         }
@@ -532,6 +1083,98 @@ impl Connection {
         LogicalAddresses::try_from(unsafe { libcec_get_logical_addresses(self.1) })
     }
 
+    /// Polls `address`, returning whether a device there acknowledged. This
+    /// goes over the bus every call.
+    pub fn poll_device(&self, address: LogicalAddress) -> bool {
+        unsafe { libcec_poll_device(self.1, address.repr()) != 0 }
+    }
+
+    /// Pings the adapter, checking the link to it is alive. Unlike
+    /// [`Connection::poll_device`], nothing is sent on the bus, so it succeeds
+    /// even if no device responds.
+    pub fn ping_adapter(&self) -> Result<()> {
+        if unsafe { libcec_ping_adapters(self.1) } == 0 {
+            Err(Error::AdapterUnresponsive)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the logical addresses of the devices libcec has seen on the
+    /// bus.
+    pub fn get_active_devices(&self) -> Result<LogicalAddresses> {
+        LogicalAddresses::try_from(unsafe { libcec_get_active_devices(self.1) })
+    }
+
+    pub fn get_device_physical_address(&self, address: LogicalAddress) -> PhysicalAddress {
+        PhysicalAddress(unsafe { libcec_get_device_physical_address(self.1, address.repr()) })
+    }
+
+    /// Returns the device's vendor, `None` if it's unknown to libcec.
+    pub fn get_device_vendor_id(&self, address: LogicalAddress) -> Option<VendorId> {
+        VendorId::from_id(unsafe { libcec_get_device_vendor_id(self.1, address.repr()) })
+    }
+
+    /// Shows `message` on the display at `address` for `duration`, truncated
+    /// to [`OSD_STRING_LEN`] characters, see [`to_cec_ascii`].
+    pub fn set_osd_string(
+        &self,
+        address: LogicalAddress,
+        duration: DisplayControl,
+        message: &str,
+    ) -> Result<()> {
+        let message = CString::new(to_cec_ascii(message, OSD_STRING_LEN)?)?;
+        if unsafe {
+            libcec_set_osd_string(self.1, address.repr(), duration.repr(), message.as_ptr())
+        } == 0
+        {
+            Err(Error::Transmit)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get_device_osd_name(&self, address: LogicalAddress) -> Result<String> {
+        let mut name: cec_osd_name = [0; 14];
+        if unsafe { libcec_get_device_osd_name(self.1, address.repr(), name.as_mut_ptr()) } == 0 {
+            return Err(Error::Transmit);
+        }
+
+        // The name isn't nul terminated if it's the full 14 characters.
+        // `c_char` is signed on some targets, the bytes are kept as they are.
+        let name = name.map(|x| x.to_ne_bytes()[0]);
+        let len = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+        Ok(String::from_utf8_lossy(&name[..len]).into_owned())
+    }
+
+    /// Polls every active device for its details, ordered by logical address.
+    /// Devices which don't report a detail are still returned, see
+    /// [`DeviceInfo`].
+    pub fn scan(&self) -> Result<Vec<DeviceInfo>> {
+        let mut addresses: Vec<LogicalAddress> = self
+            .get_active_devices()?
+            .addresses
+            .into_iter()
+            .map(LogicalAddress::from)
+            .collect();
+        addresses.sort_by_key(|x| x.repr() as c_int);
+
+        Ok(addresses
+            .into_iter()
+            .map(|address| DeviceInfo {
+                address,
+                physical_address: self.get_device_physical_address(address),
+                vendor_id: self.get_device_vendor_id(address),
+                // libcec's cached name is often blank, so ask the device.
+                name: self
+                    .request_osd_name(address)
+                    .or_else(|_| self.get_device_osd_name(address))
+                    .unwrap_or_default(),
+                power_status: self.get_device_power_status(address),
+            })
+            .collect())
+    }
+
     // Unimplemented:
     // extern DECLSPEC int libcec_set_physical_address(libcec_connection_t
     // connection, uint16_t iPhysicalAddress); extern DECLSPEC int
@@ -548,24 +1191,14 @@ impl Connection {
     // CEC_NAMESPACE cec_logical_address iLogicalAddress); extern DECLSPEC int
     // libcec_get_device_menu_language(libcec_connection_t connection, CEC_NAMESPACE
     // cec_logical_address iLogicalAddress, CEC_NAMESPACE cec_menu_language
-    // language); extern DECLSPEC uint32_t
-    // libcec_get_device_vendor_id(libcec_connection_t connection, CEC_NAMESPACE
-    // cec_logical_address iLogicalAddress); extern DECLSPEC uint16_t
-    // libcec_get_device_physical_address(libcec_connection_t connection,
-    // CEC_NAMESPACE cec_logical_address iLogicalAddress); extern DECLSPEC int
-    // libcec_poll_device(libcec_connection_t connection, CEC_NAMESPACE
-    // cec_logical_address iLogicalAddress); extern DECLSPEC CEC_NAMESPACE
-    // cec_logical_addresses libcec_get_active_devices(libcec_connection_t
-    // connection); extern DECLSPEC int
+    // language); extern DECLSPEC int
     // libcec_is_active_device(libcec_connection_t connection, CEC_NAMESPACE
     // cec_logical_address address); extern DECLSPEC int
     // libcec_is_active_device_type(libcec_connection_t connection, CEC_NAMESPACE
     // cec_device_type type); extern DECLSPEC int
     // libcec_set_hdmi_port(libcec_connection_t connection, CEC_NAMESPACE
     // cec_logical_address baseDevice, uint8_t iPort); extern DECLSPEC int
-    // libcec_get_device_osd_name(libcec_connection_t connection, CEC_NAMESPACE
-    // cec_logical_address iAddress, CEC_NAMESPACE cec_osd_name name);
-    // extern DECLSPEC int libcec_set_stream_path_logical(libcec_connection_t
+    // libcec_set_stream_path_logical(libcec_connection_t
     // connection, CEC_NAMESPACE cec_logical_address iAddress); extern DECLSPEC
     // int libcec_set_stream_path_physical(libcec_connection_t connection, uint16_t
     // iPhysicalAddress); extern DECLSPEC int
@@ -593,6 +1226,37 @@ impl Connection {
 }
 
 impl Cfg {
+    /// Returns the settings worth logging when connecting, like
+    /// `name "owl", kind RecordingDevice, device auto, hdmi port 2`. Callbacks
+    /// are left out, and unset settings are left to libcec.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut parts = vec![
+            format!("name {:?}", self.name),
+            format!("kind {:?}", self.kind),
+            format!("device {}", self.device.as_deref().unwrap_or("auto")),
+        ];
+        if let Some(port) = self.hdmi_port {
+            parts.push(format!("hdmi port {port}"));
+        }
+        if let Some(address) = self.base_device {
+            parts.push(format!("base device {address:?}"));
+        }
+        match (self.physical_address, self.detect_physical_address) {
+            (Some(address), _) => parts.push(format!("physical address {address}")),
+            (None, Some(true)) => parts.push("physical address detected".to_owned()),
+            _ => {}
+        }
+        if let Some(monitor_only) = self.monitor_only {
+            parts.push(format!("monitor only {monitor_only}"));
+        }
+        if let Some(adapter_type) = self.adapter_type {
+            parts.push(format!("adapter type {adapter_type:?}"));
+        }
+
+        parts.join(", ")
+    }
+
     /// Open connection to configuration represented by this object
     ///
     ///
@@ -601,20 +1265,30 @@ impl Cfg {
     /// Error is returned in following cases
     /// - LibInitFailed: cec_sys::libcec_initialise fails
     /// - AdapterOpenFailed: cec_sys::libcec_open fails
+    /// - `AdapterBusy`: the port is still busy after resetting the adapter once
     /// - CallbackRegistrationFailed: cec_sys::libcec_enable_callbacks fails
     pub fn connect(mut self) -> Result<Connection> {
         let mut cfg: libcec_configuration = (&self).into();
+        let port_busy = Arc::new(AtomicBool::new(false));
         // Consume self.*_callback and build CecCallbacks from those
+        let replies = Replies::default();
         let pinned_callbacks = Box::pin(Callbacks {
             on_key_press: self.on_key_press.take(),
-            on_cmd_received: self.on_command_received.take(),
+            on_cmd_received: Some(track_replies(
+                self.on_command_received.take(),
+                replies.clone(),
+            )),
             on_log_msg: self.on_log_message.take(),
             on_cfg_changed: self.on_cfg_changed.take(),
-            on_alert: self.on_alert.take(),
+            on_alert: Some(track_port_busy(self.on_alert.take(), port_busy.clone())),
             on_menu_state_changed: self.on_menu_state_change.take(),
             on_source_activated: self.on_source_activated.take(),
         });
         let rust_callbacks_as_void_ptr = &*pinned_callbacks as *const _ as *mut _;
+        // Register the callbacks before opening, so alerts raised while
+        // opening, like the port being busy, aren't lost.
+        cfg.callbacks = addr_of_mut!(CALLBACKS);
+        cfg.callbackParam = rust_callbacks_as_void_ptr;
         let detect_device = self.detect_device.unwrap_or(false);
         let device = self.device.clone();
         let open_timeout = self.timeout.as_millis() as u32;
@@ -623,6 +1297,8 @@ impl Cfg {
             self,
             unsafe { libcec_initialise(&mut cfg) },
             pinned_callbacks,
+            replies,
+            Versions::default(),
         );
 
         if connection.1.is_null() {
@@ -641,7 +1317,22 @@ impl Cfg {
         };
 
         if unsafe { libcec_open(connection.1, resolved_device.as_ptr(), open_timeout) } == 0 {
-            return Err(ConnectionError::AdapterOpenFailed.into());
+            if !port_busy.swap(false, Ordering::Relaxed) {
+                return Err(ConnectionError::AdapterOpenFailed.into());
+            }
+
+            // A port left busy by a process that didn't close it cleanly is
+            // often freed by closing libcec's handle to it, so reset it once.
+            warn!("adapter busy, resetting it...");
+            if let Err(e) = connection.reopen() {
+                return Err(if port_busy.load(Ordering::Relaxed) {
+                    ConnectionError::AdapterBusy.into()
+                } else {
+                    e
+                });
+            }
+            info!("reset the busy adapter");
+            return Ok(connection);
         }
 
         let callback_ret = unsafe {
@@ -658,28 +1349,47 @@ impl Cfg {
         Ok(connection)
     }
 
+    /// Lists the adapters libcec finds, without opening any.
+    pub fn adapters(self) -> Result<Vec<Adapter>> {
+        let mut cfg: libcec_configuration = (&self).into();
+        let connection = Connection(
+            self,
+            unsafe { libcec_initialise(&mut cfg) },
+            Box::pin(Callbacks::default()),
+            Replies::default(),
+            Versions::default(),
+        );
+        if connection.1.is_null() {
+            return Err(ConnectionError::InitFailed.into());
+        }
+
+        Self::detect_adapters(&connection)
+    }
+
     fn detect_device(connection: &Connection) -> Result<CString> {
+        let adapters = Self::detect_adapters(connection)?;
+        let adapter = Adapter::preferred(&adapters).ok_or(ConnectionError::NoAdapterFound)?;
+        Ok(CString::new(adapter.port.as_str())?)
+    }
+
+    fn detect_adapters(connection: &Connection) -> Result<Vec<Adapter>> {
         let mut devices: [cec_sys::cec_adapter_descriptor; 10] = unsafe { std::mem::zeroed() };
         let num_devices = unsafe {
             cec_sys::libcec_detect_adapters(
                 connection.1,
-                &mut devices as _,
+                devices.as_mut_ptr(),
                 10,
                 std::ptr::null(),
                 true as i32,
             )
         };
 
-        if num_devices < 0 {
-            Err(ConnectionError::NoAdapterFound.into())
-        } else {
-            let device = devices[0]
-                .strComName
-                .into_iter()
-                .flat_map(u8::try_from)
-                .filter(|x| *x != 0)
-                .collect::<Vec<u8>>();
-            Ok(CString::new(device)?)
+        match usize::try_from(num_devices) {
+            Ok(0) | Err(_) => Err(ConnectionError::NoAdapterFound.into()),
+            Ok(n) => Ok(devices[..n.min(devices.len())]
+                .iter()
+                .map(|x| Adapter::from(*x))
+                .collect()),
         }
     }
 }
@@ -693,6 +1403,36 @@ impl Drop for Connection {
     }
 }
 
+impl Adapter {
+    /// Returns the adapter opened when the device is detected: the first
+    /// external Pulse-Eight adapter by port, otherwise the first by port.
+    #[must_use]
+    pub fn preferred(adapters: &[Self]) -> Option<&Self> {
+        adapters
+            .iter()
+            .min_by_key(|x| (x.kind != AdapterType::P8External, &x.port))
+    }
+}
+
+impl LogicalAddress {
+    /// The destination addressing every device on the bus, logical address
+    /// 15.
+    ///
+    /// Address 15 means two things, which libcec names after the first: as an
+    /// initiator it's a device without a logical address, as a destination
+    /// it's a broadcast. Naming it this way makes the intent of sending to
+    /// every device explicit.
+    pub const BROADCAST: Self = Self::Unregistered;
+
+    /// Returns whether the address is the [broadcast
+    /// address](Self::BROADCAST). Broadcasts aren't acknowledged by a single
+    /// device, so a successful transmit only means no device rejected it.
+    #[must_use]
+    pub fn is_broadcast(self) -> bool {
+        self == Self::BROADCAST
+    }
+}
+
 impl KnownLogicalAddress {
     pub fn new(address: LogicalAddress) -> Option<Self> {
         match address {
@@ -711,6 +1451,382 @@ impl RegisteredLogicalAddress {
     }
 }
 
+impl Cmd {
+    /// The transmit timeout libcec uses by default.
+    pub const DEFAULT_TRANSMIT_TIMEOUT: Duration = Duration::from_millis(1000);
+    /// The longest frame the bus carries: a header, an opcode and 14
+    /// operands.
+    pub const MAX_FRAME_LEN: usize = 16;
+
+    /// Creates a cmd with the given opcode and parameters.
+    #[must_use]
+    pub fn new(
+        initiator: LogicalAddress,
+        destination: LogicalAddress,
+        opcode: Opcode,
+        parameters: &[u8],
+    ) -> Self {
+        let mut packet = DataPacket(ArrayVec::new());
+        packet
+            .0
+            .extend(parameters.iter().copied().take(packet.0.capacity()));
+
+        Self {
+            initiator,
+            destination,
+            ack: false,
+            eom: true,
+            opcode,
+            parameters: packet,
+            opcode_set: true,
+            transmit_timeout: Self::DEFAULT_TRANSMIT_TIMEOUT,
+        }
+    }
+
+    /// Creates a cmd putting `destination` in standby, every device if it's
+    /// the broadcast address.
+    #[must_use]
+    pub fn standby(initiator: LogicalAddress, destination: LogicalAddress) -> Self {
+        Self::new(initiator, destination, Opcode::Standby, &[])
+    }
+
+    /// Returns whether the cmd is sent to every device on the bus, see
+    /// [`LogicalAddress::is_broadcast`].
+    #[must_use]
+    pub fn is_broadcast(&self) -> bool {
+        self.destination.is_broadcast()
+    }
+
+    /// Returns whether the cmd was sent by `address`.
+    #[must_use]
+    pub fn is_from(&self, address: LogicalAddress) -> bool {
+        self.initiator == address
+    }
+
+    /// Returns whether `address` receives the cmd, either sent to it or
+    /// broadcast.
+    #[must_use]
+    pub fn is_to(&self, address: LogicalAddress) -> bool {
+        self.destination == address || self.is_broadcast()
+    }
+
+    /// Returns whether `address` sent or receives the cmd.
+    #[must_use]
+    pub fn involves(&self, address: LogicalAddress) -> bool {
+        self.is_from(address) || self.is_to(address)
+    }
+
+    /// Returns the opcode a `<Feature Abort>` refused and why, `None` for
+    /// other opcodes or unknown operands.
+    #[must_use]
+    pub fn feature_abort(&self) -> Option<(Opcode, AbortReason)> {
+        if self.opcode != Opcode::FeatureAbort {
+            return None;
+        }
+
+        let [opcode, reason, ..] = self.parameters.0[..] else {
+            return None;
+        };
+        let reason = match reason {
+            0 => AbortReason::UnrecognizedOpcode,
+            1 => AbortReason::NotInCorrectModeToRespond,
+            2 => AbortReason::CannotProvideSource,
+            3 => AbortReason::InvalidOperand,
+            4 => AbortReason::Refused,
+            _ => return None,
+        };
+        Some((Opcode::try_from(opcode).ok()?, reason))
+    }
+
+    /// Returns whether system audio mode is on, from a `<Set System Audio
+    /// Mode>` or `<System Audio Mode Status>`. `None` for other opcodes or an
+    /// invalid status.
+    #[must_use]
+    pub fn system_audio_status(&self) -> Option<SystemAudioStatus> {
+        if !matches!(
+            self.opcode,
+            Opcode::SetSystemAudioMode | Opcode::SystemAudioModeStatus
+        ) {
+            return None;
+        }
+
+        match self.parameters.0.first()? {
+            0 => Some(SystemAudioStatus::Off),
+            1 => Some(SystemAudioStatus::On),
+            _ => None,
+        }
+    }
+
+    /// Returns the name a `<Set OSD Name>` carries, `None` for other opcodes.
+    /// Names should be ASCII, other bytes are replaced with `?`.
+    #[must_use]
+    pub fn osd_name(&self) -> Option<String> {
+        (self.opcode == Opcode::SetOsdName).then(|| {
+            let name: String = self
+                .parameters
+                .0
+                .iter()
+                .take_while(|x| **x != 0)
+                .map(|x| match x {
+                    b' ' => ' ',
+                    x if x.is_ascii_graphic() => char::from(*x),
+                    _ => '?',
+                })
+                .collect();
+            name.trim().to_owned()
+        })
+    }
+
+    /// Returns the version a `<CEC Version>` carries, `None` for other
+    /// opcodes.
+    #[must_use]
+    pub fn cec_version(&self) -> Option<Version> {
+        (self.opcode == Opcode::CecVersion).then(|| {
+            self.parameters
+                .0
+                .first()
+                .and_then(|x| Version::from_operand(*x))
+                .unwrap_or(Version::VersionUnknown)
+        })
+    }
+
+    /// Sets how long libcec waits for the cmd to be acknowledged.
+    #[must_use]
+    pub fn with_transmit_timeout(self, transmit_timeout: Duration) -> Self {
+        Self {
+            transmit_timeout,
+            ..self
+        }
+    }
+
+    /// Creates a cmd pressing `key` on `destination`.
+    #[must_use]
+    pub fn key_press(
+        initiator: LogicalAddress,
+        destination: LogicalAddress,
+        key: UserControlCode,
+    ) -> Self {
+        Self::new(
+            initiator,
+            destination,
+            Opcode::UserControlPressed,
+            &[key.into()],
+        )
+    }
+
+    /// Creates a vendor specific cmd, see [`Connection::send_vendor_command`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CmdError::PayloadTooLong`] if the payload, including the
+    /// vendor id, doesn't fit in a packet.
+    pub fn vendor_command(
+        initiator: LogicalAddress,
+        destination: LogicalAddress,
+        vendor_id: Option<VendorId>,
+        payload: &[u8],
+    ) -> Result<Self> {
+        let mut parameters = Vec::with_capacity(payload.len() + 3);
+        let opcode = match vendor_id {
+            Some(id) => {
+                // Vendor ids are 24-bit IEEE OUIs, sent most significant byte first.
+                parameters.extend_from_slice(&(id.repr() as u32).to_be_bytes()[1..]);
+                Opcode::VendorCommandWithId
+            }
+            None => Opcode::VendorCommand,
+        };
+        parameters.extend_from_slice(payload);
+
+        let max = DataPacket(ArrayVec::new()).0.capacity();
+        if parameters.len() > max {
+            return Err(CmdError::PayloadTooLong(parameters.len(), max).into());
+        }
+
+        Ok(Self::new(initiator, destination, opcode, &parameters))
+    }
+
+    /// Creates a cmd from a raw frame, as sent on the bus. The first byte is
+    /// the header, with the initiator's logical address in the high nibble and
+    /// the destination's in the low nibble, so `0x40` is sent from playback
+    /// device 1 to the TV. The opcode and its operands follow. A frame of just
+    /// a header is a poll.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CmdError::EmptyFrame`] or [`CmdError::FrameTooLong`] if the
+    /// frame doesn't fit on the bus, or [`TryFromCmdError::UnknownOpcode`] if
+    /// libcec doesn't know the opcode.
+    pub fn from_frame(frame: &[u8]) -> Result<Self> {
+        let [header, rest @ ..] = frame else {
+            return Err(CmdError::EmptyFrame.into());
+        };
+        if frame.len() > Self::MAX_FRAME_LEN {
+            return Err(CmdError::FrameTooLong(frame.len(), Self::MAX_FRAME_LEN).into());
+        }
+
+        let initiator = LogicalAddress::try_from(c_int::from(header >> 4))
+            .map_err(|_| TryFromCmdError::UnknownInitiator)?;
+        let destination = LogicalAddress::try_from(c_int::from(header & 0xF))
+            .map_err(|_| TryFromCmdError::UnknownDestination)?;
+        let Some((opcode, parameters)) = rest.split_first() else {
+            return Ok(Self {
+                opcode_set: false,
+                ..Self::new(initiator, destination, Opcode::None, &[])
+            });
+        };
+
+        let opcode = Opcode::try_from(*opcode)?;
+        Ok(Self::new(initiator, destination, opcode, parameters))
+    }
+
+    /// Returns the cmd as a raw frame, see [`Cmd::from_frame`] for its
+    /// layout.
+    #[must_use]
+    pub fn to_frame(&self) -> Vec<u8> {
+        let header =
+            ((self.initiator.repr() as u8 & 0xF) << 4) | (self.destination.repr() as u8 & 0xF);
+        let mut frame = vec![header];
+        if self.opcode_set {
+            frame.push(self.opcode.repr() as u8);
+            frame.extend_from_slice(&self.parameters.0);
+        }
+        frame
+    }
+
+    /// Returns the frame the cmd would be sent as, converting it to libcec's
+    /// representation and back like [`Connection::transmit`] does, without
+    /// touching the bus.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if libcec's representation doesn't convert back, like
+    /// for an opcode it doesn't know.
+    pub fn dry_run(self) -> Result<Vec<u8>> {
+        Ok(Self::try_from(cec_command::from(self))?.to_frame())
+    }
+
+    /// Returns the physical address carried by commands announcing or
+    /// selecting a device, like [`Opcode::ActiveSource`]. `None` for other
+    /// commands, or if the parameters are too short.
+    #[must_use]
+    pub fn physical_address(&self) -> Option<PhysicalAddress> {
+        match self.opcode {
+            Opcode::ActiveSource
+            | Opcode::InactiveSource
+            | Opcode::SetStreamPath
+            | Opcode::RoutingInformation
+            | Opcode::ReportPhysicalAddress
+                if self.opcode_set =>
+            {
+                let [hi, lo, ..] = *self.parameters.0 else {
+                    return None;
+                };
+                Some(PhysicalAddress::from_be_bytes([hi, lo]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Creates a cmd releasing whichever key was last pressed on
+    /// `destination`.
+    #[must_use]
+    pub fn key_release(initiator: LogicalAddress, destination: LogicalAddress) -> Self {
+        Self::new(initiator, destination, Opcode::UserControlRelease, &[])
+    }
+}
+
+impl Keypress {
+    /// Whether this is the initial press of a key. libcec reports presses
+    /// with a zero duration and releases with how long the key was held.
+    #[must_use]
+    pub const fn is_press(&self) -> bool {
+        self.duration.is_zero()
+    }
+
+    /// Whether this is the release of a key.
+    #[must_use]
+    pub const fn is_release(&self) -> bool {
+        !self.is_press()
+    }
+
+    /// Whether this keypress releases `press`.
+    #[must_use]
+    pub fn releases(&self, press: &Self) -> bool {
+        let same_key = self.keycode == press.keycode;
+        same_key && self.is_release() && press.is_press()
+    }
+
+    /// Returns the cmd that reproduces this keypress, sent from `initiator`
+    /// to `destination`.
+    #[must_use]
+    pub fn to_cmd(&self, initiator: LogicalAddress, destination: LogicalAddress) -> Cmd {
+        if self.is_press() {
+            Cmd::key_press(initiator, destination, self.keycode)
+        } else {
+            Cmd::key_release(initiator, destination)
+        }
+    }
+}
+
+impl PhysicalAddress {
+    /// The TV's address, the root of the HDMI tree.
+    pub const ROOT: Self = Self(0);
+    /// The address libcec reports for devices without a known address.
+    pub const UNKNOWN: Self = Self(0xFFFF);
+
+    /// Returns the address as sent on the bus, most significant byte first.
+    #[must_use]
+    pub const fn to_be_bytes(self) -> [u8; 2] {
+        self.0.to_be_bytes()
+    }
+
+    /// Reads an address as sent on the bus, most significant byte first.
+    #[must_use]
+    pub const fn from_be_bytes(bytes: [u8; 2]) -> Self {
+        Self(u16::from_be_bytes(bytes))
+    }
+
+    /// Returns the input ports along the path from the TV, zero past the
+    /// device's depth.
+    #[must_use]
+    pub const fn ports(self) -> [u8; 4] {
+        let [a, b] = self.to_be_bytes();
+        [a >> 4, a & 0xF, b >> 4, b & 0xF]
+    }
+
+    /// Whether the address is a position in the HDMI tree, i.e. no port
+    /// follows a zero.
+    #[must_use]
+    pub fn is_valid(self) -> bool {
+        let ports = self.ports();
+        self != Self::UNKNOWN && ports.windows(2).all(|x| x[0] != 0 || x[1] == 0)
+    }
+
+    /// Returns how many hops the device is from the TV, `None` if the address
+    /// isn't valid.
+    #[must_use]
+    pub fn depth(self) -> Option<usize> {
+        self.is_valid()
+            .then(|| self.ports().iter().take_while(|x| **x != 0).count())
+    }
+
+    /// Returns the input port on the parent the device is connected to,
+    /// `None` for the TV or if the address isn't valid.
+    #[must_use]
+    pub fn port(self) -> Option<u8> {
+        let depth = self.depth().filter(|x| *x != 0)?;
+        Some(self.ports()[depth - 1])
+    }
+
+    /// Returns the address of the device this one is connected to, `None` for
+    /// the TV or if the address isn't valid.
+    #[must_use]
+    pub fn parent(self) -> Option<Self> {
+        let depth = self.depth().filter(|x| *x != 0)?;
+        Some(Self(self.0 & !(0xF << (4 * (4 - depth)))))
+    }
+}
+
 impl Display for LogLevel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -767,11 +1883,45 @@ impl LogicalAddresses {
 }
 
 impl DeviceKinds {
+    /// The most device kinds libcec supports, the size of the FFI array.
+    pub const MAX: usize = 5;
+
     pub fn new(value: DeviceKind) -> DeviceKinds {
-        let mut inner = ArrayVec::<_, 5>::new();
+        let mut inner = ArrayVec::<_, { Self::MAX }>::new();
         inner.push(value);
         DeviceKinds(inner)
     }
+
+    /// Adds a device kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeviceKindsError::TooMany`] if there's already
+    /// [`DeviceKinds::MAX`] device kinds.
+    pub fn push(&mut self, kind: DeviceKind) -> result::Result<(), DeviceKindsError> {
+        self.0
+            .try_push(kind)
+            .map_err(|_| DeviceKindsError::TooMany(Self::MAX))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DeviceKind> {
+        self.0.iter()
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[must_use]
+    pub fn contains(&self, kind: DeviceKind) -> bool {
+        self.0.contains(&kind)
+    }
 }
 
 impl Default for LogicalAddresses {
@@ -783,6 +1933,89 @@ impl Default for LogicalAddresses {
     }
 }
 
+impl Replies {
+    /// Awaits the next `opcode` from `initiator`.
+    fn expect(&self, initiator: LogicalAddress, opcode: Opcode) -> mpsc::Receiver<Cmd> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if let Ok(mut replies) = self.0.lock() {
+            replies.push((initiator, opcode, reply_tx));
+        }
+        reply_rx
+    }
+
+    /// Hands `cmd` to whoever awaits it, returning whether anyone did.
+    fn offer(&self, cmd: &Cmd) -> bool {
+        let Ok(mut replies) = self.0.lock() else {
+            return false;
+        };
+
+        let len = replies.len();
+        replies.retain(|(initiator, opcode, reply_tx)| {
+            if *initiator != cmd.initiator || *opcode != cmd.opcode {
+                return true;
+            }
+            // The receiver is gone if the request timed out, drop it anyway.
+            let _ = reply_tx.send(cmd.clone());
+            false
+        });
+        replies.len() < len
+    }
+}
+
+impl Versions {
+    fn get(&self, address: LogicalAddress) -> Option<Version> {
+        self.0.lock().ok()?.get(&address).copied()
+    }
+
+    fn insert(&self, address: LogicalAddress, version: Version) {
+        if let Ok(mut versions) = self.0.lock() {
+            versions.insert(address, version);
+        }
+    }
+}
+
+/// Wraps `on_cmd` to hand replies to [`Connection::request`].
+fn track_replies(mut on_cmd: Option<Box<OnCmd>>, replies: Replies) -> Box<OnCmd> {
+    Box::new(move |cmd| {
+        replies.offer(&cmd);
+        if let Some(on_cmd) = &mut on_cmd {
+            on_cmd(cmd);
+        }
+    })
+}
+
+/// Wraps `on_alert` to record whether libcec reported the port as busy.
+fn track_port_busy(mut on_alert: Option<Box<OnAlert>>, port_busy: Arc<AtomicBool>) -> Box<OnAlert> {
+    Box::new(move |alert| {
+        if alert == Alert::PortBusy {
+            port_busy.store(true, Ordering::Relaxed);
+        }
+        if let Some(on_alert) = &mut on_alert {
+            on_alert(alert);
+        }
+    })
+}
+
+/// Returns `string` as HDMI-CEC carries it.
+///
+/// It's truncated to its first `max` characters with a warning if it's longer.
+/// HDMI-CEC strings, like OSD names, are printable ASCII, anything else is an
+/// error rather than being mangled on the bus.
+pub fn to_cec_ascii(string: &str, max: usize) -> Result<&str> {
+    if let Some(c) = string.chars().find(|x| !matches!(x, ' '..='~')) {
+        return Err(Error::NonAscii(c));
+    }
+    if string.len() > max {
+        warn!(
+            "`{string}` is {} characters, truncating to {max}",
+            string.len()
+        );
+        return Ok(&string[..max]);
+    }
+
+    Ok(string)
+}
+
 fn first_n<const N: usize>(string: &str) -> [::std::os::raw::c_char; N] {
     let mut data: [::std::os::raw::c_char; N] = [0; N];
     let bytes = string.as_bytes();
@@ -792,3 +2025,27 @@ fn first_n<const N: usize>(string: &str) -> [::std::os::raw::c_char; N] {
     }
     data
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_port_busy() {
+        let port_busy = Arc::new(AtomicBool::new(false));
+        let (alert_tx, alert_rx) = mpsc::channel();
+        let mut on_alert = track_port_busy(
+            Some(Box::new(move |alert| alert_tx.send(alert).unwrap())),
+            port_busy.clone(),
+        );
+
+        on_alert(Alert::ConnectionLost);
+        assert!(!port_busy.load(Ordering::Relaxed));
+        on_alert(Alert::PortBusy);
+        assert!(port_busy.load(Ordering::Relaxed));
+        assert_eq!(
+            alert_rx.try_iter().collect::<Vec<_>>(),
+            [Alert::ConnectionLost, Alert::PortBusy]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::ffi::c_int;
 use cec_sys::*;
 use enum_repr::EnumRepr;
 
-use crate::TryFromLogicalAddressesError;
+use crate::{TryFromCmdError, TryFromKeypressError, TryFromLogicalAddressesError};
 
 #[EnumRepr(type = "cec_abort_reason")]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -650,3 +650,247 @@ impl TryFrom<c_int> for LogicalAddress {
         Ok(x)
     }
 }
+
+/// Converts an opcode as sent on the bus. [`Opcode::None`] is libcec's marker
+/// for a cmd without an opcode, so it isn't accepted.
+impl TryFrom<u8> for Opcode {
+    type Error = TryFromCmdError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let x = match value {
+            0x00 => Self::FeatureAbort,
+            0x04 => Self::ImageViewOn,
+            0x05 => Self::TunerStepIncrement,
+            0x06 => Self::TunerStepDecrement,
+            0x07 => Self::TunerDeviceStatus,
+            0x08 => Self::GiveTunerDeviceStatus,
+            0x09 => Self::RecordOn,
+            0x0A => Self::RecordStatus,
+            0x0B => Self::RecordOff,
+            0x0D => Self::TextViewOn,
+            0x0F => Self::RecordTvScreen,
+            0x1A => Self::GiveDeckStatus,
+            0x1B => Self::DeckStatus,
+            0x32 => Self::SetMenuLanguage,
+            0x33 => Self::ClearAnalogueTimer,
+            0x34 => Self::SetAnalogueTimer,
+            0x35 => Self::TimerStatus,
+            0x36 => Self::Standby,
+            0x41 => Self::Play,
+            0x42 => Self::DeckControl,
+            0x43 => Self::TimerClearedStatus,
+            0x44 => Self::UserControlPressed,
+            0x45 => Self::UserControlRelease,
+            0x46 => Self::GiveOsdName,
+            0x47 => Self::SetOsdName,
+            0x64 => Self::SetOsdString,
+            0x67 => Self::SetTimerProgramTitle,
+            0x70 => Self::SystemAudioModeRequest,
+            0x71 => Self::GiveAudioStatus,
+            0x72 => Self::SetSystemAudioMode,
+            0x7A => Self::ReportAudioStatus,
+            0x7D => Self::GiveSystemAudioModeStatus,
+            0x7E => Self::SystemAudioModeStatus,
+            0x80 => Self::RoutingChange,
+            0x81 => Self::RoutingInformation,
+            0x82 => Self::ActiveSource,
+            0x83 => Self::GivePhysicalAddress,
+            0x84 => Self::ReportPhysicalAddress,
+            0x85 => Self::RequestActiveSource,
+            0x86 => Self::SetStreamPath,
+            0x87 => Self::DeviceVendorId,
+            0x89 => Self::VendorCommand,
+            0x8A => Self::VendorRemoteButtonDown,
+            0x8B => Self::VendorRemoteButtonUp,
+            0x8C => Self::GiveDeviceVendorId,
+            0x8D => Self::MenuRequest,
+            0x8E => Self::MenuStatus,
+            0x8F => Self::GiveDevicePowerStatus,
+            0x90 => Self::ReportPowerStatus,
+            0x91 => Self::GetMenuLanguage,
+            0x92 => Self::SelectAnalogueService,
+            0x93 => Self::SelectDigitalService,
+            0x97 => Self::SetDigitalTimer,
+            0x99 => Self::ClearDigitalTimer,
+            0x9A => Self::SetAudioRate,
+            0x9D => Self::InactiveSource,
+            0x9E => Self::CecVersion,
+            0x9F => Self::GetCecVersion,
+            0xA0 => Self::VendorCommandWithId,
+            0xA1 => Self::ClearExternalTimer,
+            0xA2 => Self::SetExternalTimer,
+            0xA3 => Self::ReportShortAudioDescriptors,
+            0xA4 => Self::RequestShortAudioDescriptors,
+            0xC0 => Self::StartArc,
+            0xC1 => Self::ReportArcStarted,
+            0xC2 => Self::ReportArcEnded,
+            0xC3 => Self::RequestArcStart,
+            0xC4 => Self::RequestArcEnd,
+            0xC5 => Self::EndArc,
+            0xF8 => Self::Cdc,
+            0xFF => Self::Abort,
+            _ => return Err(TryFromCmdError::UnknownOpcode),
+        };
+
+        Ok(x)
+    }
+}
+
+impl TryFrom<u8> for UserControlCode {
+    type Error = TryFromKeypressError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let x = match value {
+            0 => Self::Select,
+            1 => Self::Up,
+            2 => Self::Down,
+            3 => Self::Left,
+            4 => Self::Right,
+            5 => Self::RightUp,
+            6 => Self::RightDown,
+            7 => Self::LeftUp,
+            8 => Self::LeftDown,
+            9 => Self::RootMenu,
+            10 => Self::SetupMenu,
+            11 => Self::ContentsMenu,
+            12 => Self::FavoriteMenu,
+            13 => Self::Exit,
+            16 => Self::TopMenu,
+            17 => Self::DvdMenu,
+            29 => Self::NumberEntryMode,
+            30 => Self::Number11,
+            31 => Self::Number12,
+            32 => Self::Number0,
+            33 => Self::Number1,
+            34 => Self::Number2,
+            35 => Self::Number3,
+            36 => Self::Number4,
+            37 => Self::Number5,
+            38 => Self::Number6,
+            39 => Self::Number7,
+            40 => Self::Number8,
+            41 => Self::Number9,
+            42 => Self::Dot,
+            43 => Self::Enter,
+            44 => Self::Clear,
+            47 => Self::NextFavorite,
+            48 => Self::ChannelUp,
+            49 => Self::ChannelDown,
+            50 => Self::PreviousChannel,
+            51 => Self::SoundSelect,
+            52 => Self::InputSelect,
+            53 => Self::DisplayInformation,
+            54 => Self::Help,
+            55 => Self::PageUp,
+            56 => Self::PageDown,
+            64 => Self::Power,
+            65 => Self::VolumeUp,
+            66 => Self::VolumeDown,
+            67 => Self::Mute,
+            68 => Self::Play,
+            69 => Self::Stop,
+            70 => Self::Pause,
+            71 => Self::Record,
+            72 => Self::Rewind,
+            73 => Self::FastForward,
+            74 => Self::Eject,
+            75 => Self::Forward,
+            76 => Self::Backward,
+            77 => Self::StopRecord,
+            78 => Self::PauseRecord,
+            80 => Self::Angle,
+            81 => Self::SubPicture,
+            82 => Self::VideoOnDemand,
+            83 => Self::ElectronicProgramGuide,
+            84 => Self::TimerProgramming,
+            85 => Self::InitialConfiguration,
+            86 => Self::SelectBroadcastType,
+            87 => Self::SelectSoundPresentation,
+            96 => Self::PlayFunction,
+            97 => Self::PausePlayFunction,
+            98 => Self::RecordFunction,
+            99 => Self::PauseRecordFunction,
+            100 => Self::StopFunction,
+            101 => Self::MuteFunction,
+            102 => Self::RestoreVolumeFunction,
+            103 => Self::TuneFunction,
+            104 => Self::SelectMediaFunction,
+            105 => Self::SelectAvInputFunction,
+            106 => Self::SelectAudioInputFunction,
+            107 => Self::PowerToggleFunction,
+            108 => Self::PowerOffFunction,
+            109 => Self::PowerOnFunction,
+            113 => Self::F1Blue,
+            114 => Self::F2Red,
+            115 => Self::F3Green,
+            116 => Self::F4Yellow,
+            117 => Self::F5,
+            118 => Self::Data,
+            145 => Self::AnReturn,
+            150 => Self::AnChannelsList,
+            _ => return Err(TryFromKeypressError::UnknownKeycode),
+        };
+
+        Ok(x)
+    }
+}
+
+impl Version {
+    /// Returns the version a `<CEC Version>` operand names, `None` for 1.1 and
+    /// reserved values.
+    #[must_use]
+    pub const fn from_operand(operand: u8) -> Option<Self> {
+        let x = match operand {
+            1 => Self::Version12,
+            2 => Self::Version12a,
+            3 => Self::Version13,
+            4 => Self::Version13a,
+            5 => Self::Version14,
+            6 => Self::Version20,
+            _ => return None,
+        };
+
+        Some(x)
+    }
+}
+
+impl VendorId {
+    /// Returns the vendor with the given IEEE OUI, `None` if libcec doesn't
+    /// know it.
+    #[must_use]
+    pub const fn from_id(id: u32) -> Option<Self> {
+        let x = match id {
+            57 => Self::Toshiba,
+            240 => Self::Samsung,
+            1485 => Self::Denon,
+            1656 => Self::Marantz,
+            2434 => Self::Loewe,
+            2480 => Self::Onkyo,
+            3256 => Self::Medion,
+            3303 => Self::Toshiba2,
+            4346 => Self::Apple,
+            5506 => Self::PulseEight,
+            6480 => Self::HarmanKardon2,
+            6673 => Self::Google,
+            8391 => Self::Akai,
+            9319 => Self::Aoc,
+            32837 => Self::Panasonic,
+            36926 => Self::Philips,
+            36947 => Self::Daewoo,
+            41182 => Self::Yamaha,
+            53461 => Self::Grundig,
+            57398 => Self::Pioneer,
+            57489 => Self::Lg,
+            524_319 => Self::Sharp,
+            524_358 => Self::Sony,
+            1_622_150 => Self::Broadcom,
+            5_458_000 => Self::Sharp2,
+            7_042_157 => Self::Vizio,
+            8_414_697 => Self::Benq,
+            10_249_310 => Self::HarmanKardon,
+            _ => return None,
+        };
+
+        Some(x)
+    }
+}
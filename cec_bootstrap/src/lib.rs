@@ -1,6 +1,13 @@
-use std::{io::Cursor, path::Path};
+#[cfg(feature = "bindings")]
+pub mod bindings;
 
-use color_eyre::eyre::{Context, Result};
+use std::{env, io::Cursor, path::Path, str::FromStr};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use reqwest::StatusCode;
+use target_lexicon::Triple;
+
+pub const LIBCEC_VERSION: &str = "6.0.2";
 
 #[derive(Debug, Copy, Clone)]
 pub enum BuildKind {
@@ -8,14 +15,36 @@ pub enum BuildKind {
     Release,
 }
 
-pub fn fetch_libcec<P: AsRef<Path>>(path: P, kind: BuildKind) -> Result<()> {
-    let target = target_lexicon::HOST.to_string();
-    let url = format!("https://github.com/opeik/owl/releases/download/libcec-v6.0.2/libcec-v6.0.2-{target}-{kind}.zip");
-    dbg!(target, kind, &url);
+/// No prebuilt libcec release asset exists for the target.
+#[derive(Debug, thiserror::Error)]
+#[error("no prebuilt libcec exists for target `{0}`")]
+pub struct MissingAssetError(pub Triple);
+
+/// Returns the target to fetch libcec for.
+///
+/// Cargo tells build scripts which target is being compiled via `TARGET`, which
+/// differs from the host when cross-compiling. Outside a build script, like in
+/// `cec_bindgen`, fall back to the host.
+pub fn target() -> Result<Triple> {
+    match env::var("TARGET") {
+        Ok(x) => Triple::from_str(&x).map_err(|e| eyre!("invalid target `{x}`: {e}")),
+        Err(_) => Ok(target_lexicon::HOST),
+    }
+}
+
+pub fn fetch_libcec<P: AsRef<Path>>(path: P, target: &Triple, kind: BuildKind) -> Result<()> {
+    let url = format!("https://github.com/opeik/owl/releases/download/libcec-v{LIBCEC_VERSION}/libcec-v{LIBCEC_VERSION}-{target}-{kind}.zip");
 
     if !path.as_ref().exists() {
-        let file = reqwest::blocking::get(&url)?
-            .bytes()
+        let response = reqwest::blocking::get(&url)
+            .context(format!("failed to download libcec from {url}"))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(MissingAssetError(target.clone()).into());
+        }
+
+        let file = response
+            .error_for_status()
+            .and_then(reqwest::blocking::Response::bytes)
             .context(format!("failed to download libcec from {url}"))?;
         zip_extract::extract(Cursor::new(file), path.as_ref(), true).context(format!(
             "failed to extract libcec archive to `{}`",
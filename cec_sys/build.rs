@@ -1,28 +1,58 @@
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
-use cec_bootstrap::{fetch_libcec, BuildKind};
+use cec_bootstrap::{fetch_libcec, BuildKind, MissingAssetError, LIBCEC_VERSION};
 use color_eyre::eyre::{eyre, Context, Result};
-use target_lexicon::OperatingSystem;
+use target_lexicon::{OperatingSystem, Triple};
+
+/// The oldest system libcec the fallback accepts, matching the bindings'
+/// major version.
+#[cfg(feature = "system-libcec-fallback")]
+const MIN_SYSTEM_LIBCEC_VERSION: &str = "6.0";
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let download_path =
-        PathBuf::from(env::var("OUT_DIR").context("env var `OUT_DIR` is undefined")?);
-    let lib_path = download_path.join("libcec");
-    let lib_path_str = lib_path.to_string_lossy();
+    let out_path = PathBuf::from(env::var("OUT_DIR").context("env var `OUT_DIR` is undefined")?);
+    let lib_path = out_path.join("libcec");
     let build_kind = if cfg!(debug_assertions) {
         BuildKind::Debug
     } else {
         BuildKind::Release
     };
 
-    dbg!(&lib_path, target_lexicon::HOST, build_kind);
-    println!("cargo:rustc-link-search=native={lib_path_str}");
+    let target = cec_bootstrap::target()?;
+
+    println!("cargo:rustc-check-cfg=cfg(system_libcec)");
+
+    // Building libcec from source is _painful_, so we don't! Targets without a
+    // prebuilt libcec fall back to the system's, if there is one and the
+    // `system-libcec-fallback` feature is enabled.
+    match fetch_libcec(&lib_path, &target, build_kind) {
+        Ok(()) => link_prebuilt(&lib_path, &target, build_kind),
+        Err(e) if e.is::<MissingAssetError>() => link_system(&out_path, &target).map_err(|x| {
+            x.wrap_err(format!(
+                "no prebuilt libcec exists for `{target}`, and no usable system libcec was found"
+            ))
+        }),
+        Err(e) => Err(e.wrap_err("failed to download libcec")),
+    }
+}
+
+/// Statically links the prebuilt libcec, using the bindings checked in for the
+/// target.
+fn link_prebuilt(lib_path: &Path, target: &Triple, build_kind: BuildKind) -> Result<()> {
+    println!("cargo:rustc-env=LIBCEC_VERSION={LIBCEC_VERSION}");
+    println!(
+        "cargo:rustc-link-search=native={}",
+        lib_path.to_string_lossy()
+    );
     println!("cargo:rustc-link-lib=static=cec");
     println!("cargo:rustc-link-lib=static=p8-platform");
 
-    match (target_lexicon::HOST.operating_system, build_kind) {
+    match (target.operating_system, build_kind) {
         (OperatingSystem::Windows, BuildKind::Debug) => {
             println!("cargo:rustc-link-lib=dylib=msvcrtd");
         }
@@ -38,11 +68,51 @@ fn main() -> Result<()> {
         (OperatingSystem::Linux, _) => {
             println!("cargo:rustc-link-lib=dylib=stdc++");
         }
-        _ => return Err(eyre!("unsupported target")),
+        _ => return Err(eyre!("unsupported target `{target}`")),
     };
 
-    // Building libcec from source is _painful_, so we don't!
-    fetch_libcec(&lib_path, build_kind).context("failed to download libcec")?;
+    Ok(())
+}
+
+/// Dynamically links the libcec pkg-config finds, generating bindings from its
+/// headers since none are checked in for the target.
+#[cfg(feature = "system-libcec-fallback")]
+fn link_system(out_path: &Path, target: &Triple) -> Result<()> {
+    use std::fs;
+
+    use cec_bootstrap::bindings;
+
+    let library = pkg_config::Config::new()
+        .atleast_version(MIN_SYSTEM_LIBCEC_VERSION)
+        .probe("libcec")
+        .context("failed to find libcec with pkg-config")?;
+
+    let header_path = out_path.join("wrapper.h");
+    fs::write(&header_path, "#include \"cecc.h\"\n").context(format!(
+        "failed to write `{}`",
+        header_path.to_string_lossy()
+    ))?;
+    bindings::generate(
+        &header_path,
+        &library.include_paths,
+        &out_path.join("bindings.rs"),
+    )
+    .context("failed to generate bindings from the system libcec")?;
+
+    println!("cargo:rustc-env=LIBCEC_VERSION={}", library.version);
+    println!("cargo:rustc-cfg=system_libcec");
+    println!(
+        "cargo:warning=no prebuilt libcec exists for `{target}`, using the system libcec {} \
+         with bindings generated from its headers",
+        library.version
+    );
 
     Ok(())
 }
+
+#[cfg(not(feature = "system-libcec-fallback"))]
+fn link_system(_out_path: &Path, _target: &Triple) -> Result<()> {
+    Err(eyre!(
+        "the system libcec is only used with the `system-libcec-fallback` feature enabled"
+    ))
+}
@@ -1,7 +1,10 @@
 mod bindings {
     #![allow(non_upper_case_globals, non_camel_case_types, non_snake_case)]
     cfg_if::cfg_if! {
-        if #[cfg(all(target_os = "windows", target_arch = "x86_64", target_env = "msvc"))] {
+        // Set by the build script when it fell back to the system libcec.
+        if #[cfg(system_libcec)] {
+            include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+        } else if #[cfg(all(target_os = "windows", target_arch = "x86_64", target_env = "msvc"))] {
             include!("bindings/x86_64-pc-windows-msvc.rs");
         } else if #[cfg(all(target_os = "macos", target_arch = "aarch64"))] {
             include!("bindings/aarch64-apple-darwin.rs");
@@ -15,12 +18,25 @@ mod bindings {
 
 pub use crate::bindings::*;
 
+/// The version of the linked libcec, the bundled one unless the target has no
+/// prebuilt libcec.
+pub const LIBCEC_VERSION: &str = env!("LIBCEC_VERSION");
+
 #[cfg(test)]
 mod tests {
-    use crate::CEC_LIB_VERSION_MAJOR;
+    use crate::{CEC_LIB_VERSION_MAJOR, CEC_LIB_VERSION_MINOR, LIBCEC_VERSION};
 
+    /// Every target's bindings must come from the same libcec, so a binding
+    /// regenerated against the wrong headers fails here.
     #[test]
     fn check_version() {
-        assert_eq!(CEC_LIB_VERSION_MAJOR, 6);
+        assert_eq!((CEC_LIB_VERSION_MAJOR, CEC_LIB_VERSION_MINOR), (6, 0));
+    }
+
+    /// The bindings must match the libcec that's linked.
+    #[test]
+    fn check_linked_version() {
+        let prefix = format!("{CEC_LIB_VERSION_MAJOR}.{CEC_LIB_VERSION_MINOR}.");
+        assert!(LIBCEC_VERSION.starts_with(&prefix));
     }
 }
@@ -1,210 +1,1944 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Display},
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
-use cec::{DeviceKind, LogicalAddress, UserControlCode};
-use color_eyre::eyre::{Context, Result};
-use tokio::sync::{mpsc, oneshot};
+use cec::{
+    ActiveSourceStatus, Alert, DeviceKind, LogicalAddress, Opcode, PhysicalAddress,
+    SystemAudioStatus, UserControlCode, VendorId,
+};
+use color_eyre::eyre::{eyre, Context, Result};
+use serde::Deserialize;
+use tokio::sync::{
+    mpsc::{self, error::SendTimeoutError},
+    oneshot,
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, info, trace, warn, Level};
 
 use crate::{
-    job::{self, SpawnResult},
-    os::{Event, Key},
+    cli, config,
+    job::{self, Recv, SpawnResult},
+    metrics, notify,
+    os::{self, Event, Key},
+    pipeline::{self, Stage},
     Spawn,
 };
 
-pub type CommandTx = mpsc::Sender<Command>;
-pub type CommandRx = mpsc::Receiver<Command>;
-type LastCmd = HashMap<Command, Instant>;
+/// Sends commands to the job, each with the id of the event it was translated
+/// from, see [`pipeline::received`].
+pub type CommandTx = mpsc::Sender<(Command, Option<u64>)>;
+pub type CommandRx = mpsc::Receiver<(Command, Option<u64>)>;
+pub type RemoteTx = mpsc::UnboundedSender<Event>;
+pub type RemoteRx = mpsc::UnboundedReceiver<Event>;
+pub type ConfigTx = mpsc::UnboundedSender<Config>;
+pub type ConfigRx = mpsc::UnboundedReceiver<Config>;
+type LastCmd = HashMap<DebounceKey, Instant>;
+
+/// How many commands [`LastCmd`] holds before stale ones are pruned.
+const LAST_CMD_PRUNE_LEN: usize = 32;
 
 /// Represents a HDMI-CEC job, responsible for communicating with the HDMI-CEC
 /// bus. libcec only works on a single thread, so we can't use an async task.
 pub struct Job {
     cmd_tx: CommandTx,
+    config_tx: ConfigTx,
+    remote_rx: RemoteRx,
+    /// Whether an audio system responded when the job started.
+    has_audio_system: bool,
+    history: History,
 }
 
-/// Represents a HDMI-CEC command.
+/// Represents a HDMI-CEC command. Deserializes from JSON like
+/// `{"PowerOff":null}` or `{"Keypress":["tv","input-select"]}`.
 ///
 /// See: HDMI-CEC 1.3 Supplement 1, page 65.
 /// <https://engineering.purdue.edu/ece477/Archive/2012/Spring/S12-Grp10/Datasheets/CEC_HDMI_Specification.pdf>
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum Command {
     PowerOn,
     PowerOff,
     Focus,
     Press(Button),
     Release(Button),
+    /// Mutes or unmutes the audio system, unlike [`Button::VolumeMute`] which
+    /// toggles it.
+    SetMute(bool),
+    /// Presses and releases a key on a device.
+    Keypress(
+        #[serde(deserialize_with = "config::target")] LogicalAddress,
+        #[serde(deserialize_with = "config::control")] UserControlCode,
+    ),
 }
 
 /// Represents a HDMI-CEC remote control button.
 ///
 /// See: HDMI-CEC 1.3 Supplement 1, page 47.
 /// <https://engineering.purdue.edu/ece477/Archive/2012/Spring/S12-Grp10/Datasheets/CEC_HDMI_Specification.pdf>
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum Button {
     VolumeUp,
     VolumeDown,
     VolumeMute,
 }
 
+/// A step of waking the TV. TVs differ in which messages wake them, so
+/// several may be tried in turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerOnStep {
+    /// Asks libcec to power the TV on, however it sees fit.
+    PowerOnDevices,
+    /// Sends `<Image View On>` to the TV.
+    ImageViewOn,
+    /// Sends `<Text View On>` to the TV.
+    TextViewOn,
+    /// Broadcasts `<Set Stream Path>` to owl's physical address, switching
+    /// the TV's input.
+    SetStreamPath,
+    /// Makes owl the active source, switching the TV's input.
+    ActiveSource,
+}
+
+/// What a command is debounced against. A button's presses and releases share
+/// a key, so a press bouncing right after a release is dropped. Releases are
+/// never dropped, that would leave the button held. Other commands are keyed
+/// by themselves, so e.g. keypresses of different keys don't interfere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum DebounceKey {
+    Button(Button),
+    Command(Command),
+}
+
+/// Represents the HDMI-CEC job configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The device kind owl registers as on the HDMI-CEC bus.
+    pub device_kind: DeviceKind,
+    /// The device kind owl announces when becoming the active source. Defaults
+    /// to [`Config::device_kind`].
+    pub active_source_kind: Option<DeviceKind>,
+    /// The com port of the adapter to connect to, like `/dev/ttyACM0`, or
+    /// `None` to detect it.
+    pub adapter: Option<String>,
+    /// The HDMI port the adapter is connected to.
+    pub hdmi_port: u8,
+    /// Forces the adapter's physical address instead of deriving it from the
+    /// HDMI port, for switches which confuse detection.
+    pub physical_address: Option<PhysicalAddress>,
+    /// Whether to restore the audio system's volume on resume, for audio
+    /// systems which reset their volume when powered off.
+    pub restore_volume: bool,
+    /// The volume, from 0 to 100, the audio system is set to when powering
+    /// on, so the TV never starts at a blasting level. Takes precedence over
+    /// [`Config::restore_volume`].
+    pub power_on_volume: Option<u8>,
+    /// Whether the audio system is unmuted after focusing or powering on, for
+    /// audio systems left muted by a previous session.
+    pub unmute_on_focus: bool,
+    /// Whether the audio system's system audio mode is turned on when owl
+    /// starts, for audio systems which ignore volume keys without it.
+    pub system_audio: bool,
+    /// Whether owl changes the active source. When disabled, owl only powers
+    /// the TV on and off, for users who switch inputs some other way.
+    pub activate_source: bool,
+    /// How long libcec waits for commands owl sends to be acknowledged. Kept
+    /// short so a dead bus doesn't stall the job. Only applies to the frames
+    /// owl builds itself; keypresses, power on and standby to a single device
+    /// go through libcec's helpers, which use libcec's own timeout.
+    pub transmit_timeout: Duration,
+    /// The buttons whose release is sent to the audio system. Some audio
+    /// systems need a release after every keypress, including mute.
+    pub release_buttons: HashSet<Button>,
+    /// How many dispatched commands are kept for diagnostics.
+    pub history_len: usize,
+    /// How long after a button is pressed or released that further presses
+    /// are dropped. Held buttons repeat, so this limits the volume ramp.
+    pub button_debounce: Duration,
+    /// The steps taken to wake the TV, or `None` to pick them by the TV's
+    /// vendor.
+    pub power_on: Option<Vec<PowerOnStep>>,
+    /// Whether runs of identical cmds received in quick succession are logged
+    /// once, with a count of how often they repeated.
+    pub collapse_repeats: bool,
+    /// Where standby is sent on suspend. [`LogicalAddress::BROADCAST`]
+    /// broadcasts it, so e.g. an audio system powers down too.
+    pub standby_target: LogicalAddress,
+    /// The commands sent again if sending fails, since transmits can fail
+    /// transiently on a busy bus. Keypresses go stale quickly, so aren't.
+    pub retry_commands: HashSet<Command>,
+    /// How many times a retried command is attempted before giving up.
+    pub retry_attempts: u8,
+    /// How long to wait before attempting a command again.
+    pub retry_delay: Duration,
+    /// How soon a second mute press must follow the first to power the TV
+    /// off, like libcec's `double_tap_timeout` for remote keys. `None`
+    /// disables the gesture. Both presses still toggle mute, cancelling out.
+    pub mute_double_tap: Option<Duration>,
+    /// How often the adapter is pinged, to notice it being unplugged without
+    /// sending anything on the bus. `None` never pings, leaving it to libcec
+    /// to report the connection lost. Either way, a lost adapter is reopened
+    /// this often, or every [`Config::ADAPTER_REOPEN_INTERVAL`].
+    pub adapter_ping_interval: Option<Duration>,
+    /// How long owl stays on the bus after its last command before going
+    /// back to only monitoring it. `None` stays on the bus all along. While
+    /// monitoring, owl holds no logical address, so the TV remote's keys
+    /// don't reach it and the next command waits for it to register again.
+    pub lazy_active: Option<Duration>,
+}
+
+/// A command the job dispatched, kept for diagnostics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sent {
+    pub cmd: Command,
+    pub time: SystemTime,
+    /// Why dispatching failed, `None` if it succeeded.
+    pub error: Option<String>,
+    /// How many times an unacknowledged broadcast was retried.
+    pub retries: u8,
+    /// How many times the command was attempted, see
+    /// [`Config::retry_commands`].
+    pub attempts: u8,
+}
+
+/// The commands the job most recently dispatched, oldest first. Shared with
+/// the [`Job`] so they can be read while the job runs.
+#[derive(Debug, Clone, Default)]
+pub struct History(Arc<Mutex<VecDeque<Sent>>>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("active source kind `{0:?}` can't be announced when registered as `{1:?}`")]
+    UnannounceableActiveSourceKind(DeviceKind, DeviceKind),
+    #[error("hdmi port {0} is out of range, expected 1 to 15")]
+    InvalidHdmiPort(u8),
+    /// The job didn't take the command in time, it was dropped. Transient.
+    #[error("cec job is busy, dropped `{0:?}`")]
+    Busy(Command),
+    /// The job stopped, no command will be sent again.
+    #[error("cec job stopped")]
+    Stopped,
+}
+
+/// The HDMI-CEC operations owl performs, abstracted so command handling can be
+/// tested without an adapter.
+pub trait Sink {
+    fn set_active_source(&self, kind: DeviceKind) -> cec::Result<ActiveSourceStatus>;
+    fn send_power_on_devices(&self, address: LogicalAddress) -> cec::Result<()>;
+    fn send_standby_devices(&self, address: LogicalAddress) -> cec::Result<()>;
+    fn send_keypress(
+        &self,
+        address: LogicalAddress,
+        key: UserControlCode,
+        wait: bool,
+    ) -> cec::Result<()>;
+    fn send_key_release(&self, address: LogicalAddress, wait: bool) -> cec::Result<()>;
+    fn audio_toggle_mute(&self) -> cec::Result<()>;
+    fn audio_mute(&self) -> cec::Result<cec::VolumeStatus>;
+    fn audio_unmute(&self) -> cec::Result<cec::VolumeStatus>;
+    fn volume_status(&self) -> cec::Result<cec::VolumeStatus>;
+    fn system_audio_mode(&self) -> cec::Result<SystemAudioStatus>;
+    fn set_system_audio_mode(&self, on: bool) -> cec::Result<SystemAudioStatus>;
+    fn poll_device(&self, address: LogicalAddress) -> bool;
+    fn send(
+        &self,
+        destination: LogicalAddress,
+        opcode: Opcode,
+        parameters: &[u8],
+    ) -> cec::Result<()>;
+    fn physical_address(&self) -> cec::Result<PhysicalAddress>;
+    /// Changes the port of `base_device` the adapter is connected to, without
+    /// reconnecting.
+    fn set_hdmi_port(&self, base_device: LogicalAddress, port: u8) -> cec::Result<()>;
+    fn vendor_id(&self, address: LogicalAddress) -> Option<VendorId>;
+    /// Whether owl is the active source.
+    fn is_active_source(&self) -> bool;
+    /// Whether the adapter is still connected.
+    fn is_connected(&self) -> bool;
+    /// Pings the adapter without sending anything on the bus, updating
+    /// [`Sink::is_connected`] with whether it answered.
+    fn ping_adapter(&self) -> cec::Result<()>;
+    /// Reopens the adapter after it was lost, updating
+    /// [`Sink::is_connected`] with whether it opened.
+    fn reopen(&self) -> cec::Result<()>;
+    /// Switches between only monitoring the bus and taking part in it,
+    /// releasing or registering owl's logical address.
+    fn set_monitoring(&self, enable: bool) -> cec::Result<()>;
+}
+
+/// A connection to the HDMI-CEC bus, along with whether libcec last reported
+/// owl as the active source, and whether it reported losing the adapter.
 #[derive(Debug, derive_more::Deref)]
-struct Cec(cec::Connection);
+struct Cec(#[deref] cec::Connection, Arc<AtomicBool>, Arc<AtomicBool>);
 
-impl Job {
-    fn handle_cmd(cec: &Cec, cmd_rx: &mut CommandRx, last_cmd: &mut LastCmd) {
+/// A connection listening to the HDMI-CEC bus without taking part in it, see
+/// [`monitor`]. Listening stops when dropped.
+#[derive(Debug)]
+pub struct Monitor {
+    _cec: Cec,
+}
+
+/// Decodes keypresses from the TV remote into owl events.
+///
+/// libcec calls us from its own thread, so the events are relayed via a
+/// non-blocking channel. Keys owl itself recently sent are ignored, otherwise a
+/// device echoing them back would create a feedback loop.
+#[derive(Debug, Clone)]
+struct Remote {
+    event_tx: RemoteTx,
+    sent: Arc<Mutex<HashMap<Button, Instant>>>,
+}
+
+/// Collapses runs of identical cmds received in quick succession, so TVs
+/// repeating e.g. power status reports don't flood the log.
+#[derive(Debug, Default)]
+struct RepeatFilter {
+    /// The last frame logged, when it was last seen, and how many repeats of
+    /// it were suppressed since.
+    last: Option<(Vec<u8>, Instant, usize)>,
+}
+
+/// Why the job woke up, see [`wait`].
+#[derive(Debug)]
+enum Wake {
+    /// A command, with the id of the event it came from.
+    Cmd(Command, Option<u64>),
+    /// A reloaded config, see [`Job::reconfigure`].
+    Config(Config),
+    /// Something timed is due, see [`Worker::poll`].
+    Poll,
+    /// The job was cancelled, or every sender was dropped.
+    Stop,
+}
+
+/// The power and mute states asked for while the adapter was disconnected,
+/// reapplied once it's back so the TV ends up as the user last asked. Only
+/// the latest of each is kept, and volume presses are dropped since they'd be
+/// stale by then.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Pending {
+    power: Option<Command>,
+    mute: Option<Command>,
+}
+
+/// The state of a running HDMI-CEC job.
+struct Worker<S> {
+    sink: S,
+    config: Config,
+    remote: Remote,
+    last_cmd: LastCmd,
+    /// The audio system's volume before the last suspend.
+    last_volume: Option<u8>,
+    /// The buttons pressed on this connection and not yet released, with where
+    /// the press was sent.
+    held: HashMap<Button, LogicalAddress>,
+    /// Whether the audio system responded when last polled, and when.
+    audio_system: Option<(bool, Instant)>,
+    history: History,
+    /// The TV's vendor, last time it was known.
+    tv_vendor: Option<VendorId>,
+    /// How many broadcasts the current command retried.
+    retries: u8,
+    pending: Pending,
+    /// The pipeline log id of the event the current command came from.
+    pipeline_id: Option<u64>,
+    /// When mute was last pressed, unless that press completed a double tap.
+    last_mute: Option<Instant>,
+    /// When the adapter was last pinged.
+    last_ping: Option<Instant>,
+    /// Whether the connection only monitors the bus, see
+    /// [`Config::lazy_active`].
+    monitoring: bool,
+    /// When a command was last sent.
+    last_sent: Option<Instant>,
+}
+
+impl<S: Sink> Worker<S> {
+    fn new(sink: S, config: Config, remote: Remote, history: History) -> Self {
+        let monitoring = config.lazy_active.is_some();
+        Self {
+            sink,
+            config,
+            remote,
+            last_cmd: LastCmd::new(),
+            last_volume: None,
+            held: HashMap::new(),
+            audio_system: None,
+            history,
+            tv_vendor: None,
+            retries: 0,
+            pending: Pending::default(),
+            pipeline_id: None,
+            last_mute: None,
+            last_ping: None,
+            monitoring,
+            last_sent: None,
+        }
+    }
+
+    fn handle_cmd(&mut self, cmd: Command, pipeline_id: Option<u64>) {
         // Volume up/down events fire continuously if the button is held.
         // Debouncing prevents the channel and CEC bus from getting congested.
-        if let Some(cmd) = cmd_rx.blocking_recv()
-            && let Some(cmd) = Self::debounce_cmd(cmd, last_cmd)
+        self.pipeline_id = pipeline_id;
+        let debounced = debounce_cmd(cmd, &mut self.last_cmd, self.config.button_debounce);
+        if pipeline::enabled() {
+            let stage = Stage::Debounce {
+                command: cmd.to_string(),
+                dropped: debounced.is_none(),
+            };
+            pipeline::record(self.pipeline_id, &stage);
+        }
+        let Some(cmd) = debounced else {
+            metrics::command_debounced();
+            return;
+        };
+
+        if !self.sink.is_connected() {
+            if self.pending.push(cmd) {
+                warn!("adapter disconnected, deferring {cmd:?} until it's back");
+            } else {
+                warn!("adapter disconnected, dropping {cmd:?}");
+            }
+            return;
+        }
+
+        for pending in self.pending.take(cmd) {
+            info!("adapter reconnected, reapplying {pending:?}");
+            self.send(pending);
+        }
+        // Focus is sent on most keys, so only unmute when it switches the TV.
+        let unmute = self.config.unmute_on_focus
+            && match cmd {
+                Command::PowerOn => true,
+                Command::Focus => self.config.activate_source && !self.sink.is_active_source(),
+                _ => false,
+            };
+        self.send(cmd);
+        if self.mute_double_tapped(cmd, Instant::now()) {
+            info!("mute double tapped, powering the tv off...");
+            self.send(Command::PowerOff);
+        }
+        if unmute {
+            self.send(Command::SetMute(false));
+        }
+    }
+
+    /// Returns whether `cmd` is a mute press following the last within
+    /// [`Config::mute_double_tap`]. A triple tap only counts once.
+    fn mute_double_tapped(&mut self, cmd: Command, now: Instant) -> bool {
+        let Some(window) = self.config.mute_double_tap else {
+            return false;
+        };
+        if cmd != Command::Press(Button::VolumeMute) {
+            return false;
+        }
+
+        match self.last_mute.take() {
+            Some(at) if now.saturating_duration_since(at) <= window => true,
+            _ => {
+                self.last_mute = Some(now);
+                false
+            }
+        }
+    }
+
+    /// Returns when something timed is next due, see [`Worker::poll`], or
+    /// `None` if nothing is.
+    fn deadline(&self, now: Instant) -> Option<Instant> {
+        let adapter = self
+            .adapter_interval()
+            .map(|interval| self.last_ping.map_or(now, |at| at + interval));
+        let idle = self
+            .config
+            .lazy_active
+            .filter(|_| !self.monitoring)
+            .map(|idle| self.last_sent.map_or(now, |at| at + idle));
+
+        adapter.into_iter().chain(idle).min()
+    }
+
+    /// Does whatever timed is due by `now`.
+    fn poll(&mut self, now: Instant) {
+        self.poll_adapter(now);
+        self.poll_idle(now);
+    }
+
+    /// Returns how often the adapter is pinged, or reopened while it's lost.
+    fn adapter_interval(&self) -> Option<Duration> {
+        match self.config.adapter_ping_interval {
+            Some(x) => Some(x),
+            None if !self.sink.is_connected() => Some(Config::ADAPTER_REOPEN_INTERVAL),
+            None => None,
+        }
+    }
+
+    /// Pings the adapter once [`Config::adapter_ping_interval`] elapsed, or
+    /// reopens it if it was lost. An unanswered ping marks the adapter
+    /// disconnected, deferring commands until it's reopened.
+    fn poll_adapter(&mut self, now: Instant) {
+        let Some(interval) = self.adapter_interval() else {
+            return;
+        };
+        if self
+            .last_ping
+            .is_some_and(|at| now.saturating_duration_since(at) < interval)
         {
-            debug!("sending command: {cmd:?}");
-            let result = match cmd {
-                Command::PowerOn | Command::Focus => {
-                    cec.set_active_source(DeviceKind::PlaybackDevice)
+            return;
+        }
+
+        self.last_ping = Some(now);
+        if self.sink.is_connected() {
+            if let Err(e) = self.sink.ping_adapter() {
+                error!("adapter didn't answer a ping: {e}");
+            }
+            return;
+        }
+
+        match self.sink.reopen() {
+            Ok(()) => {
+                info!("reopened the adapter");
+                metrics::cec_reconnected();
+                // Reapplied now, rather than with the next command, since
+                // that may not come for a while after a suspend.
+                for pending in self.pending.take_all() {
+                    info!("adapter reconnected, reapplying {pending:?}");
+                    self.send(pending);
                 }
-                Command::PowerOff => cec.send_standby_devices(LogicalAddress::Tv),
-                Command::Press(button) => match button {
-                    Button::VolumeUp => cec.send_keypress(
-                        LogicalAddress::Audiosystem,
-                        UserControlCode::VolumeUp,
-                        false,
-                    ),
-                    Button::VolumeDown => cec.send_keypress(
-                        LogicalAddress::Audiosystem,
-                        UserControlCode::VolumeDown,
-                        false,
-                    ),
-                    Button::VolumeMute => cec.audio_toggle_mute(),
-                },
-                Command::Release(button) => match button {
-                    Button::VolumeDown | Button::VolumeUp => {
-                        cec.send_key_release(LogicalAddress::Audiosystem, false)
-                    }
-                    Button::VolumeMute => Ok(()),
-                },
+            }
+            Err(e) => debug!("failed to reopen the adapter: {e}"),
+        }
+    }
+
+    /// Goes back to only monitoring the bus once no command was sent for
+    /// [`Config::lazy_active`].
+    fn poll_idle(&mut self, now: Instant) {
+        if let Some(idle) = self.config.lazy_active
+            && !self.monitoring
+            && !self
+                .last_sent
+                .is_some_and(|at| now.saturating_duration_since(at) < idle)
+        {
+            self.set_monitoring(true);
+        }
+    }
+
+    /// Switches between only monitoring the bus and taking part in it,
+    /// keeping track of which it is.
+    fn set_monitoring(&mut self, enable: bool) {
+        if self.monitoring == enable {
+            return;
+        }
+
+        let mode = if enable {
+            "monitoring"
+        } else {
+            "taking part in"
+        };
+        match self.sink.set_monitoring(enable) {
+            Ok(()) => {
+                info!("{mode} the bus");
+                self.monitoring = enable;
+            }
+            Err(e) => warn!("failed to switch to {mode} the bus: {e}"),
+        }
+    }
+
+    /// Sends a command, attempting it again if it's one of
+    /// [`Config::retry_commands`], recording it in the history.
+    fn send(&mut self, cmd: Command) {
+        debug!("sending command: {cmd:?}");
+        self.set_monitoring(false);
+        self.last_sent = Some(Instant::now());
+        let max_attempts = if self.config.retry_commands.contains(&cmd) {
+            self.config.retry_attempts.max(1)
+        } else {
+            1
+        };
+
+        self.retries = 0;
+        let mut attempts = 1;
+        let mut result = self.dispatch(cmd);
+        while let Err(e) = &result
+            && attempts < max_attempts
+        {
+            warn!("failed to send {cmd:?}: {e}, retrying ({attempts}/{max_attempts})...");
+            thread::sleep(self.config.retry_delay);
+            attempts += 1;
+            result = self.dispatch(cmd);
+        }
+
+        let sent = Sent {
+            cmd,
+            time: SystemTime::now(),
+            error: result.as_ref().err().map(ToString::to_string),
+            retries: self.retries,
+            attempts,
+        };
+        if pipeline::enabled() {
+            let stage = Stage::Sent {
+                command: cmd.to_string(),
+                error: sent.error.clone(),
+                attempts,
             };
+            pipeline::record(self.pipeline_id, &stage);
+        }
+        self.history.push(sent, self.config.history_len);
 
-            if let Err(e) = result {
-                error!("failed to send cec command: {e}");
+        match result {
+            Ok(()) => {
+                metrics::command_sent(cmd);
+                if let Command::Press(button) | Command::Release(button) = cmd {
+                    self.remote.mark_sent(button);
+                }
             }
+            Err(e) => error!(
+                "failed to send cec command: {e}, recent commands:\n{}",
+                self.history.recent(History::LOGGED)
+            ),
         }
     }
 
-    fn debounce_cmd(cmd: Command, time_by_cmd: &mut HashMap<Command, Instant>) -> Option<Command> {
-        let time = Instant::now();
+    /// Applies a reloaded config. Settings libcec only reads when connecting
+    /// are kept until owl restarts.
+    fn reconfigure(&mut self, mut config: Config) {
+        if let Err(e) = config.validate() {
+            warn!("ignoring reloaded cec config: {e}");
+            return;
+        }
 
-        if let Some(last_time) = time_by_cmd.get_mut(&cmd) {
-            let delta = time.duration_since(*last_time);
-            if let Some(duration) = cmd.debounce_duration()
-                && delta <= duration
+        if config.device_kind != self.config.device_kind {
+            warn!("changing the device kind requires a restart");
+            config.device_kind = self.config.device_kind;
+            config.active_source_kind = self.config.active_source_kind;
+        }
+        if config.adapter != self.config.adapter {
+            warn!("changing the adapter requires a restart");
+            config.adapter.clone_from(&self.config.adapter);
+        }
+        // A forced physical address leaves libcec ignoring the port.
+        if config.hdmi_port != self.config.hdmi_port && self.config.physical_address.is_none() {
+            match self
+                .sink
+                .set_hdmi_port(LogicalAddress::Tv, config.hdmi_port)
             {
-                return None;
+                Ok(()) => info!("changed hdmi port to {}", config.hdmi_port),
+                Err(e) => {
+                    warn!("failed to change the hdmi port, it requires a restart: {e}");
+                    config.hdmi_port = self.config.hdmi_port;
+                }
+            }
+        }
+        if config.physical_address != self.config.physical_address {
+            warn!("changing the physical address requires a restart");
+            config.physical_address = self.config.physical_address;
+        }
+        if config.transmit_timeout != self.config.transmit_timeout {
+            warn!("changing the transmit timeout requires a restart");
+            config.transmit_timeout = self.config.transmit_timeout;
+        }
+        if config.collapse_repeats != self.config.collapse_repeats {
+            warn!("changing whether repeats are collapsed requires a restart");
+            config.collapse_repeats = self.config.collapse_repeats;
+        }
+
+        debug!("applying cec config: {config:?}");
+        self.config = config;
+        self.last_cmd.clear();
+        if self.config.lazy_active.is_none() {
+            self.set_monitoring(false);
+        }
+    }
+
+    fn dispatch(&mut self, cmd: Command) -> cec::Result<()> {
+        let sink = &self.sink;
+        match cmd {
+            Command::PowerOn => {
+                self.power_on()?;
+                self.restore_volume();
+                Ok(())
+            }
+            Command::Focus if self.config.activate_source => {
+                if self.sink.is_active_source() {
+                    debug!("already the active source, not activating");
+                    Ok(())
+                } else {
+                    self.activate_source()
+                }
+            }
+            Command::Focus => Ok(()),
+            Command::PowerOff => {
+                self.save_volume();
+                self.sink.send_standby_devices(self.config.standby_target)
+            }
+            Command::Press(button) => {
+                let target = self.volume_target();
+                let sink = &self.sink;
+                match (button, target) {
+                    (Button::VolumeUp, _) => {
+                        sink.send_keypress(target, UserControlCode::VolumeUp, false)
+                    }
+                    (Button::VolumeDown, _) => {
+                        sink.send_keypress(target, UserControlCode::VolumeDown, false)
+                    }
+                    (Button::VolumeMute, LogicalAddress::Audiosystem) => sink.audio_toggle_mute(),
+                    (Button::VolumeMute, _) => {
+                        sink.send_keypress(target, UserControlCode::Mute, false)
+                    }
+                }?;
+                self.held.insert(button, target);
+                Ok(())
+            }
+            Command::Release(button) => {
+                // A release without a press, e.g. one held before reconnecting,
+                // would release whatever the audio system thinks is held.
+                let Some(target) = self.held.remove(&button) else {
+                    debug!("ignoring release of {button:?}, it wasn't pressed");
+                    return Ok(());
+                };
+
+                // Only the audio system's mute is a toggle, sent without a
+                // keypress to release.
+                let keypress =
+                    button == Button::VolumeMute && target != LogicalAddress::Audiosystem;
+                if keypress || self.config.release_buttons.contains(&button) {
+                    self.sink.send_key_release(target, false)
+                } else {
+                    Ok(())
+                }
+            }
+            Command::SetMute(muted) => self.set_mute(muted),
+            Command::Keypress(address, key) => {
+                sink.send_keypress(address, key, false)?;
+                sink.send_key_release(address, false)
+            }
+        }
+    }
+
+    /// Wakes the TV with the configured steps, otherwise those known to work
+    /// for its vendor. Steps switching the input are skipped unless owl
+    /// activates the source. Succeeds if any step was sent.
+    fn power_on(&mut self) -> cec::Result<()> {
+        if let Some(vendor) = self.sink.vendor_id(LogicalAddress::Tv) {
+            self.tv_vendor = Some(vendor);
+        }
+
+        let mut steps: Vec<_> = self
+            .config
+            .power_on
+            .clone()
+            .unwrap_or_else(|| PowerOnStep::for_vendor(self.tv_vendor).to_vec())
+            .into_iter()
+            .filter(|x| self.config.activate_source || !x.switches_input())
+            .collect();
+        if steps.is_empty() {
+            steps.push(PowerOnStep::PowerOnDevices);
+        }
+        info!(
+            "powering on {} tv with {steps:?}",
+            self.tv_vendor
+                .map_or_else(|| "unknown".to_owned(), |x| format!("{x:?}"))
+        );
+
+        let mut result = Ok(());
+        let mut sent = false;
+        for step in steps {
+            match self.power_on_step(step) {
+                Ok(()) => sent = true,
+                Err(e) => {
+                    warn!("failed to power on with {step:?}: {e}");
+                    result = Err(e);
+                }
+            }
+        }
+
+        if sent {
+            Ok(())
+        } else {
+            result
+        }
+    }
+
+    fn power_on_step(&mut self, step: PowerOnStep) -> cec::Result<()> {
+        let sink = &self.sink;
+        match step {
+            PowerOnStep::PowerOnDevices => sink.send_power_on_devices(LogicalAddress::Tv),
+            PowerOnStep::ImageViewOn => sink.send(LogicalAddress::Tv, Opcode::ImageViewOn, &[]),
+            PowerOnStep::TextViewOn => sink.send(LogicalAddress::Tv, Opcode::TextViewOn, &[]),
+            PowerOnStep::SetStreamPath => {
+                let address = sink.physical_address()?;
+                sink.send(
+                    LogicalAddress::BROADCAST,
+                    Opcode::SetStreamPath,
+                    &address.to_be_bytes(),
+                )
+            }
+            PowerOnStep::ActiveSource => self.activate_source(),
+        }
+    }
+
+    /// Makes owl the active source. A TV can miss the broadcast while it's
+    /// waking, so it's retried, then owl falls back to asking the TV to switch
+    /// to its input.
+    fn activate_source(&mut self) -> cec::Result<()> {
+        const RETRIES: u8 = 1;
+        const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+        let kind = self.config.active_source_kind();
+        loop {
+            match self.sink.set_active_source(kind)? {
+                ActiveSourceStatus::Acknowledged => return Ok(()),
+                ActiveSourceStatus::NotAcknowledged => metrics::active_source_unacknowledged(),
+            }
+
+            if self.retries >= RETRIES {
+                break;
+            }
+            self.retries += 1;
+            warn!("active source wasn't acknowledged, retrying...");
+            thread::sleep(RETRY_DELAY);
+        }
+
+        warn!(
+            "active source wasn't acknowledged, falling back to {:?}",
+            PowerOnStep::SetStreamPath
+        );
+        self.power_on_step(PowerOnStep::SetStreamPath)
+    }
+
+    /// Turns the audio system's system audio mode on if it's off. Does nothing
+    /// without an audio system.
+    fn enable_system_audio(&mut self) {
+        self.set_monitoring(false);
+        self.last_sent = Some(Instant::now());
+        if self.volume_target() != LogicalAddress::Audiosystem {
+            debug!("no audio system, not enabling system audio mode");
+            return;
+        }
+
+        match self.sink.system_audio_mode() {
+            Ok(SystemAudioStatus::On) => debug!("system audio mode already on"),
+            Ok(SystemAudioStatus::Off) => match self.sink.set_system_audio_mode(true) {
+                Ok(SystemAudioStatus::On) => info!("turned system audio mode on"),
+                Ok(SystemAudioStatus::Off) => {
+                    warn!("audio system refused to turn system audio mode on");
+                }
+                Err(e) => warn!("failed to turn system audio mode on: {e}"),
+            },
+            Err(e) => warn!("failed to get system audio mode: {e}"),
+        }
+    }
+
+    /// Returns where volume keys are sent: the audio system if one responds,
+    /// otherwise the TV. Polling goes over the bus, so the result is cached
+    /// briefly rather than polling every command.
+    fn volume_target(&mut self) -> LogicalAddress {
+        const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+        let present = match self.audio_system {
+            Some((present, time)) if time.elapsed() < POLL_INTERVAL => present,
+            _ => {
+                let present = self.sink.poll_device(LogicalAddress::Audiosystem);
+                if self.audio_system.map(|(x, _)| x) != Some(present) {
+                    debug!("audio system present: {present}");
+                    os::set_volume_to_tv(!present);
+                }
+                self.audio_system = Some((present, Instant::now()));
+                present
             }
+        };
+
+        if present {
+            LogicalAddress::Audiosystem
+        } else {
+            LogicalAddress::Tv
+        }
+    }
 
-            *last_time = time;
+    /// Mutes or unmutes the audio system. Some audio systems don't honor
+    /// discrete mute, so if the reported status doesn't match, mute is toggled
+    /// instead.
+    fn set_mute(&self, muted: bool) -> cec::Result<()> {
+        let status = if muted {
+            self.sink.audio_mute()?
         } else {
-            time_by_cmd.insert(cmd, time);
+            self.sink.audio_unmute()?
+        };
+
+        if status.muted != muted {
+            debug!("audio system ignored discrete mute, toggling instead...");
+            self.sink.audio_toggle_mute()?;
+        }
+
+        Ok(())
+    }
+
+    /// Remembers the audio system's volume, so it can be restored on resume.
+    fn save_volume(&mut self) {
+        if !self.config.restore_volume {
+            return;
+        }
+
+        match self.sink.volume_status() {
+            Ok(status) => {
+                debug!("saving volume: {status:?}");
+                self.last_volume = status.volume;
+            }
+            Err(e) => warn!("failed to save volume: {e}"),
+        }
+    }
+
+    /// Sets the audio system's volume to [`Config::power_on_volume`], or else
+    /// restores the volume saved on suspend, by pressing the volume keys.
+    fn restore_volume(&mut self) {
+        let saved = self.last_volume.take();
+        let Some(target) = self.config.power_on_volume.or(saved) else {
+            return;
+        };
+
+        let current = match self.sink.volume_status() {
+            Ok(cec::VolumeStatus {
+                volume: Some(x), ..
+            }) => x,
+            Ok(_) => {
+                warn!("failed to restore volume: volume unknown");
+                return;
+            }
+            Err(e) => {
+                warn!("failed to restore volume: {e}");
+                return;
+            }
+        };
+
+        let Some((key, count)) = volume_steps(current, target) else {
+            return;
+        };
+
+        debug!("setting volume from {current} to {target}...");
+        for _ in 0..count {
+            let result = self
+                .sink
+                .send_keypress(LogicalAddress::Audiosystem, key, false)
+                .and_then(|()| {
+                    self.sink
+                        .send_key_release(LogicalAddress::Audiosystem, false)
+                });
+            if let Err(e) = result {
+                warn!("failed to restore volume: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Returns the volume key and how many times to press it to move the volume
+/// from `current` to `target`. Assumes each press moves the volume by one,
+/// capped to the full 0-100 range so a misbehaving audio system can't cause a
+/// flood of presses.
+fn volume_steps(current: u8, target: u8) -> Option<(UserControlCode, u8)> {
+    const MAX_STEPS: u8 = 100;
+
+    let key = match current.cmp(&target) {
+        std::cmp::Ordering::Less => UserControlCode::VolumeUp,
+        std::cmp::Ordering::Greater => UserControlCode::VolumeDown,
+        std::cmp::Ordering::Equal => return None,
+    };
+
+    Some((key, current.abs_diff(target).min(MAX_STEPS)))
+}
+
+/// Waits for the next command or reloaded config, or until `deadline`.
+/// Blocking rather than polling keeps the job asleep while there's nothing to
+/// do. A reloaded config wins over a command, so the command sees it.
+#[allow(clippy::redundant_pub_crate)]
+async fn wait(
+    cmd_rx: &mut CommandRx,
+    config_rx: &mut ConfigRx,
+    deadline: Option<Instant>,
+    run_token: &CancellationToken,
+) -> Wake {
+    let due = async {
+        match deadline {
+            Some(at) => tokio::time::sleep_until(at.into()).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        biased;
+        () = run_token.cancelled() => Wake::Stop,
+        Some(config) = config_rx.recv() => Wake::Config(config),
+        cmd = cmd_rx.recv() => cmd.map_or(Wake::Stop, |(cmd, id)| Wake::Cmd(cmd, id)),
+        () = due => Wake::Poll,
+    }
+}
+
+/// Drops `cmd` if another with the same [`DebounceKey`] passed too recently.
+fn debounce_cmd(
+    cmd: Command,
+    last_cmd: &mut LastCmd,
+    button_debounce: Duration,
+) -> Option<Command> {
+    debounce_cmd_at(cmd, last_cmd, button_debounce, Instant::now())
+}
+
+/// Like [`debounce_cmd`], for a command passing at `time`. Once the map grows
+/// past [`LAST_CMD_PRUNE_LEN`], entries too old to debounce anything are
+/// dropped, so parameterized commands don't accumulate.
+fn debounce_cmd_at(
+    cmd: Command,
+    last_cmd: &mut LastCmd,
+    button_debounce: Duration,
+    time: Instant,
+) -> Option<Command> {
+    let key = DebounceKey::from(cmd);
+
+    if let Some(last_time) = last_cmd.get(&key)
+        && let Some(duration) = cmd.debounce_duration(button_debounce)
+        && time.duration_since(*last_time) <= duration
+    {
+        return None;
+    }
+
+    last_cmd.insert(key, time);
+    if last_cmd.len() > LAST_CMD_PRUNE_LEN {
+        let window = Command::max_debounce_duration(button_debounce);
+        last_cmd.retain(|_, last_time| time.duration_since(*last_time) <= window);
+    }
+
+    Some(cmd)
+}
+
+impl Pending {
+    /// Keeps `cmd` if it's a power or mute state, replacing the previous one.
+    /// Returns whether it was kept.
+    fn push(&mut self, cmd: Command) -> bool {
+        match cmd {
+            Command::PowerOn | Command::PowerOff => self.power = Some(cmd),
+            Command::SetMute(_) => self.mute = Some(cmd),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Takes the kept states, except any `next` supersedes.
+    fn take(&mut self, next: Command) -> impl Iterator<Item = Command> {
+        match next {
+            Command::PowerOn | Command::PowerOff => self.power = None,
+            Command::SetMute(_) => self.mute = None,
+            _ => {}
         }
 
-        Some(cmd)
+        self.take_all()
+    }
+
+    /// Takes all the kept states.
+    fn take_all(&mut self) -> impl Iterator<Item = Command> {
+        let pending = mem::take(self);
+        pending.power.into_iter().chain(pending.mute)
     }
 }
 
 impl Spawn for Job {
+    type Config = Config;
+
     /// Spawns a new HDMI-CEC job. The job runs on a thread.
-    async fn spawn(run_token: CancellationToken) -> SpawnResult<Self> {
-        let (cmd_tx, mut cmd_rx) = mpsc::channel::<Command>(8);
+    async fn spawn(run_token: CancellationToken, config: Config) -> SpawnResult<Self> {
+        config.validate()?;
+
+        let (cmd_tx, mut cmd_rx) = mpsc::channel::<(Command, Option<u64>)>(8);
+        let (config_tx, mut config_rx) = mpsc::unbounded_channel::<Config>();
+        let (remote_tx, remote_rx) = mpsc::unbounded_channel::<Event>();
         let (ready_tx, ready_rx) = oneshot::channel::<Result<()>>();
+        let (audio_system_tx, audio_system_rx) = oneshot::channel::<bool>();
+        let history = History::default();
+        let worker_history = history.clone();
 
         debug!("spawning cec job...");
-        let handle = thread::spawn(move || {
+        let runtime = tokio::runtime::Handle::current();
+        let handle = job::spawn_thread("owl-cec", move || {
             debug!("cec job starting...");
 
-            let mut last_cmd = LastCmd::new();
             let run_token = run_token;
-            let cec = job::send_ready_status(ready_tx, Cec::new)?;
+            let remote = Remote::new(remote_tx);
+            let cec = job::send_ready_status(ready_tx, || {
+                let cec = Cec::new(&config, remote.clone())?;
+                audio_system_tx
+                    .send(cec.has_audio_system())
+                    .map_err(|_| eyre!("failed to send audio system status to task"))?;
+                Ok(cec)
+            })?;
+            // Only asked once the job is ready, the TV may take a while to
+            // answer, and other commands connecting don't report it.
+            match cec.cec_version(LogicalAddress::Tv) {
+                Ok(version) => info!("TV reports CEC {version}"),
+                Err(e) => debug!("failed to get the tv's cec version: {e}"),
+            }
+            let mut worker = Worker::new(cec, config, remote, worker_history);
+            if worker.config.system_audio {
+                worker.enable_system_audio();
+            }
 
             loop {
-                if run_token.is_cancelled() {
-                    debug!("stopping cec job...");
-                    break;
+                let deadline = worker.deadline(Instant::now());
+                match runtime.block_on(wait(&mut cmd_rx, &mut config_rx, deadline, &run_token)) {
+                    Wake::Cmd(cmd, id) => worker.handle_cmd(cmd, id),
+                    Wake::Config(config) => worker.reconfigure(config),
+                    Wake::Poll => worker.poll(Instant::now()),
+                    Wake::Stop => {
+                        debug!("stopping cec job...");
+                        break;
+                    }
                 }
-
-                Self::handle_cmd(&cec, &mut cmd_rx, &mut last_cmd);
-                std::thread::sleep(Duration::from_millis(1));
             }
 
             Ok(())
-        });
+        })?;
 
         ready_rx
             .await
             .context("failed to read job status")?
             .context("job failed to start")?;
+        let has_audio_system = audio_system_rx
+            .await
+            .context("failed to receive audio system status from job")?;
         debug!("cec job ready!");
 
-        Ok((handle, Self { cmd_tx }))
+        Ok((
+            handle,
+            Self {
+                cmd_tx,
+                config_tx,
+                remote_rx,
+                has_audio_system,
+                history,
+            },
+        ))
     }
 }
 
-impl Command {
-    const fn debounce_duration(self) -> Option<Duration> {
-        match self {
-            Self::Press(_) | Self::Release(_) => Some(Duration::from_millis(200)),
-            Self::Focus => Some(Duration::from_secs(3)),
-            _ => None,
+impl Config {
+    /// How long owl stays on the bus after its last command by default, see
+    /// [`Config::lazy_active`].
+    pub const LAZY_ACTIVE_IDLE: Duration = Duration::from_secs(30);
+    /// How often a lost adapter is reopened without
+    /// [`Config::adapter_ping_interval`].
+    pub const ADAPTER_REOPEN_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Returns the device kind owl announces when becoming the active source.
+    #[must_use]
+    pub fn active_source_kind(&self) -> DeviceKind {
+        self.active_source_kind.unwrap_or(self.device_kind)
+    }
+
+    /// Validates the configuration. Announcing a source kind owl didn't
+    /// register as is fine, libcec then announces the device it registered.
+    /// Only the TV can announce itself as the TV though, and only a source
+    /// can be announced when not the TV.
+    pub fn validate(&self) -> Result<(), Error> {
+        let active_source_kind = self.active_source_kind();
+        if (active_source_kind == DeviceKind::Tv) != (self.device_kind == DeviceKind::Tv) {
+            return Err(Error::UnannounceableActiveSourceKind(
+                active_source_kind,
+                self.device_kind,
+            ));
+        }
+        if !cec::HDMI_PORTS.contains(&self.hdmi_port) {
+            return Err(Error::InvalidHdmiPort(self.hdmi_port));
         }
+
+        Ok(())
     }
 }
 
-impl job::Send<Command> for Job {
-    async fn send(&self, cmd: Command) -> Result<()> {
-        Ok(self.cmd_tx.send(cmd).await?)
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            device_kind: DeviceKind::RecordingDevice,
+            active_source_kind: None,
+            adapter: None,
+            hdmi_port: 2,
+            physical_address: None,
+            restore_volume: false,
+            power_on_volume: None,
+            unmute_on_focus: false,
+            system_audio: false,
+            activate_source: true,
+            transmit_timeout: Duration::from_secs(1),
+            release_buttons: HashSet::from([Button::VolumeUp, Button::VolumeDown]),
+            history_len: 64,
+            power_on: None,
+            button_debounce: Duration::from_millis(200),
+            standby_target: LogicalAddress::Tv,
+            collapse_repeats: true,
+            retry_commands: HashSet::from([Command::PowerOn, Command::PowerOff, Command::Focus]),
+            retry_attempts: 3,
+            retry_delay: Duration::from_millis(100),
+            mute_double_tap: None,
+            adapter_ping_interval: None,
+            lazy_active: None,
+        }
     }
 }
 
-impl Cec {
-    pub fn new() -> Result<Self> {
-        debug!("connecting to cec...");
-        let connection = cec::Connection::builder()
-            .detect_device(true)
-            .name("owl".to_owned())
-            .kind(DeviceKind::RecordingDevice)
-            .activate_source(false)
-            .on_key_press(Box::new(Self::on_key_press))
-            .on_command_received(Box::new(Self::on_command_received))
-            .on_log_message(Box::new(Self::on_log_level))
-            .hdmi_port(2)
-            .connect()
-            .context("failed to connect to cec")?;
+/// Lists the adapters libcec finds, without connecting to any.
+pub fn adapters(config: &Config) -> Result<Vec<cec::Adapter>> {
+    Cec::builder(config)
+        .adapters()
+        .context("failed to list cec adapters")
+}
+
+/// Connects to the HDMI-CEC bus and returns the devices on it, without
+/// starting a job.
+pub fn scan(config: &Config) -> Result<Vec<cec::DeviceInfo>> {
+    Cec::connect(Cec::builder(config))?
+        .scan()
+        .context("failed to scan cec bus")
+}
+
+/// Connects to the HDMI-CEC bus in monitor mode, relaying every cmd seen on
+/// the bus along with when it was received. With a `filter`, only cmds
+/// involving it are relayed, see [`cec::Cmd::involves`].
+pub fn monitor(
+    config: &Config,
+    filter: Option<LogicalAddress>,
+    cmd_tx: mpsc::UnboundedSender<(Instant, cec::Cmd)>,
+) -> Result<Monitor> {
+    let mut repeats = config.collapse_repeats.then(RepeatFilter::default);
+    let cec = Cec::connect(
+        Cec::builder(config)
+            .monitor_only(true)
+            .on_command_received(Box::new(move |cmd| {
+                let time = Instant::now();
+                Cec::on_command_received(repeats.as_mut(), &cmd);
+                if filter.is_some_and(|x| !cmd.involves(x)) {
+                    return;
+                }
+                if cmd_tx.send((time, cmd)).is_err() {
+                    error!("failed to relay command: monitor rx closed");
+                }
+            })),
+    )?;
+
+    Ok(Monitor { _cec: cec })
+}
+
+/// A connection registered as the TV, see [`connect_tv`].
+#[cfg(feature = "tv-emulator")]
+#[derive(Debug)]
+pub struct Tv(Cec);
+
+/// Connects to the HDMI-CEC bus registered as the TV, relaying every cmd
+/// received. libcec answers queries like the OSD name and vendor id itself,
+/// with those of `tv`, the rest are left to the caller to reply to with
+/// [`Tv::transmit`].
+#[cfg(feature = "tv-emulator")]
+pub fn connect_tv(
+    config: &Config,
+    tv: &config::TvEmulator,
+    cmd_tx: mpsc::UnboundedSender<cec::Cmd>,
+) -> Result<Tv> {
+    let mut repeats = config.collapse_repeats.then(RepeatFilter::default);
+    let cec = Cec::connect(
+        Cec::builder(config)
+            .kind(DeviceKind::Tv)
+            .name(tv.name.clone())
+            .tv_vendor(tv.vendor_id)
+            .on_command_received(Box::new(move |cmd| {
+                Cec::on_command_received(repeats.as_mut(), &cmd);
+                if cmd_tx.send(cmd).is_err() {
+                    error!("failed to relay command: tv rx closed");
+                }
+            })),
+    )?;
 
-        debug!("connected to cec!");
-        Ok(Self(connection))
+    Ok(Tv(cec))
+}
+
+#[cfg(feature = "tv-emulator")]
+impl Tv {
+    /// Sends a cmd with the configured transmit timeout.
+    pub fn transmit(&self, cmd: cec::Cmd) -> Result<()> {
+        let cmd = cmd.with_transmit_timeout(self.0.transmit_timeout());
+        self.0.transmit(cmd).context("failed to transmit reply")
+    }
+}
+
+/// A connection driven directly rather than by a job, see [`connect_session`].
+#[cfg(feature = "repl")]
+pub struct Session {
+    worker: Worker<Cec>,
+    _remote_rx: RemoteRx,
+}
+
+/// Connects to the HDMI-CEC bus for sending commands one at a time, like the
+/// job would, without starting a job.
+#[cfg(feature = "repl")]
+pub fn connect_session(config: &Config) -> Result<Session> {
+    config.validate()?;
+    // Commands are dispatched straight away, so the session stays on the bus
+    // rather than waiting to register for each.
+    let config = Config {
+        lazy_active: None,
+        ..config.clone()
+    };
+    let (remote_tx, remote_rx) = mpsc::unbounded_channel();
+    let remote = Remote::new(remote_tx);
+    let cec = Cec::new(&config, remote.clone())?;
+
+    Ok(Session {
+        worker: Worker::new(cec, config, remote, History::default()),
+        _remote_rx: remote_rx,
+    })
+}
+
+#[cfg(feature = "repl")]
+impl Session {
+    /// Dispatches a command, like the job would once it's debounced.
+    pub fn send(&mut self, cmd: Command) -> Result<()> {
+        self.worker
+            .dispatch(cmd)
+            .with_context(|| format!("failed to send {cmd}"))
     }
 
-    fn on_key_press(keypress: cec::Keypress) {
-        trace!(target: "libcec", "key pressed: {:?}", keypress);
+    /// Returns the devices on the bus.
+    pub fn scan(&self) -> Result<Vec<cec::DeviceInfo>> {
+        self.worker.sink.scan().context("failed to scan cec bus")
     }
 
-    #[allow(clippy::needless_pass_by_value)]
-    fn on_command_received(cmd: cec::Cmd) {
-        trace!(target: "libcec", "command received: {:?}", cmd);
+    /// Shows `message` on the TV for its default time.
+    pub fn set_osd_string(&self, message: &str) -> Result<()> {
+        self.worker
+            .sink
+            .set_osd_string(
+                LogicalAddress::Tv,
+                cec::DisplayControl::DisplayForDefaultTime,
+                message,
+            )
+            .context("failed to show osd string")
     }
 
-    #[allow(clippy::needless_pass_by_value)]
-    fn on_log_level(log: cec::LogMsg) {
-        const TARGET: &str = "libcec";
-        match log.level {
-            cec::LogLevel::Error => error!(target: TARGET, "{}", log.message),
-            cec::LogLevel::Warning => warn!(target: TARGET, "{}", log.message),
-            cec::LogLevel::Notice => trace!(target: TARGET, "{}", log.message),
-            cec::LogLevel::Traffic => trace!(target: TARGET, "{}", log.message),
-            cec::LogLevel::Debug => debug!(target: TARGET, "{}", log.message),
-            cec::LogLevel::All => trace!(target: TARGET, "{}", log.message),
-        }
+    /// Sends a raw frame, see [`cec::Cmd::from_frame`].
+    pub fn transmit_raw(&self, frame: &[u8]) -> Result<()> {
+        self.worker
+            .sink
+            .transmit_raw(frame)
+            .context("failed to send raw frame")
+    }
+}
+
+/// Connects to the HDMI-CEC bus and sends a raw frame, without starting a
+/// job.
+pub fn send_raw(config: &Config, frame: &[u8]) -> Result<()> {
+    Cec::connect(Cec::builder(config))?
+        .transmit_raw(frame)
+        .context("failed to send raw frame")
+}
+
+/// Returns the frame [`send_raw`] would send, without connecting to the bus.
+pub fn dry_run_raw(frame: &[u8]) -> Result<Vec<u8>> {
+    cec::Cmd::from_frame(frame)
+        .and_then(cec::Cmd::dry_run)
+        .context("invalid raw frame")
+}
+
+impl PowerOnStep {
+    /// Returns the steps known to wake TVs from `vendor`.
+    #[must_use]
+    pub const fn for_vendor(vendor: Option<VendorId>) -> &'static [Self] {
+        match vendor {
+            // Samsung TVs ignore becoming the active source while off.
+            Some(VendorId::Samsung) => &[Self::ImageViewOn, Self::ActiveSource],
+            // LG TVs wake, but don't always switch input without a stream path.
+            Some(VendorId::Lg) => &[Self::ImageViewOn, Self::SetStreamPath, Self::ActiveSource],
+            Some(VendorId::Sony) => &[Self::TextViewOn, Self::ActiveSource],
+            _ => &[Self::ActiveSource],
+        }
+    }
+
+    /// Whether the step switches the TV's input.
+    #[must_use]
+    pub const fn switches_input(self) -> bool {
+        matches!(self, Self::SetStreamPath | Self::ActiveSource)
+    }
+}
+
+impl Command {
+    const KEYPRESS_DEBOUNCE: Duration = Duration::from_millis(200);
+    const FOCUS_DEBOUNCE: Duration = Duration::from_secs(3);
+
+    const fn debounce_duration(self, button_debounce: Duration) -> Option<Duration> {
+        match self {
+            Self::Press(_) => Some(button_debounce),
+            Self::Keypress(..) => Some(Self::KEYPRESS_DEBOUNCE),
+            Self::Focus => Some(Self::FOCUS_DEBOUNCE),
+            _ => None,
+        }
+    }
+
+    /// The longest any command is debounced for.
+    fn max_debounce_duration(button_debounce: Duration) -> Duration {
+        button_debounce
+            .max(Self::KEYPRESS_DEBOUNCE)
+            .max(Self::FOCUS_DEBOUNCE)
+    }
+
+    /// Returns every command, for listing what owl can send. Keypresses
+    /// aren't listed since they take a free device and key.
+    #[must_use]
+    pub fn all() -> Vec<Self> {
+        let mut all = vec![Self::PowerOn, Self::PowerOff, Self::Focus];
+        all.extend(Button::ALL.map(Self::Press));
+        all.extend(Button::ALL.map(Self::Release));
+        all.extend([Self::SetMute(true), Self::SetMute(false)]);
+        all
+    }
+}
+
+/// Writes the command's name, like `power-on` or `press:volume-up`.
+impl Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::PowerOn => f.write_str("power-on"),
+            Self::PowerOff => f.write_str("power-off"),
+            Self::Focus => f.write_str("focus"),
+            Self::Press(button) => write!(f, "press:{}", button.name()),
+            Self::Release(button) => write!(f, "release:{}", button.name()),
+            Self::SetMute(true) => f.write_str("mute"),
+            Self::SetMute(false) => f.write_str("unmute"),
+            Self::Keypress(address, code) => write!(
+                f,
+                "keypress:{}:{}",
+                cli::kebab_case(&format!("{address:?}")),
+                cli::kebab_case(&format!("{code:?}"))
+            ),
+        }
+    }
+}
+
+impl From<Command> for DebounceKey {
+    fn from(cmd: Command) -> Self {
+        match cmd {
+            Command::Press(button) | Command::Release(button) => Self::Button(button),
+            cmd => Self::Command(cmd),
+        }
+    }
+}
+
+impl Job {
+    /// Whether an audio system responded when the job started. Without one,
+    /// volume commands go nowhere.
+    #[must_use]
+    pub const fn has_audio_system(&self) -> bool {
+        self.has_audio_system
+    }
+
+    /// Returns the commands the job most recently dispatched, oldest first.
+    #[must_use]
+    pub fn history(&self) -> Vec<Sent> {
+        self.history.entries()
+    }
+
+    /// Returns a sender for commands, for other jobs to send through.
+    #[must_use]
+    pub fn command_tx(&self) -> CommandTx {
+        self.cmd_tx.clone()
+    }
+
+    /// Sends a command translated from the event with pipeline log id `id`,
+    /// see [`job::Send::send`].
+    pub async fn send_traced(&self, cmd: Command, id: Option<u64>) -> Result<()> {
+        const SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+        self.cmd_tx
+            .send_timeout((cmd, id), SEND_TIMEOUT)
+            .await
+            .map_err(|e| match e {
+                SendTimeoutError::Timeout((cmd, _)) => Error::Busy(cmd).into(),
+                SendTimeoutError::Closed(_) => Error::Stopped.into(),
+            })
+    }
+
+    /// Sends the commands translated from the event with pipeline log id `id`
+    /// in turn. A busy job drops a command, the rest are still sent. A stopped
+    /// job won't take any again, so `run_token` is cancelled to stop owl
+    /// rather than dropping every command from then on.
+    pub async fn send_all(
+        &self,
+        cmds: impl IntoIterator<Item = Command>,
+        id: Option<u64>,
+        run_token: &CancellationToken,
+    ) -> Result<()> {
+        for cmd in cmds {
+            match self.send_traced(cmd, id).await {
+                Ok(()) => {}
+                Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::Stopped)) => {
+                    error!("cec job stopped, stopping owl...");
+                    run_token.cancel();
+                    return Err(e);
+                }
+                Err(e) => warn!("{e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a reloaded config, waking the job even while idle.
+    pub fn reconfigure(&self, config: Config) -> Result<()> {
+        self.config_tx
+            .send(config)
+            .map_err(|_| eyre!("config rx closed"))
+    }
+}
+
+impl job::Send<Command> for Job {
+    /// Sends a command to the job. If the job is busy, waits briefly before
+    /// dropping the command with [`Error::Busy`]. [`Error::Stopped`] means the
+    /// job died.
+    async fn send(&self, cmd: Command) -> Result<()> {
+        self.send_traced(cmd, None).await
+    }
+}
+
+impl Recv<Event> for Job {
+    /// Receives an event decoded from the TV remote.
+    async fn recv(&mut self) -> Result<Event> {
+        self.remote_rx
+            .recv()
+            .await
+            .ok_or_else(|| eyre!("remote rx closed"))
+    }
+}
+
+impl RepeatFilter {
+    /// How soon a frame must repeat to be suppressed.
+    const WINDOW: Duration = Duration::from_secs(2);
+
+    /// Records a received frame. Returns `None` if it repeats the last one and
+    /// should be suppressed, otherwise how many repeats of the last one were
+    /// suppressed. A run is only summarized once something else arrives.
+    fn push(&mut self, frame: Vec<u8>, now: Instant) -> Option<usize> {
+        if let Some((last, seen, repeats)) = &mut self.last
+            && *last == frame
+            && now.saturating_duration_since(*seen) <= Self::WINDOW
+        {
+            *seen = now;
+            *repeats += 1;
+            return None;
+        }
+
+        let repeats = self.last.take().map_or(0, |(_, _, x)| x);
+        self.last = Some((frame, now, 0));
+        Some(repeats)
+    }
+}
+
+impl History {
+    /// How many of the most recent commands are logged when one fails.
+    const LOGGED: usize = 8;
+
+    /// Records a command, dropping the oldest beyond `capacity`.
+    fn push(&self, sent: Sent, capacity: usize) {
+        match self.0.lock() {
+            Ok(mut history) => {
+                history.push_back(sent);
+                while history.len() > capacity {
+                    history.pop_front();
+                }
+            }
+            Err(e) => error!("failed to lock command history: {e}"),
+        }
+    }
+
+    fn entries(&self) -> Vec<Sent> {
+        self.0
+            .lock()
+            .map(|x| x.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Formats the last `n` commands, one per line.
+    fn recent(&self, n: usize) -> String {
+        let entries = self.entries();
+        entries[entries.len().saturating_sub(n)..]
+            .iter()
+            .map(|x| format!("    {x}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Display for Sent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ago = self.time.elapsed().unwrap_or_default();
+        write!(f, "{:?} {:.1}s ago: ", self.cmd, ago.as_secs_f32())?;
+        match &self.error {
+            Some(e) => write!(f, "failed: {e}")?,
+            None => write!(f, "ok")?,
+        }
+        if self.retries > 0 {
+            write!(f, " (retries: {})", self.retries)?;
+        }
+        if self.attempts > 1 {
+            write!(f, " (attempts: {})", self.attempts)?;
+        }
+        Ok(())
+    }
+}
+
+impl Remote {
+    /// How long after owl sends a key that the same key from the bus is
+    /// considered an echo.
+    const FEEDBACK_WINDOW: Duration = Duration::from_millis(500);
+
+    fn new(event_tx: RemoteTx) -> Self {
+        Self {
+            event_tx,
+            sent: Arc::default(),
+        }
+    }
+
+    /// Decodes a keypress into an owl event. libcec reports a key press with a
+    /// zero duration, repeating it while the key is held, and reports the
+    /// release with how long the key was held.
+    fn decode(keypress: cec::Keypress) -> Option<Event> {
+        let key = Key::from(Button::from_control(keypress.keycode)?);
+
+        if keypress.duration.is_zero() {
+            Some(Event::Press(key))
+        } else {
+            Some(Event::Release(key))
+        }
+    }
+
+    fn mark_sent(&self, button: Button) {
+        match self.sent.lock() {
+            Ok(mut sent) => {
+                sent.insert(button, Instant::now());
+            }
+            Err(e) => error!("failed to lock sent keys: {e}"),
+        }
+    }
+
+    fn is_echo(&self, event: Event) -> bool {
+        let button = match event {
+            Event::Press(key) | Event::Release(key) => Button::from(key),
+            _ => return false,
+        };
+
+        self.sent.lock().is_ok_and(|sent| {
+            sent.get(&button)
+                .is_some_and(|time| time.elapsed() <= Self::FEEDBACK_WINDOW)
+        })
+    }
+
+    fn on_key_press(&self, keypress: cec::Keypress) {
+        trace!(target: "libcec", "key {keypress}");
+
+        let Some(event) = Self::decode(keypress) else {
+            return;
+        };
+
+        if self.is_echo(event) {
+            trace!("ignoring echoed remote event: {event:?}");
+            return;
+        }
+
+        if let Err(e) = self.event_tx.send(event) {
+            error!("failed to relay remote event: {event:?}: {e}");
+        }
+    }
+}
+
+impl Cec {
+    pub fn new(config: &Config, remote: Remote) -> Result<Self> {
+        Self::connect(
+            Self::builder(config)
+                .monitor_only(config.lazy_active.is_some())
+                .on_key_press(Box::new(move |keypress| remote.on_key_press(keypress))),
+        )
+    }
+
+    /// Connects to the adapter. A busy adapter, usually left open by a
+    /// previous owl that didn't close it cleanly, is reset by
+    /// [`cec::CfgBuilder::connect`] before giving up.
+    fn connect(builder: cec::CfgBuilder) -> Result<Self> {
+        let active = Arc::new(AtomicBool::new(false));
+        let connected = Arc::new(AtomicBool::new(true));
+        let builder = {
+            let active = active.clone();
+            let connected = connected.clone();
+            builder
+                .on_source_activated(Box::new(move |address, activated| {
+                    Self::on_source_activated(&active, address, activated);
+                }))
+                .on_alert(Box::new(move |alert| {
+                    if alert == Alert::ConnectionLost {
+                        error!("lost connection to the cec adapter");
+                        notify::error("owl lost the CEC adapter");
+                        connected.store(false, Ordering::Relaxed);
+                    }
+                }))
+        };
+
+        debug!("connecting to cec...");
+        let connection = builder
+            .connect()
+            .inspect_err(|e| {
+                notify::error(&format!("owl couldn't connect to the CEC adapter: {e}"));
+            })
+            .context("failed to connect to cec")?;
+
+        info!("connected to cec with {}", connection.0.summary());
+        Ok(Self(connection, active, connected))
+    }
+
+    fn builder(config: &Config) -> cec::CfgBuilder {
+        let mut repeats = config.collapse_repeats.then(RepeatFilter::default);
+        let mut builder = cec::Connection::builder()
+            .detect_device(config.adapter.is_none())
+            .device(config.adapter.clone())
+            .name("owl".to_owned())
+            .kind(config.device_kind)
+            .activate_source(false)
+            .transmit_timeout(config.transmit_timeout)
+            .on_command_received(Box::new(move |cmd| {
+                Self::on_feature_abort(&cmd);
+                Self::on_command_received(repeats.as_mut(), &cmd);
+            }))
+            .on_log_message(Box::new(Self::on_log_level))
+            .hdmi_port(config.hdmi_port);
+        if let Some(address) = config.physical_address {
+            builder = builder
+                .physical_address(address)
+                .detect_physical_address(false);
+        }
+
+        builder
+    }
+
+    /// Whether an audio system is on the bus.
+    fn has_audio_system(&self) -> bool {
+        match self.get_active_devices() {
+            Ok(devices) => devices
+                .addresses
+                .into_iter()
+                .any(|x| LogicalAddress::from(x) == LogicalAddress::Audiosystem),
+            Err(e) => {
+                warn!("failed to get active devices: {e}");
+                false
+            }
+        }
+    }
+
+    /// Keeps the active source cache current, noting when another device
+    /// takes focus from owl.
+    fn on_source_activated(
+        active: &AtomicBool,
+        address: cec::KnownLogicalAddress,
+        activated: bool,
+    ) {
+        debug!(
+            "{:?} active source: {activated}",
+            LogicalAddress::from(address)
+        );
+        if active.swap(activated, Ordering::Relaxed) && !activated {
+            info!("another device became the active source");
+        }
+    }
+
+    fn on_command_received(repeats: Option<&mut RepeatFilter>, cmd: &cec::Cmd) {
+        if let Some(repeats) = repeats {
+            match repeats.push(cmd.to_frame(), Instant::now()) {
+                None => return,
+                Some(0) => {}
+                Some(n) => trace!(target: "libcec", "previous command repeated x{n}"),
+            }
+        }
+
+        trace!(target: "libcec", "command received: {:?}", cmd);
+    }
+
+    /// Logs a device refusing a command owl sent. Devices only reply to the
+    /// sender, so any `<Feature Abort>` owl receives is for one of its own.
+    fn on_feature_abort(cmd: &cec::Cmd) {
+        if let Some((opcode, reason)) = cmd.feature_abort() {
+            warn!("{:?} refused {opcode:?}: {reason:?}", cmd.initiator);
+        }
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn on_log_level(log: cec::LogMsg) {
+        const TARGET: &str = "libcec";
+        match Self::log_level(log.level) {
+            Level::ERROR => error!(target: TARGET, "{}", log.message),
+            Level::WARN => warn!(target: TARGET, "{}", log.message),
+            Level::INFO => info!(target: TARGET, "{}", log.message),
+            Level::DEBUG => debug!(target: TARGET, "{}", log.message),
+            _ => trace!(target: TARGET, "{}", log.message),
+        }
+    }
+
+    /// Returns the level a libcec log message is logged at. Each gets its own
+    /// level, so notices can be seen without the very noisy bus traffic.
+    const fn log_level(level: cec::LogLevel) -> Level {
+        match level {
+            cec::LogLevel::Error => Level::ERROR,
+            cec::LogLevel::Warning => Level::WARN,
+            cec::LogLevel::Notice => Level::INFO,
+            cec::LogLevel::Debug => Level::DEBUG,
+            cec::LogLevel::Traffic | cec::LogLevel::All => Level::TRACE,
+        }
+    }
+}
+
+impl Sink for Cec {
+    fn set_active_source(&self, kind: DeviceKind) -> cec::Result<ActiveSourceStatus> {
+        self.0.set_active_source(kind)
+    }
+
+    fn send_power_on_devices(&self, address: LogicalAddress) -> cec::Result<()> {
+        self.0.send_power_on_devices(address)
+    }
+
+    fn send_standby_devices(&self, address: LogicalAddress) -> cec::Result<()> {
+        self.0.send_standby_devices(address)
+    }
+
+    fn send_keypress(
+        &self,
+        address: LogicalAddress,
+        key: UserControlCode,
+        wait: bool,
+    ) -> cec::Result<()> {
+        self.0.send_keypress(address, key, wait)
+    }
+
+    fn send_key_release(&self, address: LogicalAddress, wait: bool) -> cec::Result<()> {
+        self.0.send_key_release(address, wait)
+    }
+
+    fn audio_toggle_mute(&self) -> cec::Result<()> {
+        self.0.audio_toggle_mute()
+    }
+
+    fn audio_mute(&self) -> cec::Result<cec::VolumeStatus> {
+        self.0.audio_mute()
+    }
+
+    fn audio_unmute(&self) -> cec::Result<cec::VolumeStatus> {
+        self.0.audio_unmute()
+    }
+
+    fn system_audio_mode(&self) -> cec::Result<SystemAudioStatus> {
+        self.0.request_system_audio_mode_status()
+    }
+
+    fn set_system_audio_mode(&self, on: bool) -> cec::Result<SystemAudioStatus> {
+        self.0.set_system_audio_mode(on)
+    }
+
+    fn volume_status(&self) -> cec::Result<cec::VolumeStatus> {
+        self.0.volume_status()
+    }
+
+    fn poll_device(&self, address: LogicalAddress) -> bool {
+        self.0.poll_device(address)
+    }
+
+    fn send(
+        &self,
+        destination: LogicalAddress,
+        opcode: Opcode,
+        parameters: &[u8],
+    ) -> cec::Result<()> {
+        self.0.send(destination, opcode, parameters)
+    }
+
+    fn physical_address(&self) -> cec::Result<PhysicalAddress> {
+        let address = self.get_logical_addresses()?.primary.into();
+        Ok(self.get_device_physical_address(address))
+    }
+
+    fn set_hdmi_port(&self, base_device: LogicalAddress, port: u8) -> cec::Result<()> {
+        self.0.set_hdmi_port(base_device, port)
+    }
+
+    fn vendor_id(&self, address: LogicalAddress) -> Option<VendorId> {
+        self.get_device_vendor_id(address)
+    }
+
+    fn is_active_source(&self) -> bool {
+        // The callback keeps the cache current, libcec confirms it's still us.
+        self.1.load(Ordering::Relaxed)
+            && self.get_active_source().is_some_and(|active| {
+                self.get_logical_addresses()
+                    .is_ok_and(|x| LogicalAddress::from(x.primary) == active)
+            })
+    }
+
+    fn is_connected(&self) -> bool {
+        self.2.load(Ordering::Relaxed)
+    }
+
+    fn ping_adapter(&self) -> cec::Result<()> {
+        let result = self.0.ping_adapter();
+        self.2.store(result.is_ok(), Ordering::Relaxed);
+        result
+    }
+
+    fn reopen(&self) -> cec::Result<()> {
+        let result = self.0.reopen();
+        self.2.store(result.is_ok(), Ordering::Relaxed);
+        result
+    }
+
+    fn set_monitoring(&self, enable: bool) -> cec::Result<()> {
+        self.switch_monitoring(enable)
+    }
+}
+
+impl Button {
+    /// Every button, in declaration order.
+    pub const ALL: [Self; 3] = [Self::VolumeUp, Self::VolumeDown, Self::VolumeMute];
+
+    /// Returns the button's name, like `volume-up`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::VolumeUp => "volume-up",
+            Self::VolumeDown => "volume-down",
+            Self::VolumeMute => "volume-mute",
+        }
+    }
+
+    /// Returns the button a user control code from the bus presses, `None` if
+    /// it isn't one owl handles.
+    #[must_use]
+    pub const fn from_control(code: UserControlCode) -> Option<Self> {
+        match code {
+            UserControlCode::VolumeUp => Some(Self::VolumeUp),
+            UserControlCode::VolumeDown => Some(Self::VolumeDown),
+            UserControlCode::Mute => Some(Self::VolumeMute),
+            _ => None,
+        }
+    }
+}
+
+impl From<Button> for Key {
+    fn from(value: Button) -> Self {
+        match value {
+            Button::VolumeUp => Self::VolumeUp,
+            Button::VolumeDown => Self::VolumeDown,
+            Button::VolumeMute => Self::VolumeMute,
+        }
     }
 }
 
@@ -218,14 +1952,1576 @@ impl From<Key> for Button {
     }
 }
 
-impl From<Event> for Command {
-    fn from(value: Event) -> Self {
-        match value {
-            Event::Suspend => Self::PowerOff,
-            Event::Resume => Self::PowerOn,
-            Event::Focus => Self::Focus,
-            Event::Press(key) => Self::Press(key.into()),
-            Event::Release(key) => Self::Release(key.into()),
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    /// A call made to the [`MockSink`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Call {
+        SetActiveSource(DeviceKind),
+        PowerOnDevices(LogicalAddress),
+        StandbyDevices(LogicalAddress),
+        Keypress(LogicalAddress, UserControlCode),
+        KeyRelease(LogicalAddress),
+        ToggleMute,
+        Mute,
+        Unmute,
+        VolumeStatus,
+        Send(LogicalAddress, Opcode),
+        SetHdmiPort(LogicalAddress, u8),
+        SystemAudioMode,
+        SetSystemAudioMode(bool),
+        SetMonitoring(bool),
+    }
+
+    /// A [`Sink`] which records calls instead of touching the HDMI-CEC bus.
+    #[derive(Debug, Default)]
+    struct MockSink {
+        calls: RefCell<Vec<Call>>,
+        volume: Cell<cec::VolumeStatus>,
+        /// Whether the audio system ignores discrete mute.
+        ignore_discrete_mute: Cell<bool>,
+        /// Whether the audio system doesn't respond to polls.
+        no_audio_system: Cell<bool>,
+        /// How many times devices were polled.
+        polls: Cell<usize>,
+        tv_vendor: Cell<Option<VendorId>>,
+        /// How many active source broadcasts go unacknowledged.
+        unacknowledged: Cell<usize>,
+        /// Whether owl is reported as the active source.
+        active_source: Cell<bool>,
+        /// Whether the adapter is reported as disconnected.
+        disconnected: Cell<bool>,
+        /// Whether the adapter doesn't answer pings.
+        unplugged: Cell<bool>,
+        /// How many times the adapter was pinged.
+        pings: Cell<usize>,
+        /// How many times the adapter was reopened.
+        reopens: Cell<usize>,
+        /// How many calls fail to transmit before calls succeed again.
+        failures: Cell<usize>,
+        /// Whether the audio system's system audio mode is on.
+        system_audio: Cell<bool>,
+    }
+
+    impl MockSink {
+        fn record(&self, call: Call) -> cec::Result<()> {
+            self.calls.borrow_mut().push(call);
+            let failures = self.failures.get();
+            if failures > 0 {
+                self.failures.set(failures - 1);
+                return Err(cec::Error::Transmit);
+            }
+            Ok(())
+        }
+
+        fn calls(&self) -> Vec<Call> {
+            self.calls.borrow().clone()
+        }
+
+        fn system_audio_status(&self) -> SystemAudioStatus {
+            if self.system_audio.get() {
+                SystemAudioStatus::On
+            } else {
+                SystemAudioStatus::Off
+            }
+        }
+
+        fn set_mute(&self, call: Call, muted: bool) -> cec::Result<cec::VolumeStatus> {
+            self.record(call)?;
+            if !self.ignore_discrete_mute.get() {
+                self.volume.set(cec::VolumeStatus {
+                    muted,
+                    ..self.volume.get()
+                });
+            }
+            Ok(self.volume.get())
+        }
+    }
+
+    impl Sink for MockSink {
+        fn set_active_source(&self, kind: DeviceKind) -> cec::Result<ActiveSourceStatus> {
+            self.record(Call::SetActiveSource(kind))?;
+            let unacknowledged = self.unacknowledged.get();
+            if unacknowledged > 0 {
+                self.unacknowledged.set(unacknowledged - 1);
+                Ok(ActiveSourceStatus::NotAcknowledged)
+            } else {
+                Ok(ActiveSourceStatus::Acknowledged)
+            }
+        }
+
+        fn send_power_on_devices(&self, address: LogicalAddress) -> cec::Result<()> {
+            self.record(Call::PowerOnDevices(address))
+        }
+
+        fn send_standby_devices(&self, address: LogicalAddress) -> cec::Result<()> {
+            self.record(Call::StandbyDevices(address))
+        }
+
+        fn send_keypress(
+            &self,
+            address: LogicalAddress,
+            key: UserControlCode,
+            _wait: bool,
+        ) -> cec::Result<()> {
+            self.record(Call::Keypress(address, key))
+        }
+
+        fn send_key_release(&self, address: LogicalAddress, _wait: bool) -> cec::Result<()> {
+            self.record(Call::KeyRelease(address))
+        }
+
+        fn audio_toggle_mute(&self) -> cec::Result<()> {
+            self.record(Call::ToggleMute)
+        }
+
+        fn audio_mute(&self) -> cec::Result<cec::VolumeStatus> {
+            self.set_mute(Call::Mute, true)
+        }
+
+        fn audio_unmute(&self) -> cec::Result<cec::VolumeStatus> {
+            self.set_mute(Call::Unmute, false)
+        }
+
+        fn system_audio_mode(&self) -> cec::Result<SystemAudioStatus> {
+            self.record(Call::SystemAudioMode)?;
+            Ok(self.system_audio_status())
+        }
+
+        fn set_system_audio_mode(&self, on: bool) -> cec::Result<SystemAudioStatus> {
+            self.record(Call::SetSystemAudioMode(on))?;
+            self.system_audio.set(on);
+            Ok(self.system_audio_status())
+        }
+
+        fn volume_status(&self) -> cec::Result<cec::VolumeStatus> {
+            self.record(Call::VolumeStatus)?;
+            Ok(self.volume.get())
+        }
+
+        fn poll_device(&self, address: LogicalAddress) -> bool {
+            self.polls.set(self.polls.get() + 1);
+            !(address == LogicalAddress::Audiosystem && self.no_audio_system.get())
+        }
+
+        fn send(
+            &self,
+            destination: LogicalAddress,
+            opcode: Opcode,
+            _parameters: &[u8],
+        ) -> cec::Result<()> {
+            self.record(Call::Send(destination, opcode))
+        }
+
+        fn physical_address(&self) -> cec::Result<PhysicalAddress> {
+            Ok(PhysicalAddress(0x2000))
+        }
+
+        fn set_hdmi_port(&self, base_device: LogicalAddress, port: u8) -> cec::Result<()> {
+            self.record(Call::SetHdmiPort(base_device, port))
+        }
+
+        fn vendor_id(&self, address: LogicalAddress) -> Option<VendorId> {
+            (address == LogicalAddress::Tv)
+                .then(|| self.tv_vendor.get())
+                .flatten()
+        }
+
+        fn is_active_source(&self) -> bool {
+            self.active_source.get()
+        }
+
+        fn is_connected(&self) -> bool {
+            !self.disconnected.get()
+        }
+
+        fn set_monitoring(&self, enable: bool) -> cec::Result<()> {
+            self.record(Call::SetMonitoring(enable))
+        }
+
+        fn ping_adapter(&self) -> cec::Result<()> {
+            self.pings.set(self.pings.get() + 1);
+            self.disconnected.set(self.unplugged.get());
+            if self.unplugged.get() {
+                Err(cec::Error::AdapterUnresponsive)
+            } else {
+                Ok(())
+            }
+        }
+
+        fn reopen(&self) -> cec::Result<()> {
+            self.reopens.set(self.reopens.get() + 1);
+            self.disconnected.set(self.unplugged.get());
+            if self.unplugged.get() {
+                Err(cec::ConnectionError::AdapterOpenFailed.into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn worker(config: Config) -> Worker<MockSink> {
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        Worker::new(
+            MockSink::default(),
+            config,
+            Remote::new(event_tx),
+            History::default(),
+        )
+    }
+
+    mod config {
+        use super::*;
+
+        #[test]
+        fn test_active_source_kind_defaults_to_device_kind() {
+            let config = Config {
+                device_kind: DeviceKind::PlaybackDevice,
+                active_source_kind: None,
+                ..Config::default()
+            };
+            assert_eq!(config.active_source_kind(), DeviceKind::PlaybackDevice);
+            assert!(config.validate().is_ok());
+        }
+
+        #[test]
+        fn test_unregistered_active_source_kind() {
+            let config = Config {
+                device_kind: DeviceKind::RecordingDevice,
+                active_source_kind: Some(DeviceKind::PlaybackDevice),
+                ..Config::default()
+            };
+            assert!(config.validate().is_ok());
+
+            let config = Config {
+                active_source_kind: Some(DeviceKind::Tv),
+                ..config
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(Error::UnannounceableActiveSourceKind(
+                    DeviceKind::Tv,
+                    DeviceKind::RecordingDevice
+                ))
+            ));
+
+            let config = Config {
+                device_kind: DeviceKind::Tv,
+                active_source_kind: Some(DeviceKind::PlaybackDevice),
+                ..config
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(Error::UnannounceableActiveSourceKind(
+                    DeviceKind::PlaybackDevice,
+                    DeviceKind::Tv
+                ))
+            ));
+        }
+
+        #[test]
+        fn test_set_active_source_matches_config() -> cec::Result<()> {
+            let mut worker = worker(Config {
+                device_kind: DeviceKind::PlaybackDevice,
+                active_source_kind: Some(DeviceKind::PlaybackDevice),
+                ..Config::default()
+            });
+            worker.dispatch(Command::PowerOn)?;
+            worker.dispatch(Command::Focus)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::SetActiveSource(DeviceKind::PlaybackDevice),
+                    Call::SetActiveSource(DeviceKind::PlaybackDevice)
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_no_activate_source() -> cec::Result<()> {
+            let mut worker = worker(Config {
+                activate_source: false,
+                ..Config::default()
+            });
+            worker.dispatch(Command::PowerOn)?;
+            worker.dispatch(Command::Focus)?;
+            worker.dispatch(Command::PowerOff)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::PowerOnDevices(LogicalAddress::Tv),
+                    Call::StandbyDevices(LogicalAddress::Tv)
+                ]
+            );
+            Ok(())
+        }
+    }
+
+    mod standby {
+        use clap::Parser;
+
+        use super::*;
+
+        #[test]
+        fn test_standby_target() -> cec::Result<()> {
+            for target in [
+                LogicalAddress::Tv,
+                LogicalAddress::BROADCAST,
+                LogicalAddress::Audiosystem,
+            ] {
+                let mut worker = worker(Config {
+                    standby_target: target,
+                    ..Config::default()
+                });
+                worker.dispatch(Command::PowerOff)?;
+                assert_eq!(worker.sink.calls(), vec![Call::StandbyDevices(target)]);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn test_no_power_on_on_resume() -> Result<()> {
+            let mut args = cli::Args::try_parse_from(["owl"])?;
+            args.file = crate::config::File::parse("power-on-on-resume = false")?;
+            let translator = args.translator();
+            let mut worker = worker(Config::default());
+
+            // Standby is sent straight away, there's nothing held for resume to
+            // cancel.
+            for cmd in translator.translate(Event::Suspend) {
+                worker.dispatch(cmd)?;
+            }
+            let standby = vec![Call::StandbyDevices(LogicalAddress::Tv)];
+            assert_eq!(worker.sink.calls(), standby);
+            for cmd in translator.translate(Event::Resume) {
+                worker.dispatch(cmd)?;
+            }
+            assert_eq!(worker.sink.calls(), standby);
+            Ok(())
+        }
+    }
+
+    mod mute_double_tap {
+        use super::*;
+
+        const WINDOW: Duration = Duration::from_millis(500);
+
+        fn worker() -> Worker<MockSink> {
+            super::worker(Config {
+                mute_double_tap: Some(WINDOW),
+                ..Config::default()
+            })
+        }
+
+        #[test]
+        fn test_single_tap() {
+            let mut worker = worker();
+            let start = Instant::now();
+            let mute = Command::Press(Button::VolumeMute);
+            assert!(!worker.mute_double_tapped(mute, start));
+            assert!(!worker.mute_double_tapped(mute, start + WINDOW * 2));
+            assert!(!worker.mute_double_tapped(Command::Release(Button::VolumeMute), start));
+        }
+
+        #[test]
+        fn test_double_tap() {
+            let mut worker = worker();
+            let start = Instant::now();
+            let mute = Command::Press(Button::VolumeMute);
+            assert!(!worker.mute_double_tapped(mute, start));
+            assert!(!worker.mute_double_tapped(Command::Release(Button::VolumeMute), start));
+            assert!(worker.mute_double_tapped(mute, start + WINDOW));
+
+            // The third tap starts over.
+            assert!(!worker.mute_double_tapped(mute, start + WINDOW + WINDOW / 2));
+        }
+
+        #[test]
+        fn test_other_buttons() {
+            let mut worker = worker();
+            let start = Instant::now();
+            let up = Command::Press(Button::VolumeUp);
+            assert!(!worker.mute_double_tapped(up, start));
+            assert!(!worker.mute_double_tapped(up, start));
+        }
+
+        #[test]
+        fn test_disabled() {
+            let mut worker = super::worker(Config::default());
+            let start = Instant::now();
+            let mute = Command::Press(Button::VolumeMute);
+            assert!(!worker.mute_double_tapped(mute, start));
+            assert!(!worker.mute_double_tapped(mute, start));
+        }
+    }
+
+    mod reconfigure {
+        use super::*;
+
+        #[test]
+        fn test_reconfigure_live() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.dispatch(Command::Focus)?;
+            worker.reconfigure(Config {
+                activate_source: false,
+                restore_volume: true,
+                ..Config::default()
+            });
+            worker.dispatch(Command::Focus)?;
+
+            assert!(worker.config.restore_volume);
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::SetActiveSource(DeviceKind::RecordingDevice)]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_reconfigure_requires_restart() {
+            let mut worker = worker(Config::default());
+            worker.reconfigure(Config {
+                device_kind: DeviceKind::PlaybackDevice,
+                transmit_timeout: Duration::from_secs(3),
+                ..Config::default()
+            });
+
+            assert_eq!(worker.config.device_kind, DeviceKind::RecordingDevice);
+            assert_eq!(
+                worker.config.transmit_timeout,
+                Config::default().transmit_timeout
+            );
+        }
+
+        #[test]
+        fn test_reconfigure_hdmi_port() {
+            let mut worker = worker(Config::default());
+            worker.reconfigure(Config {
+                hdmi_port: 3,
+                ..Config::default()
+            });
+            assert_eq!(worker.config.hdmi_port, 3);
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::SetHdmiPort(LogicalAddress::Tv, 3)]
+            );
+
+            worker.sink.failures.set(1);
+            worker.reconfigure(Config {
+                hdmi_port: 4,
+                ..Config::default()
+            });
+            assert_eq!(worker.config.hdmi_port, 3);
+
+            worker.reconfigure(Config {
+                hdmi_port: 16,
+                ..Config::default()
+            });
+            assert_eq!(worker.config.hdmi_port, 3);
+            assert_eq!(worker.sink.calls().len(), 2);
+        }
+
+        #[test]
+        fn test_reconfigure_clears_debounce() {
+            let mut worker = worker(Config::default());
+            assert!(debounce_cmd(Command::Focus, &mut worker.last_cmd, Duration::ZERO).is_some());
+            worker.reconfigure(Config::default());
+            assert!(worker.last_cmd.is_empty());
+        }
+    }
+
+    mod restore_volume {
+        use super::*;
+
+        const fn volume(x: u8) -> cec::VolumeStatus {
+            cec::VolumeStatus {
+                muted: false,
+                volume: Some(x),
+            }
+        }
+
+        #[test]
+        fn test_volume_steps() {
+            assert_eq!(volume_steps(25, 30), Some((UserControlCode::VolumeUp, 5)));
+            assert_eq!(volume_steps(30, 25), Some((UserControlCode::VolumeDown, 5)));
+            assert_eq!(volume_steps(30, 30), None);
+            assert_eq!(volume_steps(0, 100), Some((UserControlCode::VolumeUp, 100)));
+            assert_eq!(
+                volume_steps(100, 20),
+                Some((UserControlCode::VolumeDown, 80))
+            );
+            assert_eq!(volume_steps(0, 127), Some((UserControlCode::VolumeUp, 100)));
+        }
+
+        #[test]
+        fn test_restore_volume() -> cec::Result<()> {
+            let mut worker = worker(Config {
+                restore_volume: true,
+                ..Config::default()
+            });
+
+            worker.sink.volume.set(volume(30));
+            worker.dispatch(Command::PowerOff)?;
+            worker.sink.volume.set(volume(27));
+            worker.dispatch(Command::PowerOn)?;
+
+            let presses = worker
+                .sink
+                .calls()
+                .into_iter()
+                .filter(|x| {
+                    *x == Call::Keypress(LogicalAddress::Audiosystem, UserControlCode::VolumeUp)
+                })
+                .count();
+            assert_eq!(presses, 3);
+            Ok(())
+        }
+
+        #[test]
+        fn test_restore_volume_disabled() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+
+            worker.sink.volume.set(volume(30));
+            worker.dispatch(Command::PowerOff)?;
+            worker.sink.volume.set(volume(27));
+            worker.dispatch(Command::PowerOn)?;
+
+            assert!(!worker.sink.calls().contains(&Call::VolumeStatus));
+            Ok(())
+        }
+
+        #[test]
+        fn test_power_on_volume() -> cec::Result<()> {
+            let presses = |worker: &Worker<MockSink>, key| {
+                worker
+                    .sink
+                    .calls()
+                    .into_iter()
+                    .filter(|x| *x == Call::Keypress(LogicalAddress::Audiosystem, key))
+                    .count()
+            };
+            let mut worker = worker(Config {
+                restore_volume: true,
+                power_on_volume: Some(20),
+                ..Config::default()
+            });
+
+            // The configured volume wins over the one saved on suspend.
+            worker.sink.volume.set(volume(30));
+            worker.dispatch(Command::PowerOff)?;
+            worker.sink.volume.set(volume(27));
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(presses(&worker, UserControlCode::VolumeDown), 7);
+            assert_eq!(presses(&worker, UserControlCode::VolumeUp), 0);
+
+            // Without a suspend first, too.
+            worker.sink.calls.borrow_mut().clear();
+            worker.sink.volume.set(volume(12));
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(presses(&worker, UserControlCode::VolumeUp), 8);
+            Ok(())
+        }
+    }
+
+    mod release {
+        use super::*;
+
+        #[test]
+        fn test_release_after_press() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.dispatch(Command::Press(Button::VolumeUp))?;
+            worker.dispatch(Command::Release(Button::VolumeUp))?;
+            worker.dispatch(Command::Press(Button::VolumeMute))?;
+            worker.dispatch(Command::Release(Button::VolumeMute))?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Keypress(LogicalAddress::Audiosystem, UserControlCode::VolumeUp),
+                    Call::KeyRelease(LogicalAddress::Audiosystem),
+                    Call::ToggleMute,
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_release_configurable() -> cec::Result<()> {
+            let mut worker = worker(Config {
+                release_buttons: HashSet::from([Button::VolumeMute]),
+                ..Config::default()
+            });
+            worker.dispatch(Command::Press(Button::VolumeDown))?;
+            worker.dispatch(Command::Release(Button::VolumeDown))?;
+            worker.dispatch(Command::Press(Button::VolumeMute))?;
+            worker.dispatch(Command::Release(Button::VolumeMute))?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Keypress(LogicalAddress::Audiosystem, UserControlCode::VolumeDown),
+                    Call::ToggleMute,
+                    Call::KeyRelease(LogicalAddress::Audiosystem),
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_no_release_without_press() -> cec::Result<()> {
+            let mut before = worker(Config::default());
+            before.dispatch(Command::Press(Button::VolumeUp))?;
+
+            // Reconnecting starts a fresh worker, which never saw the press.
+            let mut worker = worker(Config::default());
+            worker.dispatch(Command::Release(Button::VolumeUp))?;
+            assert_eq!(worker.sink.calls(), vec![]);
+
+            // Only the first release of a press is sent.
+            worker.dispatch(Command::Press(Button::VolumeUp))?;
+            worker.dispatch(Command::Release(Button::VolumeUp))?;
+            worker.dispatch(Command::Release(Button::VolumeUp))?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Keypress(LogicalAddress::Audiosystem, UserControlCode::VolumeUp),
+                    Call::KeyRelease(LogicalAddress::Audiosystem),
+                ]
+            );
+            Ok(())
+        }
+    }
+
+    mod volume_target {
+        use super::*;
+
+        #[test]
+        fn test_no_audio_system() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.sink.no_audio_system.set(true);
+            worker.dispatch(Command::Press(Button::VolumeUp))?;
+            worker.dispatch(Command::Release(Button::VolumeUp))?;
+            worker.dispatch(Command::Press(Button::VolumeMute))?;
+            worker.dispatch(Command::Release(Button::VolumeMute))?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Keypress(LogicalAddress::Tv, UserControlCode::VolumeUp),
+                    Call::KeyRelease(LogicalAddress::Tv),
+                    Call::Keypress(LogicalAddress::Tv, UserControlCode::Mute),
+                    Call::KeyRelease(LogicalAddress::Tv),
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_poll_cached() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.dispatch(Command::Press(Button::VolumeUp))?;
+            worker.dispatch(Command::Release(Button::VolumeUp))?;
+            worker.dispatch(Command::Press(Button::VolumeDown))?;
+            assert_eq!(worker.sink.polls.get(), 1);
+            Ok(())
+        }
+    }
+
+    mod keypress {
+        use super::*;
+
+        #[test]
+        fn test_keypress() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.dispatch(Command::Keypress(
+                LogicalAddress::Tv,
+                UserControlCode::InputSelect,
+            ))?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Keypress(LogicalAddress::Tv, UserControlCode::InputSelect),
+                    Call::KeyRelease(LogicalAddress::Tv),
+                ]
+            );
+            Ok(())
+        }
+    }
+
+    mod mute {
+        use super::*;
+
+        #[test]
+        fn test_set_mute() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.dispatch(Command::SetMute(true))?;
+            assert_eq!(worker.sink.calls(), vec![Call::Mute]);
+
+            worker.dispatch(Command::SetMute(false))?;
+            assert_eq!(worker.sink.calls(), vec![Call::Mute, Call::Unmute]);
+            Ok(())
+        }
+
+        #[test]
+        fn test_set_mute_fallback() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.sink.ignore_discrete_mute.set(true);
+            worker.dispatch(Command::SetMute(true))?;
+            assert_eq!(worker.sink.calls(), vec![Call::Mute, Call::ToggleMute]);
+            Ok(())
+        }
+    }
+
+    mod unmute_on_focus {
+        use super::*;
+
+        fn focus(unmute_on_focus: bool, active_source: bool) -> Vec<Call> {
+            let mut worker = worker(Config {
+                unmute_on_focus,
+                ..Config::default()
+            });
+            worker.sink.active_source.set(active_source);
+            worker.handle_cmd(Command::Focus, None);
+            worker.sink.calls()
+        }
+
+        #[test]
+        fn test_enabled() {
+            assert_eq!(
+                focus(true, false),
+                vec![
+                    Call::SetActiveSource(DeviceKind::RecordingDevice),
+                    Call::Unmute,
+                ]
+            );
+        }
+
+        #[test]
+        fn test_disabled() {
+            assert_eq!(
+                focus(false, false),
+                vec![Call::SetActiveSource(DeviceKind::RecordingDevice)]
+            );
+        }
+
+        #[test]
+        fn test_already_active_source() {
+            assert!(focus(true, true).is_empty());
+        }
+    }
+
+    mod remote {
+        use super::*;
+
+        const fn keypress(keycode: UserControlCode, millis: u64) -> cec::Keypress {
+            cec::Keypress {
+                keycode,
+                duration: Duration::from_millis(millis),
+            }
+        }
+
+        #[test]
+        fn test_decode_press() {
+            assert_eq!(
+                Remote::decode(keypress(UserControlCode::VolumeUp, 0)),
+                Some(Event::Press(Key::VolumeUp))
+            );
+            assert_eq!(
+                Remote::decode(keypress(UserControlCode::Mute, 0)),
+                Some(Event::Press(Key::VolumeMute))
+            );
+        }
+
+        #[test]
+        fn test_button_from_control() {
+            for (code, button) in [
+                (UserControlCode::VolumeUp, Button::VolumeUp),
+                (UserControlCode::VolumeDown, Button::VolumeDown),
+                (UserControlCode::Mute, Button::VolumeMute),
+            ] {
+                assert_eq!(Button::from_control(code), Some(button));
+                assert_eq!(Button::from(Key::from(button)), button);
+            }
+            assert_eq!(Button::from_control(UserControlCode::Select), None);
+        }
+
+        #[test]
+        fn test_decode_release() {
+            assert_eq!(
+                Remote::decode(keypress(UserControlCode::VolumeDown, 300)),
+                Some(Event::Release(Key::VolumeDown))
+            );
+        }
+
+        #[test]
+        fn test_decode_unmapped() {
+            assert_eq!(Remote::decode(keypress(UserControlCode::Play, 0)), None);
+        }
+
+        #[test]
+        fn test_echo_ignored() {
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            let remote = Remote::new(event_tx);
+
+            remote.mark_sent(Button::VolumeUp);
+            remote.on_key_press(keypress(UserControlCode::VolumeUp, 0));
+            remote.on_key_press(keypress(UserControlCode::VolumeDown, 0));
+
+            assert_eq!(
+                event_rx.try_recv().ok(),
+                Some(Event::Press(Key::VolumeDown))
+            );
+            assert!(event_rx.try_recv().is_err());
+        }
+    }
+
+    mod pending {
+        use super::*;
+
+        #[test]
+        fn test_reapply_after_reconnect() {
+            let mut worker = worker(Config::default());
+            let send = |worker: &mut Worker<MockSink>, cmd| {
+                worker.handle_cmd(cmd, None);
+            };
+
+            worker.sink.disconnected.set(true);
+            for cmd in [
+                Command::PowerOn,
+                Command::SetMute(true),
+                Command::Press(Button::VolumeUp),
+                Command::PowerOff,
+            ] {
+                send(&mut worker, cmd);
+            }
+            assert!(worker.sink.calls().is_empty());
+
+            worker.sink.disconnected.set(false);
+            send(
+                &mut worker,
+                Command::Keypress(LogicalAddress::Tv, UserControlCode::Select),
+            );
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::StandbyDevices(LogicalAddress::Tv),
+                    Call::Mute,
+                    Call::Keypress(LogicalAddress::Tv, UserControlCode::Select),
+                    Call::KeyRelease(LogicalAddress::Tv),
+                ]
+            );
+            assert_eq!(worker.pending, Pending::default());
+        }
+
+        #[test]
+        fn test_reapply_on_reopen() {
+            let mut worker = worker(Config::default());
+
+            // Suspended while the adapter was gone, nothing else follows.
+            worker.sink.disconnected.set(true);
+            worker.handle_cmd(Command::PowerOff, None);
+            assert!(worker.sink.calls().is_empty());
+
+            worker.poll_adapter(Instant::now());
+            assert_eq!(worker.sink.reopens.get(), 1);
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::StandbyDevices(LogicalAddress::Tv)]
+            );
+            assert_eq!(worker.pending, Pending::default());
+        }
+
+        #[test]
+        fn test_superseded() {
+            let mut pending = Pending::default();
+            assert!(pending.push(Command::PowerOff));
+            assert!(pending.push(Command::SetMute(true)));
+            assert!(!pending.push(Command::Focus));
+            assert_eq!(
+                pending.take(Command::SetMute(false)).collect::<Vec<_>>(),
+                vec![Command::PowerOff]
+            );
+            assert_eq!(pending, Pending::default());
+        }
+    }
+
+    mod lazy_active {
+        use super::*;
+
+        const IDLE: Duration = Duration::from_secs(30);
+
+        #[test]
+        fn test_transition() {
+            let mut worker = worker(Config {
+                lazy_active: Some(IDLE),
+                ..Config::default()
+            });
+            assert!(worker.monitoring);
+
+            // The first command takes part in the bus, later ones stay on it.
+            worker.send(Command::PowerOff);
+            worker.send(Command::PowerOff);
+            assert!(!worker.monitoring);
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::SetMonitoring(false),
+                    Call::StandbyDevices(LogicalAddress::Tv),
+                    Call::StandbyDevices(LogicalAddress::Tv),
+                ]
+            );
+
+            // Idle long enough, it goes back to monitoring.
+            let sent = worker.last_sent.expect("command sent");
+            worker.poll_idle(sent + IDLE / 2);
+            assert!(!worker.monitoring);
+            worker.poll_idle(sent + IDLE);
+            assert!(worker.monitoring);
+            assert_eq!(worker.sink.calls().last(), Some(&Call::SetMonitoring(true)));
+
+            // Turning it off while monitoring takes part in the bus again.
+            worker.reconfigure(Config::default());
+            assert!(!worker.monitoring);
+            assert_eq!(
+                worker.sink.calls().last(),
+                Some(&Call::SetMonitoring(false))
+            );
+        }
+
+        #[test]
+        fn test_disabled() {
+            let mut worker = worker(Config::default());
+            assert!(!worker.monitoring);
+            worker.send(Command::PowerOff);
+            worker.poll_idle(Instant::now() + IDLE);
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::StandbyDevices(LogicalAddress::Tv)]
+            );
+        }
+
+        #[test]
+        fn test_failed_switch_keeps_mode() {
+            let mut worker = worker(Config {
+                lazy_active: Some(IDLE),
+                ..Config::default()
+            });
+            worker.sink.failures.set(1);
+            worker.set_monitoring(false);
+            assert!(worker.monitoring);
+        }
+    }
+
+    mod adapter_ping {
+        use super::*;
+
+        const INTERVAL: Duration = Duration::from_secs(1);
+
+        fn worker() -> Worker<MockSink> {
+            super::worker(Config {
+                adapter_ping_interval: Some(INTERVAL),
+                ..Config::default()
+            })
+        }
+
+        #[test]
+        fn test_interval() {
+            let mut worker = worker();
+            let start = Instant::now();
+            worker.poll_adapter(start);
+            worker.poll_adapter(start + INTERVAL / 2);
+            assert_eq!(worker.sink.pings.get(), 1);
+            worker.poll_adapter(start + INTERVAL);
+            assert_eq!(worker.sink.pings.get(), 2);
+
+            let mut worker = super::worker(Config::default());
+            worker.poll_adapter(start);
+            assert_eq!(worker.sink.pings.get(), 0);
+        }
+
+        #[test]
+        fn test_unanswered_ping_defers() {
+            let mut worker = worker();
+            let start = Instant::now();
+
+            worker.sink.unplugged.set(true);
+            worker.poll_adapter(start);
+            assert!(!worker.sink.is_connected());
+            worker.handle_cmd(Command::SetMute(true), None);
+            assert!(worker.sink.calls().is_empty());
+
+            // Plugged back in, the next poll reopens it and reapplies the
+            // mute straight away, then commands are sent as usual.
+            worker.sink.unplugged.set(false);
+            worker.poll_adapter(start + INTERVAL);
+            assert!(worker.sink.is_connected());
+            assert_eq!(worker.sink.reopens.get(), 1);
+            assert_eq!(worker.sink.calls(), vec![Call::Mute]);
+            worker.handle_cmd(
+                Command::Keypress(LogicalAddress::Tv, UserControlCode::Select),
+                None,
+            );
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Mute,
+                    Call::Keypress(LogicalAddress::Tv, UserControlCode::Select),
+                    Call::KeyRelease(LogicalAddress::Tv),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_reopened_without_ping() {
+            let mut worker = super::worker(Config::default());
+            let start = Instant::now();
+
+            // libcec reported the connection lost, and it's still unplugged.
+            worker.sink.disconnected.set(true);
+            worker.sink.unplugged.set(true);
+            worker.poll_adapter(start);
+            assert_eq!(worker.sink.reopens.get(), 1);
+            assert!(!worker.sink.is_connected());
+
+            worker.sink.unplugged.set(false);
+            worker.poll_adapter(start + Config::ADAPTER_REOPEN_INTERVAL / 2);
+            assert_eq!(worker.sink.reopens.get(), 1);
+            worker.poll_adapter(start + Config::ADAPTER_REOPEN_INTERVAL);
+            assert_eq!(worker.sink.reopens.get(), 2);
+            assert!(worker.sink.is_connected());
+            assert_eq!(worker.sink.pings.get(), 0);
+        }
+    }
+
+    mod deadline {
+        use super::*;
+
+        const INTERVAL: Duration = Duration::from_secs(5);
+        const IDLE: Duration = Duration::from_secs(2);
+
+        #[tokio::test]
+        async fn test_wait_config() -> Result<()> {
+            let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+            let (config_tx, mut config_rx) = mpsc::unbounded_channel();
+            let run_token = CancellationToken::new();
+
+            // A reload wakes the job by itself, without waiting for a command.
+            config_tx.send(Config::default())?;
+            let wake = wait(&mut cmd_rx, &mut config_rx, None, &run_token).await;
+            assert!(matches!(wake, Wake::Config(_)));
+
+            // And is applied before a command already waiting.
+            cmd_tx.send((Command::PowerOn, None)).await?;
+            config_tx.send(Config::default())?;
+            let wake = wait(&mut cmd_rx, &mut config_rx, None, &run_token).await;
+            assert!(matches!(wake, Wake::Config(_)));
+            let wake = wait(&mut cmd_rx, &mut config_rx, None, &run_token).await;
+            assert!(matches!(wake, Wake::Cmd(Command::PowerOn, None)));
+            Ok(())
+        }
+
+        #[test]
+        fn test_nothing_timed() {
+            let worker = worker(Config::default());
+            let start = Instant::now();
+            assert_eq!(worker.deadline(start), None);
+
+            // A lost adapter is reopened, even without pinging.
+            worker.sink.disconnected.set(true);
+            assert_eq!(worker.deadline(start), Some(start));
+        }
+
+        #[test]
+        fn test_earliest_due() {
+            let mut worker = worker(Config {
+                adapter_ping_interval: Some(INTERVAL),
+                lazy_active: Some(IDLE),
+                ..Config::default()
+            });
+            let start = Instant::now();
+            assert_eq!(worker.deadline(start), Some(start));
+
+            // While monitoring, only the next ping is due.
+            worker.poll(start);
+            assert_eq!(worker.sink.pings.get(), 1);
+            assert_eq!(worker.deadline(start), Some(start + INTERVAL));
+
+            // On the bus, going back to monitoring is due first.
+            worker.monitoring = false;
+            worker.last_sent = Some(start);
+            assert_eq!(worker.deadline(start), Some(start + IDLE));
+        }
+    }
+
+    mod send {
+        use super::*;
+        use crate::job::Send;
+
+        fn job(cmd_tx: CommandTx) -> Job {
+            let (config_tx, _config_rx) = mpsc::unbounded_channel();
+            let (_remote_tx, remote_rx) = mpsc::unbounded_channel();
+            Job {
+                cmd_tx,
+                config_tx,
+                remote_rx,
+                has_audio_system: false,
+                history: History::default(),
+            }
+        }
+
+        #[tokio::test]
+        async fn test_stopped() {
+            let (cmd_tx, cmd_rx) = mpsc::channel(1);
+            drop(cmd_rx);
+            let result = job(cmd_tx).send(Command::PowerOn).await;
+            assert!(matches!(
+                result.map_err(color_eyre::Report::downcast),
+                Err(Ok(Error::Stopped))
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_traced() -> Result<()> {
+            let (cmd_tx, mut cmd_rx) = mpsc::channel(2);
+            let job = job(cmd_tx);
+            job.send_traced(Command::PowerOn, Some(3)).await?;
+            job.send(Command::PowerOn).await?;
+            assert_eq!(cmd_rx.try_recv()?, (Command::PowerOn, Some(3)));
+            assert_eq!(cmd_rx.try_recv()?, (Command::PowerOn, None));
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_send_all_stopped() {
+            let (cmd_tx, cmd_rx) = mpsc::channel(1);
+            drop(cmd_rx);
+            let run_token = CancellationToken::new();
+            let result = job(cmd_tx)
+                .send_all([Command::PowerOn, Command::Focus], None, &run_token)
+                .await;
+            assert!(matches!(
+                result.map_err(color_eyre::Report::downcast),
+                Err(Ok(Error::Stopped))
+            ));
+            assert!(run_token.is_cancelled());
+        }
+
+        #[tokio::test]
+        async fn test_send_all_busy() -> Result<()> {
+            let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+            let run_token = CancellationToken::new();
+            // The second command is dropped, the batch still succeeds.
+            job(cmd_tx)
+                .send_all([Command::PowerOn, Command::PowerOff], Some(1), &run_token)
+                .await?;
+            assert!(!run_token.is_cancelled());
+            assert_eq!(cmd_rx.try_recv()?, (Command::PowerOn, Some(1)));
+            assert!(cmd_rx.try_recv().is_err());
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_busy() -> Result<()> {
+            let (cmd_tx, _cmd_rx) = mpsc::channel(1);
+            let job = job(cmd_tx);
+            job.send(Command::PowerOn).await?;
+            let result = job.send(Command::PowerOff).await;
+            assert!(matches!(
+                result.map_err(color_eyre::Report::downcast),
+                Err(Ok(Error::Busy(Command::PowerOff)))
+            ));
+            Ok(())
+        }
+    }
+
+    mod history {
+        use super::*;
+
+        #[test]
+        fn test_history_len() {
+            let mut worker = worker(Config {
+                history_len: 2,
+                ..Config::default()
+            });
+            for cmd in [Command::PowerOn, Command::SetMute(true), Command::PowerOff] {
+                worker.handle_cmd(cmd, None);
+            }
+
+            let history = worker.history.entries();
+            assert_eq!(
+                history.iter().map(|x| x.cmd).collect::<Vec<_>>(),
+                vec![Command::SetMute(true), Command::PowerOff]
+            );
+            assert!(history.iter().all(|x| x.error.is_none()));
+        }
+
+        #[test]
+        fn test_recent() {
+            let history = History::default();
+            for cmd in [Command::PowerOn, Command::PowerOff] {
+                history.push(
+                    Sent {
+                        cmd,
+                        time: SystemTime::now(),
+                        error: Some("failed to transmit command".to_owned()),
+                        retries: 0,
+                        attempts: 1,
+                    },
+                    8,
+                );
+            }
+
+            assert_eq!(
+                history.recent(1),
+                "    PowerOff 0.0s ago: failed: failed to transmit command"
+            );
+        }
+    }
+
+    mod log_level {
+        use super::*;
+
+        #[test]
+        fn test_distinct_levels() {
+            assert_eq!(Cec::log_level(cec::LogLevel::Warning), Level::WARN);
+            assert_eq!(Cec::log_level(cec::LogLevel::Notice), Level::INFO);
+            assert_eq!(Cec::log_level(cec::LogLevel::Debug), Level::DEBUG);
+            assert_eq!(Cec::log_level(cec::LogLevel::Traffic), Level::TRACE);
+        }
+    }
+
+    mod retry {
+        use super::*;
+
+        fn worker() -> Worker<MockSink> {
+            super::worker(Config {
+                power_on: Some(vec![PowerOnStep::PowerOnDevices]),
+                retry_delay: Duration::ZERO,
+                ..Config::default()
+            })
+        }
+
+        #[test]
+        fn test_retry_until_sent() {
+            let mut worker = worker();
+            worker.sink.failures.set(2);
+            worker.send(Command::PowerOn);
+
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::PowerOnDevices(LogicalAddress::Tv); 3]
+            );
+            let history = worker.history.entries();
+            assert_eq!(history[0].error, None);
+            assert_eq!(history[0].attempts, 3);
+        }
+
+        #[test]
+        fn test_give_up() {
+            let mut worker = worker();
+            worker.config.retry_attempts = 2;
+            worker.sink.failures.set(5);
+            worker.send(Command::PowerOn);
+
+            assert_eq!(worker.sink.calls().len(), 2);
+            let history = worker.history.entries();
+            assert!(history[0].error.is_some());
+            assert_eq!(history[0].attempts, 2);
+        }
+
+        #[test]
+        fn test_keypress_not_retried() {
+            let mut worker = worker();
+            worker.sink.failures.set(1);
+            worker.send(Command::Press(Button::VolumeUp));
+
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::Keypress(
+                    LogicalAddress::Audiosystem,
+                    UserControlCode::VolumeUp
+                )]
+            );
+            assert_eq!(worker.history.entries()[0].attempts, 1);
+        }
+    }
+
+    mod repeat_filter {
+        use super::*;
+
+        #[test]
+        fn test_collapse_repeats() {
+            let mut filter = RepeatFilter::default();
+            let start = Instant::now();
+            let power_status = vec![0x04, 0x90, 0x00];
+            assert_eq!(filter.push(power_status.clone(), start), Some(0));
+            for ms in [100, 200, 300] {
+                let now = start + Duration::from_millis(ms);
+                assert_eq!(filter.push(power_status.clone(), now), None);
+            }
+
+            let polling = vec![0x04];
+            let now = start + Duration::from_millis(400);
+            assert_eq!(filter.push(polling, now), Some(3));
+            assert_eq!(filter.push(power_status, now), Some(0));
+        }
+
+        #[test]
+        fn test_repeat_outside_window() {
+            let mut filter = RepeatFilter::default();
+            let start = Instant::now();
+            assert_eq!(filter.push(vec![0x04], start), Some(0));
+            let later = start + RepeatFilter::WINDOW + Duration::from_millis(1);
+            assert_eq!(filter.push(vec![0x04], later), Some(0));
+        }
+    }
+
+    mod active_source {
+        use super::*;
+
+        #[test]
+        fn test_retry_unacknowledged() {
+            let mut worker = worker(Config::default());
+            worker.sink.unacknowledged.set(1);
+            worker.handle_cmd(Command::Focus, None);
+
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::SetActiveSource(DeviceKind::RecordingDevice),
+                    Call::SetActiveSource(DeviceKind::RecordingDevice),
+                ]
+            );
+            let history = worker.history.entries();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].error, None);
+            assert_eq!(history[0].retries, 1);
+        }
+
+        #[test]
+        fn test_skip_when_active_source() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.sink.active_source.set(true);
+            worker.dispatch(Command::Focus)?;
+            assert_eq!(worker.sink.calls(), vec![]);
+
+            // Power on always broadcasts, the TV forgets the active source in
+            // standby.
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::SetActiveSource(DeviceKind::RecordingDevice)]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_fall_back_to_set_stream_path() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.sink.unacknowledged.set(2);
+            worker.dispatch(Command::Focus)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::SetActiveSource(DeviceKind::RecordingDevice),
+                    Call::SetActiveSource(DeviceKind::RecordingDevice),
+                    Call::Send(LogicalAddress::Unregistered, Opcode::SetStreamPath),
+                ]
+            );
+            Ok(())
+        }
+    }
+
+    mod power_on {
+        use super::*;
+
+        #[test]
+        fn test_vendor_sequence() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.sink.tv_vendor.set(Some(VendorId::Lg));
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Send(LogicalAddress::Tv, Opcode::ImageViewOn),
+                    Call::Send(LogicalAddress::Unregistered, Opcode::SetStreamPath),
+                    Call::SetActiveSource(DeviceKind::RecordingDevice),
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_configured_sequence() -> cec::Result<()> {
+            let mut worker = worker(Config {
+                power_on: Some(vec![PowerOnStep::TextViewOn, PowerOnStep::PowerOnDevices]),
+                ..Config::default()
+            });
+            worker.sink.tv_vendor.set(Some(VendorId::Samsung));
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::Send(LogicalAddress::Tv, Opcode::TextViewOn),
+                    Call::PowerOnDevices(LogicalAddress::Tv),
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_no_activate_source_skips_input_switching() -> cec::Result<()> {
+            let mut worker = worker(Config {
+                activate_source: false,
+                ..Config::default()
+            });
+            worker.sink.tv_vendor.set(Some(VendorId::Samsung));
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(
+                worker.sink.calls(),
+                vec![Call::Send(LogicalAddress::Tv, Opcode::ImageViewOn)]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_vendor_remembered() -> cec::Result<()> {
+            let mut worker = worker(Config::default());
+            worker.sink.tv_vendor.set(Some(VendorId::Sony));
+            worker.dispatch(Command::PowerOn)?;
+            worker.sink.tv_vendor.set(None);
+            worker.dispatch(Command::PowerOn)?;
+            assert_eq!(worker.tv_vendor, Some(VendorId::Sony));
+            let calls = worker.sink.calls();
+            assert_eq!(calls[2..], calls[..2]);
+            Ok(())
+        }
+    }
+
+    mod debounce {
+        use super::*;
+
+        const WINDOW: Duration = Duration::from_secs(60);
+
+        #[test]
+        fn test_press_repeats() {
+            let mut last_cmd = LastCmd::new();
+            let press = Command::Press(Button::VolumeUp);
+            assert!(debounce_cmd(press, &mut last_cmd, WINDOW).is_some());
+            assert!(debounce_cmd(press, &mut last_cmd, WINDOW).is_none());
+            assert!(
+                debounce_cmd(Command::Press(Button::VolumeDown), &mut last_cmd, WINDOW).is_some()
+            );
+        }
+
+        #[test]
+        fn test_press_release_press() {
+            let mut last_cmd = LastCmd::new();
+            let press = Command::Press(Button::VolumeUp);
+            let release = Command::Release(Button::VolumeUp);
+            assert!(debounce_cmd(press, &mut last_cmd, WINDOW).is_some());
+            // A release always passes, otherwise the button stays held.
+            assert!(debounce_cmd(release, &mut last_cmd, WINDOW).is_some());
+            // A press bouncing right after the release is dropped.
+            assert!(debounce_cmd(press, &mut last_cmd, WINDOW).is_none());
+        }
+
+        #[test]
+        fn test_window_elapsed() {
+            let mut last_cmd = LastCmd::new();
+            let press = Command::Press(Button::VolumeUp);
+            assert!(debounce_cmd(press, &mut last_cmd, Duration::ZERO).is_some());
+            thread::sleep(Duration::from_millis(1));
+            assert!(debounce_cmd(press, &mut last_cmd, Duration::ZERO).is_some());
+        }
+
+        #[test]
+        fn test_pruned() {
+            let mut last_cmd = LastCmd::new();
+            let start = Instant::now();
+            let keys = [
+                UserControlCode::Select,
+                UserControlCode::Up,
+                UserControlCode::Down,
+                UserControlCode::Exit,
+            ];
+            let cmds = (0..16)
+                .filter_map(|x| LogicalAddress::try_from(x).ok())
+                .flat_map(|address| keys.map(|key| Command::Keypress(address, key)));
+            for (i, cmd) in (0..).zip(cmds) {
+                let time = start + Duration::from_secs(i);
+                assert!(debounce_cmd_at(cmd, &mut last_cmd, Duration::ZERO, time).is_some());
+                assert!(last_cmd.len() <= LAST_CMD_PRUNE_LEN);
+            }
+        }
+    }
+
+    mod all {
+        use super::*;
+
+        #[test]
+        fn test_all_commands() {
+            // Fails to compile when a variant is added, so it isn't left out
+            // of `Command::all`.
+            let variant = |x: &Command| match x {
+                Command::PowerOn => 0,
+                Command::PowerOff => 1,
+                Command::Focus => 2,
+                Command::Press(_) => 3,
+                Command::Release(_) => 4,
+                Command::SetMute(_) => 5,
+                Command::Keypress(..) => 6,
+            };
+            let all = Command::all();
+            let variants: HashSet<_> = all.iter().map(variant).collect();
+            assert_eq!(variants, (0..6).collect());
+            assert_eq!(all.len(), 5 + 2 * Button::ALL.len());
+
+            let names: HashSet<_> = all.iter().map(ToString::to_string).collect();
+            assert_eq!(names.len(), all.len());
+            assert!(names.contains("press:volume-up"));
+            assert_eq!(
+                Command::Keypress(LogicalAddress::Tv, UserControlCode::InputSelect).to_string(),
+                "keypress:tv:input-select"
+            );
+        }
+
+        #[test]
+        fn test_button_names() -> Result<(), String> {
+            for button in Button::ALL {
+                assert_eq!(cli::parse_button(button.name())?, button);
+                assert_eq!(Key::from(button).name(), button.name());
+            }
+            Ok(())
+        }
+    }
+
+    mod system_audio {
+        use super::*;
+
+        #[test]
+        fn test_enable() {
+            let mut worker = worker(Config::default());
+            worker.enable_system_audio();
+            worker.enable_system_audio();
+            assert_eq!(
+                worker.sink.calls(),
+                vec![
+                    Call::SystemAudioMode,
+                    Call::SetSystemAudioMode(true),
+                    Call::SystemAudioMode,
+                ]
+            );
+        }
+
+        #[test]
+        fn test_no_audio_system() {
+            let mut worker = worker(Config::default());
+            worker.sink.no_audio_system.set(true);
+            worker.enable_system_audio();
+            assert_eq!(worker.sink.calls(), vec![]);
         }
     }
 }
@@ -0,0 +1,159 @@
+//! A bounded channel which drops the oldest value when full, so a burst of
+//! values can't grow memory without bound or block the sender. Sending never
+//! blocks, so it's safe to use from OS hooks.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::sync::Notify;
+
+/// Creates a drop-oldest channel holding at most `capacity` values.
+#[must_use]
+pub fn drop_oldest<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+        dropped: AtomicUsize::new(0),
+        senders: AtomicUsize::new(1),
+        notify: Notify::new(),
+    });
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+    /// How many values were dropped since last checked.
+    dropped: AtomicUsize,
+    senders: AtomicUsize,
+    notify: Notify,
+}
+
+impl<T> Sender<T> {
+    /// Sends a value, dropping the oldest value if the channel is full.
+    pub fn send(&self, value: T) {
+        let Ok(mut queue) = self.shared.queue.lock() else {
+            return;
+        };
+
+        if queue.len() >= self.shared.capacity {
+            queue.pop_front();
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queue.push_back(value);
+        drop(queue);
+
+        self.shared.notify.notify_one();
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the oldest value, or `None` once every sender is dropped and
+    /// the channel is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        // Holding our own handle lets `notified` outlive the borrow of `self`
+        // taken by `try_recv`.
+        let shared = self.shared.clone();
+        loop {
+            let notified = shared.notify.notified();
+            if let Some(value) = self.try_recv() {
+                return Some(value);
+            }
+            if self.shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Receives the oldest value, if any.
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.shared.queue.lock().ok()?.pop_front()
+    }
+
+    /// Returns how many values were dropped since last called.
+    #[must_use]
+    pub fn take_dropped(&self) -> usize {
+        self.shared.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drops_oldest() {
+        let (tx, mut rx) = drop_oldest(2);
+        tx.send(1);
+        tx.send(2);
+        tx.send(3);
+
+        assert_eq!(rx.take_dropped(), 1);
+        assert_eq!(rx.take_dropped(), 0);
+        assert_eq!(rx.try_recv(), Some(2));
+        assert_eq!(rx.try_recv(), Some(3));
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[tokio::test]
+    async fn test_recv_closed() {
+        let (tx, mut rx) = drop_oldest(2);
+        let tx2 = tx.clone();
+        tx.send(1);
+        drop(tx);
+        drop(tx2);
+
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_recv_waits() {
+        let (tx, mut rx) = drop_oldest(2);
+        let handle = tokio::spawn(async move { rx.recv().await });
+        tx.send(1);
+
+        assert_eq!(handle.await.ok(), Some(Some(1)));
+    }
+}
@@ -0,0 +1,1315 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, IsTerminal, Write},
+    num::ParseIntError,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use ::cec::{
+    Adapter, DataPacket, DeviceKind, LogicalAddress, ParseDataPacketError, PhysicalAddress,
+    UserControlCode,
+};
+use clap::{ArgAction, Parser, Subcommand};
+use color_eyre::eyre::{eyre, Result};
+use once_cell::sync::Lazy;
+use tracing::{info, level_filters::LevelFilter, warn};
+
+use crate::{
+    cec::{self, Button, PowerOnStep},
+    config, ipc, os, timer,
+    translate::Translator,
+};
+
+/// owl's version, along with the bundled libcec version and the build target,
+/// so bug reports say exactly what's running.
+static VERSION: Lazy<String> = Lazy::new(|| {
+    format!(
+        "{} (libcec {}, {})",
+        env!("CARGO_PKG_VERSION"),
+        ::cec::LIBCEC_VERSION,
+        target_lexicon::HOST
+    )
+});
+
+/// Integrates your PC with HDMI-CEC.
+#[derive(Debug, Clone, Parser)]
+#[command(version = VERSION.as_str(), about, long_about = None)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The TOML config file to read settings from. Settings given on the
+    /// command line take precedence.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Check the config file for problems, then exit without touching the
+    /// adapter or the OS. Exits non-zero listing the problems if there are
+    /// any.
+    #[arg(long)]
+    pub config_check: bool,
+
+    /// The device kind owl registers as on the HDMI-CEC bus. Defaults to
+    /// `recording`.
+    #[arg(long, value_parser = parse_device_kind)]
+    pub device_kind: Option<DeviceKind>,
+
+    /// The device kind owl announces when becoming the active source. Defaults
+    /// to `--device-kind`.
+    #[arg(long, value_parser = parse_device_kind)]
+    pub active_source_kind: Option<DeviceKind>,
+
+    /// The com port of the adapter to connect to, like `/dev/ttyACM0` or
+    /// `COM3`. Needed when several adapters are connected, unless owl can ask
+    /// which to use. Defaults to the only adapter found.
+    #[arg(long)]
+    pub adapter: Option<String>,
+
+    /// The HDMI port the adapter is connected to, or `auto` to read it from
+    /// the display's EDID, falling back to the config file's port. Defaults
+    /// to 2.
+    #[arg(long)]
+    pub hdmi_port: Option<HdmiPort>,
+
+    /// Forces the adapter's physical address, like `1.0.0.0`, instead of
+    /// deriving it from the HDMI port.
+    #[arg(long)]
+    pub physical_address: Option<PhysicalAddress>,
+
+    /// Restore the audio system's volume on resume, for audio systems which
+    /// reset their volume when powered off.
+    #[arg(long)]
+    pub restore_volume: bool,
+
+    /// Set the audio system to this volume, from 0 to 100, when powering the
+    /// TV on, so it never starts blasting. Takes precedence over
+    /// `--restore-volume`.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub on_power_on_volume: Option<u8>,
+
+    /// Unmute the audio system after switching the TV to the PC or powering
+    /// it on, for audio systems left muted by a previous session.
+    #[arg(long)]
+    pub unmute_on_focus: bool,
+
+    /// Turn the audio system's system audio mode on at startup, for audio
+    /// systems which ignore volume keys until ARC is enabled on the TV.
+    #[arg(long)]
+    pub system_audio: bool,
+
+    /// Never change the active source, only power the TV on and off. Useful if
+    /// inputs are switched some other way.
+    #[arg(long)]
+    pub no_activate_source: bool,
+
+    /// Log every HDMI-CEC command received, instead of logging runs of
+    /// identical commands once with a count.
+    #[arg(long)]
+    pub no_collapse_repeats: bool,
+
+    /// A command sent again if sending fails, may be repeated, like
+    /// `power-on`. Defaults to `power-on`, `power-off` and `focus`.
+    #[arg(long = "retry-command", value_parser = parse_command)]
+    pub retry_commands: Vec<cec::Command>,
+
+    /// How many times a retried command is attempted before giving up.
+    /// Defaults to 3.
+    #[arg(long)]
+    pub retry_attempts: Option<u8>,
+
+    /// How long to wait before attempting a command again, in milliseconds.
+    /// Defaults to 100.
+    #[arg(long)]
+    pub retry_delay_ms: Option<u64>,
+
+    /// How long to wait for HDMI-CEC commands to be acknowledged, in
+    /// milliseconds. Defaults to 1000. Keypresses, power on and standby are
+    /// sent by libcec with its own timeout.
+    #[arg(long)]
+    pub transmit_timeout_ms: Option<u64>,
+
+    /// A button whose release is sent to the audio system, may be repeated.
+    /// Defaults to `volume-up` and `volume-down`.
+    #[arg(long = "release-button", value_parser = parse_button)]
+    pub release_buttons: Vec<Button>,
+
+    /// A step taken to wake the TV, may be repeated, like `image-view-on`.
+    /// Defaults to steps known to work for the TV's vendor.
+    #[arg(long = "power-on-step", value_parser = parse_power_on_step)]
+    pub power_on: Vec<PowerOnStep>,
+
+    /// Where standby is sent on suspend: `tv`, `all` to broadcast it so e.g. an
+    /// audio system powers down too, or a logical address like `audiosystem`.
+    /// Defaults to `tv`.
+    #[arg(long, value_parser = parse_standby_target)]
+    pub standby_target: Option<LogicalAddress>,
+
+    /// Power the TV off when mute is pressed twice within this many
+    /// milliseconds. Both presses still toggle mute. Must be longer than the
+    /// button debounce. Defaults to off.
+    #[arg(long)]
+    pub mute_double_tap_ms: Option<u64>,
+
+    /// Ping the HDMI-CEC adapter this often, in milliseconds, to notice it
+    /// being unplugged without sending anything on the bus. Defaults to off.
+    #[arg(long)]
+    pub adapter_ping_interval_ms: Option<u64>,
+
+    /// Only monitor the HDMI-CEC bus until a command needs sending, then go
+    /// back to monitoring once idle, so owl holds no logical address while
+    /// it's doing nothing. The first command after idling is slower, and the
+    /// TV remote's keys only reach owl while it's on the bus.
+    #[arg(long)]
+    pub lazy_active: bool,
+
+    /// How long owl stays on the bus after its last command with
+    /// `--lazy-active`, in milliseconds. Defaults to 30000.
+    #[arg(long)]
+    pub lazy_active_idle_ms: Option<u64>,
+
+    /// How long after a volume button is pressed or released that further
+    /// presses are ignored, in milliseconds. Limits how fast a held button
+    /// ramps the volume. Defaults to 200.
+    #[arg(long)]
+    pub button_debounce_ms: Option<u64>,
+
+    /// How many sent HDMI-CEC commands are kept for diagnostics. Defaults to
+    /// 64.
+    #[arg(long)]
+    pub history_len: Option<usize>,
+
+    /// How long the display must stay off before owl suspends, in
+    /// milliseconds. Defaults to 2000.
+    #[arg(long)]
+    pub display_off_delay_ms: Option<u64>,
+
+    /// How often the Windows message loop is checked to still be responding,
+    /// in milliseconds, 0 to never check. Defaults to 10000.
+    #[arg(long)]
+    pub heartbeat_interval_ms: Option<u64>,
+
+    /// Whether to hide volume keys from the rest of the system, so only the
+    /// audio system's volume changes. Defaults to whether an audio system is
+    /// on the bus.
+    #[arg(long)]
+    pub suppress_volume_keys: Option<bool>,
+
+    /// A monitor whose focus switches the TV to the PC, may be repeated, like
+    /// `1` for `\\.\DISPLAY1`. Defaults to any monitor. Only supported on
+    /// Windows.
+    #[arg(long = "focus-monitor")]
+    pub focus_monitors: Vec<u32>,
+
+    /// Log only warnings and errors.
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log more detail, may be repeated. `-v` logs owl's debug messages,
+    /// `-vv` traces owl and shows libcec's debug messages too.
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// The tracing filter, like `owl=debug,libcec=info`. Takes precedence
+    /// over `--quiet`, `--verbose` and `RUST_LOG`.
+    #[arg(long)]
+    pub log_filter: Option<String>,
+
+    /// Forward volume keys pressed on the TV remote to the PC, so the remote
+    /// changes the PC's volume too. Only supported on Windows.
+    #[arg(long)]
+    pub bidirectional_volume: bool,
+
+    /// Mute and unmute the audio system along with the PC, however the PC was
+    /// muted, rather than only on the mute key. Only supported on Windows.
+    #[arg(long)]
+    pub mirror_mute: bool,
+
+    /// Leave the TV on when the PC suspends, rather than powering it off.
+    #[arg(long)]
+    pub no_power_off_on_suspend: bool,
+
+    /// Leave the TV off when the PC resumes, rather than powering it on. Useful
+    /// if the PC wakes by itself, like for updates.
+    #[arg(long)]
+    pub no_power_on_on_resume: bool,
+
+    /// The pidfile holding the running instance's pid. On Unix it's also the
+    /// lock keeping a second instance from starting, defaulting to `owl.pid`
+    /// in `$XDG_RUNTIME_DIR`.
+    #[arg(long)]
+    pub pidfile: Option<PathBuf>,
+
+    /// Listen for JSON commands from other local processes on this Unix
+    /// socket, or on Windows this named pipe, like `\\.\pipe\owl`. Each
+    /// line is a command like `{"PowerOff":null}`.
+    #[arg(long)]
+    pub ipc_socket: Option<PathBuf>,
+
+    /// Run owl's async side on a single thread rather than one per core. The
+    /// jobs run on their own threads either way, so this only trims idle
+    /// threads, which helps on small boxes like a Raspberry Pi.
+    #[arg(long)]
+    pub single_thread: bool,
+
+    /// The most verbose libcec log level shown, independent of `RUST_LOG`'s
+    /// filter for owl. libcec's notices are logged at `info`, and its very
+    /// chatty bus traffic at `trace`. Defaults to `warn`, or `RUST_LOG`'s
+    /// filter if set.
+    #[arg(long)]
+    pub log_libcec_level: Option<LevelFilter>,
+
+    /// The address to serve Prometheus metrics on.
+    #[cfg(feature = "metrics")]
+    #[arg(long, default_value = "127.0.0.1:9898")]
+    pub metrics_addr: std::net::SocketAddr,
+
+    /// Append a JSON line to this file for every stage each OS event passes
+    /// on its way to the bus, from being received to the command being sent.
+    #[arg(long)]
+    pub trace_pipeline: Option<PathBuf>,
+
+    /// Show a desktop notification when owl loses or can't connect to the
+    /// adapter. Does nothing without a desktop session.
+    #[cfg(feature = "notifications")]
+    #[arg(long)]
+    pub notify: bool,
+
+    /// The settings read from the config file.
+    #[arg(skip)]
+    pub file: config::File,
+}
+
+/// The HDMI port the adapter is connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdmiPort {
+    /// Detect the port from the OS, best-effort.
+    Auto,
+    Port(u8),
+}
+
+impl FromStr for HdmiPort {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            _ => Ok(Self::Port(s.parse()?)),
+        }
+    }
+}
+
+/// Runs something other than the integration.
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Prints the devices on the HDMI-CEC bus as a tree, showing which HDMI
+    /// port each is connected to.
+    Topology,
+    /// Sends a raw HDMI-CEC frame, for opcodes owl doesn't model.
+    SendRaw {
+        /// The frame as colon separated hex bytes, like `40:04`. The first
+        /// byte holds the initiator's logical address in the high nibble and
+        /// the destination's in the low nibble, followed by the opcode and its
+        /// operands.
+        frame: Frame,
+        /// Prints the frame owl would send, without connecting to the bus.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Records the frames on the HDMI-CEC bus to a trace file until stopped
+    /// with Ctrl+C, without taking part in the bus.
+    Record {
+        /// The trace file to append to.
+        #[arg(long)]
+        out: PathBuf,
+        /// Only record frames sent by or to this logical address, like `tv`,
+        /// including broadcasts.
+        #[arg(long, value_parser = parse_logical_address)]
+        filter: Option<LogicalAddress>,
+    },
+
+    /// Sends the HDMI-CEC commands a single OS event translates into, printing
+    /// each and whether it was sent.
+    TestEvent {
+        /// The event: `suspend`, `resume`, `shutdown`, `focus`,
+        /// `press:<key>` or `release:<key>` with a key like `volume-up`, or
+        /// `raw:<key code>` for a mapped key.
+        #[arg(value_parser = parse_event)]
+        event: os::Event,
+    },
+
+    /// Prints the key code and name of each key pressed until Esc, for
+    /// mapping keys in the config's `[[keymap]]`.
+    ListKeys,
+
+    /// Registers as the TV and replies to queries like the OSD name with
+    /// canned responses from the config file's `[tv-emulator]` table, to test
+    /// other HDMI-CEC software without a real TV. Runs until Ctrl+C.
+    #[cfg(feature = "tv-emulator")]
+    TvEmulator,
+    /// Connects, then sends commands typed at a prompt, like `on`, `vol+` or
+    /// `raw 40:04`, printing how each went. Type `help` for the commands.
+    #[cfg(feature = "repl")]
+    Repl,
+}
+
+/// A raw HDMI-CEC frame, parsed from colon separated hex bytes like `40:04`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame(pub Vec<u8>);
+
+// Frames are written like cec-client does, colon separated and lowercase, on
+// top of the space separated `DataPacket` format.
+impl Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A frame is at most 16 bytes, so it always fits in a packet.
+        let packet = DataPacket(self.0.as_slice().try_into().map_err(|_| fmt::Error)?);
+        write!(f, "{}", packet.to_string().to_lowercase().replace(' ', ":"))
+    }
+}
+
+impl FromStr for Frame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The packet splits on any whitespace, so only colons may separate
+        // bytes here.
+        if let Some(x) = s
+            .split(':')
+            .find(|x| x.is_empty() || x.contains(char::is_whitespace))
+        {
+            return Err(ParseDataPacketError::InvalidByte(x.to_owned()).to_string());
+        }
+
+        s.replace(':', " ")
+            .parse::<DataPacket>()
+            .map(|x| Self(x.0.to_vec()))
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Args {
+    /// Parses the command line, then reads the config file if one was given.
+    pub fn load() -> Result<Self> {
+        Self::parse().reload()
+    }
+
+    /// Re-reads the config file, keeping the command line settings.
+    pub fn reload(mut self) -> Result<Self> {
+        if let Some(path) = &self.config {
+            self.file = config::File::read(path)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Reads and checks the config file, without connecting to anything,
+    /// returning every problem found.
+    #[must_use]
+    pub fn check_config(&self) -> Vec<String> {
+        let Some(path) = &self.config else {
+            return vec!["no config file given, pass one with `--config`".to_owned()];
+        };
+
+        match config::File::read(path) {
+            Ok(file) => file.problems().iter().map(ToString::to_string).collect(),
+            Err(e) => vec![format!("{e:#}")],
+        }
+    }
+
+    /// Picks the adapter to connect to when several are connected and none
+    /// was configured, since libcec may detect the wrong one. owl asks which
+    /// when run from a terminal, and fails listing the adapters otherwise.
+    pub fn choose_adapter(mut self) -> Result<Self> {
+        if self.adapter.is_some() || self.file.adapter.is_some() {
+            return Ok(self);
+        }
+
+        let adapters = cec::adapters(&cec::Config::default())?;
+        if adapters.len() < 2 {
+            return Ok(self);
+        }
+
+        let list = adapters
+            .iter()
+            .enumerate()
+            .map(|(i, x)| format!("    {}. {x}", i + 1))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !io::stdin().is_terminal() {
+            return Err(eyre!(
+                "found {} cec adapters, choose one with `--adapter`:\n{list}",
+                adapters.len()
+            ));
+        }
+
+        println!("found {} cec adapters:\n{list}", adapters.len());
+        let adapter = loop {
+            print!("which adapter? [1-{}] ", adapters.len());
+            io::stdout().flush()?;
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Err(eyre!("no cec adapter chosen"));
+            }
+            if let Some(adapter) = choose(&adapters, &line) {
+                break adapter;
+            }
+        };
+
+        info!("using cec adapter {adapter}");
+        self.adapter = Some(adapter.port.clone());
+        Ok(self)
+    }
+
+    /// Returns the HDMI-CEC job configuration.
+    #[must_use]
+    pub fn cec_config(&self) -> cec::Config {
+        let default = cec::Config::default();
+        let file = &self.file;
+        let fallback_port = file.hdmi_port.unwrap_or(default.hdmi_port);
+        let (hdmi_port, detected_address) = match self.hdmi_port {
+            Some(HdmiPort::Port(x)) => (x, None),
+            Some(HdmiPort::Auto) => detect_hdmi_port(fallback_port),
+            None => (fallback_port, None),
+        };
+
+        cec::Config {
+            device_kind: self
+                .device_kind
+                .or(file.device_kind)
+                .unwrap_or(default.device_kind),
+            active_source_kind: self.active_source_kind.or(file.active_source_kind),
+            adapter: self.adapter.clone().or_else(|| file.adapter.clone()),
+            hdmi_port,
+            physical_address: self
+                .physical_address
+                .or(file.physical_address)
+                .or(detected_address),
+            restore_volume: self.restore_volume || file.restore_volume.unwrap_or(false),
+            power_on_volume: self.on_power_on_volume.or(file.on_power_on_volume),
+            unmute_on_focus: self.unmute_on_focus || file.unmute_on_focus.unwrap_or(false),
+            system_audio: self.system_audio || file.system_audio.unwrap_or(false),
+            activate_source: !self.no_activate_source
+                && file.activate_source.unwrap_or(default.activate_source),
+            transmit_timeout: self
+                .transmit_timeout_ms
+                .or(file.transmit_timeout_ms)
+                .map_or(default.transmit_timeout, Duration::from_millis),
+            release_buttons: if self.release_buttons.is_empty() {
+                file.release_buttons
+                    .as_ref()
+                    .map_or(default.release_buttons, |x| x.iter().copied().collect())
+            } else {
+                self.release_buttons.iter().copied().collect()
+            },
+            history_len: self
+                .history_len
+                .or(file.history_len)
+                .unwrap_or(default.history_len),
+            button_debounce: self
+                .button_debounce_ms
+                .or(file.button_debounce_ms)
+                .map_or(default.button_debounce, Duration::from_millis),
+            power_on: if self.power_on.is_empty() {
+                file.power_on.clone()
+            } else {
+                Some(self.power_on.clone())
+            },
+            collapse_repeats: !self.no_collapse_repeats
+                && file.collapse_repeats.unwrap_or(default.collapse_repeats),
+            standby_target: self
+                .standby_target
+                .or(file.standby_target)
+                .unwrap_or(default.standby_target),
+            retry_commands: if self.retry_commands.is_empty() {
+                file.retry_commands
+                    .as_ref()
+                    .map_or(default.retry_commands, |x| x.iter().copied().collect())
+            } else {
+                self.retry_commands.iter().copied().collect()
+            },
+            retry_attempts: self
+                .retry_attempts
+                .or(file.retry_attempts)
+                .unwrap_or(default.retry_attempts),
+            retry_delay: self
+                .retry_delay_ms
+                .or(file.retry_delay_ms)
+                .map_or(default.retry_delay, Duration::from_millis),
+            mute_double_tap: self
+                .mute_double_tap_ms
+                .or(file.mute_double_tap_ms)
+                .map(Duration::from_millis),
+            adapter_ping_interval: self
+                .adapter_ping_interval_ms
+                .or(file.adapter_ping_interval_ms)
+                .map(Duration::from_millis),
+            lazy_active: (self.lazy_active || file.lazy_active.unwrap_or(false)).then(|| {
+                self.lazy_active_idle_ms
+                    .or(file.lazy_active_idle_ms)
+                    .map_or(cec::Config::LAZY_ACTIVE_IDLE, Duration::from_millis)
+            }),
+        }
+    }
+
+    /// Returns the explicitly configured volume key suppression, if any.
+    #[must_use]
+    pub fn suppress_volume_keys(&self) -> Option<bool> {
+        self.suppress_volume_keys.or(self.file.suppress_volume_keys)
+    }
+
+    /// Returns whether volume keys from the TV remote are forwarded to the PC.
+    #[must_use]
+    pub fn bidirectional_volume(&self) -> bool {
+        self.bidirectional_volume || self.file.bidirectional_volume.unwrap_or(false)
+    }
+
+    /// Returns whether the audio system's mute state follows the PC's.
+    #[must_use]
+    pub fn mirror_mute(&self) -> bool {
+        self.mirror_mute || self.file.mirror_mute.unwrap_or(false)
+    }
+
+    /// Whether the TV is powered off when the PC suspends.
+    #[must_use]
+    pub fn power_off_on_suspend(&self) -> bool {
+        !self.no_power_off_on_suspend && self.file.power_off_on_suspend.unwrap_or(true)
+    }
+
+    /// Whether the TV is powered on when the PC resumes.
+    #[must_use]
+    pub fn power_on_on_resume(&self) -> bool {
+        !self.no_power_on_on_resume && self.file.power_on_on_resume.unwrap_or(true)
+    }
+
+    /// Returns whether the async runtime runs on a single thread.
+    #[must_use]
+    pub fn single_thread(&self) -> bool {
+        self.single_thread || self.file.single_thread.unwrap_or(false)
+    }
+
+    /// Returns whether the command connects to the adapter, which only one
+    /// instance can hold. Dry runs and listing keys don't touch the bus.
+    #[must_use]
+    pub const fn connects(&self) -> bool {
+        !matches!(
+            self.command,
+            Some(Command::ListKeys | Command::SendRaw { dry_run: true, .. })
+        )
+    }
+
+    /// Returns the pidfile, if one was configured.
+    #[must_use]
+    pub fn pidfile(&self) -> Option<&Path> {
+        self.pidfile.as_deref().or(self.file.pidfile.as_deref())
+    }
+
+    /// Returns the OS job configuration. Unless configured otherwise, volume
+    /// keys are only suppressed if there's an audio system to send them to.
+    #[must_use]
+    pub fn os_config(&self, has_audio_system: bool) -> os::Config {
+        os::Config {
+            suppress_volume_keys: self.suppress_volume_keys().unwrap_or(has_audio_system),
+            raw_keys: self.file.keymap.iter().map(|x| x.key).collect(),
+            display_off_delay: self
+                .display_off_delay_ms
+                .or(self.file.display_off_delay_ms)
+                .map_or_else(
+                    || os::Config::default().display_off_delay,
+                    Duration::from_millis,
+                ),
+            heartbeat_interval: self
+                .heartbeat_interval_ms
+                .or(self.file.heartbeat_interval_ms)
+                .map_or_else(
+                    || os::Config::default().heartbeat_interval,
+                    Duration::from_millis,
+                ),
+            mirror_mute: self.mirror_mute(),
+        }
+    }
+
+    /// Returns the tracing filter chosen on the command line, `None` if
+    /// neither `--log-filter`, `--quiet` nor `--verbose` were given.
+    #[must_use]
+    pub fn log_filter(&self) -> Option<String> {
+        if let Some(filter) = &self.log_filter {
+            return Some(filter.clone());
+        }
+
+        let filter = match (self.quiet, self.verbose) {
+            (true, _) => "owl=warn,libcec=warn",
+            (false, 0) => return None,
+            (false, 1) => "owl=debug,libcec=warn",
+            (false, _) => "owl=trace,libcec=debug",
+        };
+        Some(filter.to_owned())
+    }
+
+    /// Returns the timer job configuration, sending commands to `cmd_tx`.
+    #[must_use]
+    pub fn timer_config(&self, cmd_tx: cec::CommandTx) -> timer::Config {
+        timer::Config {
+            timers: self.file.timer.clone(),
+            cmd_tx,
+        }
+    }
+
+    /// Returns the IPC job configuration, sending commands to `cmd_tx`, `None`
+    /// if no socket was configured.
+    #[must_use]
+    pub fn ipc_config(&self, cmd_tx: cec::CommandTx) -> Option<ipc::Config> {
+        let path = self.ipc_socket.as_ref().or(self.file.ipc_socket.as_ref())?;
+        Some(ipc::Config {
+            path: path.clone(),
+            cmd_tx,
+        })
+    }
+
+    /// Returns the translator from OS events to HDMI-CEC commands.
+    #[must_use]
+    pub fn translator(&self) -> Translator {
+        let focus_monitors = if self.focus_monitors.is_empty() {
+            self.file.focus_monitors.as_deref().unwrap_or_default()
+        } else {
+            &self.focus_monitors
+        };
+        let mut translator = Translator::default()
+            .with_keymap(&self.file.keymap)
+            .with_focus_monitors(focus_monitors.iter().copied().map(os::Monitor));
+        if !self.power_off_on_suspend() {
+            translator = translator.with(os::Event::Suspend, vec![]);
+        }
+        if !self.power_on_on_resume() {
+            translator = translator.with(os::Event::Resume, vec![]);
+        }
+        translator
+    }
+
+    /// Returns the metrics exporter job configuration.
+    #[cfg(feature = "metrics")]
+    #[must_use]
+    pub const fn metrics_config(&self) -> crate::metrics::Config {
+        crate::metrics::Config {
+            addr: self.metrics_addr,
+        }
+    }
+}
+
+/// Returns the adapter chosen by its number in the list, or by its port.
+fn choose<'a>(adapters: &'a [Adapter], choice: &str) -> Option<&'a Adapter> {
+    let choice = choice.trim();
+    match choice.parse::<usize>() {
+        Ok(i) => adapters.get(i.checked_sub(1)?),
+        Err(_) => adapters.iter().find(|x| x.port == choice),
+    }
+}
+
+/// Detects the TV's HDMI port from the OS, returning the full physical
+/// address too if the PC is behind another device, like an audio system.
+fn detect_hdmi_port(fallback: u8) -> (u8, Option<PhysicalAddress>) {
+    if let Some(address) = os::hdmi_address().filter(|x| x.port().is_some()) {
+        let port = address.ports()[0];
+        info!("detected physical address {address} from the display, using hdmi port {port}");
+        (port, (address.depth() > Some(1)).then_some(address))
+    } else {
+        warn!("failed to detect the hdmi port, falling back to {fallback}");
+        (fallback, None)
+    }
+}
+
+pub(crate) fn parse_device_kind(s: &str) -> Result<DeviceKind, String> {
+    match s {
+        "tv" => Ok(DeviceKind::Tv),
+        "recording" => Ok(DeviceKind::RecordingDevice),
+        "tuner" => Ok(DeviceKind::Tuner),
+        "playback" => Ok(DeviceKind::PlaybackDevice),
+        "audio" => Ok(DeviceKind::AudioSystem),
+        _ => Err(format!(
+            "unknown device kind `{s}`, expected one of: tv, recording, tuner, playback, audio"
+        )),
+    }
+}
+
+pub(crate) fn parse_button(s: &str) -> Result<Button, String> {
+    Button::ALL
+        .into_iter()
+        .find(|x| x.name() == s)
+        .ok_or_else(|| {
+            let names: Vec<_> = Button::ALL.map(Button::name).into();
+            format!(
+                "unknown button `{s}`, expected one of: {}",
+                names.join(", ")
+            )
+        })
+}
+
+pub(crate) fn parse_power_on_step(s: &str) -> Result<PowerOnStep, String> {
+    match s {
+        "power-on-devices" => Ok(PowerOnStep::PowerOnDevices),
+        "image-view-on" => Ok(PowerOnStep::ImageViewOn),
+        "text-view-on" => Ok(PowerOnStep::TextViewOn),
+        "set-stream-path" => Ok(PowerOnStep::SetStreamPath),
+        "active-source" => Ok(PowerOnStep::ActiveSource),
+        _ => Err(format!(
+            "unknown power on step `{s}`, expected one of: power-on-devices, image-view-on, \
+             text-view-on, set-stream-path, active-source"
+        )),
+    }
+}
+
+/// Parses a command a timer can send or which can be retried, like
+/// `power-off`.
+pub(crate) fn parse_command(s: &str) -> Result<cec::Command, String> {
+    match s {
+        "power-on" => Ok(cec::Command::PowerOn),
+        "power-off" => Ok(cec::Command::PowerOff),
+        "focus" => Ok(cec::Command::Focus),
+        "mute" => Ok(cec::Command::SetMute(true)),
+        "unmute" => Ok(cec::Command::SetMute(false)),
+        _ => Err(format!(
+            "unknown command `{s}`, expected one of: power-on, power-off, focus, mute, unmute"
+        )),
+    }
+}
+
+/// Parses a user control code from its kebab-case name, like `input-select`.
+pub(crate) fn parse_control(s: &str) -> Result<UserControlCode, String> {
+    (0..=u8::MAX)
+        .filter_map(|x| UserControlCode::try_from(x).ok())
+        .find(|x| kebab_case(&format!("{x:?}")) == s)
+        .ok_or_else(|| format!("unknown control `{s}`, expected a kebab-case user control code"))
+}
+
+/// Parses a logical address from its kebab-case name, like `tv` or
+/// `playbackdevice1`.
+pub(crate) fn parse_logical_address(s: &str) -> Result<LogicalAddress, String> {
+    (0..16)
+        .filter_map(|x| LogicalAddress::try_from(x).ok())
+        .find(|x| kebab_case(&format!("{x:?}")) == s)
+        .ok_or_else(|| format!("unknown logical address `{s}`, expected e.g. tv, audiosystem"))
+}
+
+pub(crate) fn parse_event(s: &str) -> Result<os::Event, String> {
+    let key = |s: &str| {
+        os::Key::ALL
+            .into_iter()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = os::Key::ALL.map(os::Key::name).into();
+                format!("unknown key `{s}`, expected one of: {}", names.join(", "))
+            })
+    };
+
+    match s.split_once(':') {
+        None if s == "suspend" => Ok(os::Event::Suspend),
+        None if s == "resume" => Ok(os::Event::Resume),
+        None if s == "shutdown" => Ok(os::Event::Shutdown),
+        None if s == "focus" => Ok(os::Event::Focus(None)),
+        Some(("focus", x)) => x
+            .parse()
+            .map(|x| os::Event::Focus(Some(os::Monitor(x))))
+            .map_err(|e| format!("invalid monitor `{x}`: {e}")),
+        Some(("press", x)) => key(x).map(os::Event::Press),
+        Some(("release", x)) => key(x).map(os::Event::Release),
+        Some(("raw", x)) => x.parse().map(os::Event::RawKey).map_err(|e| format!("{e}")),
+        None if s == "mute" => Ok(os::Event::Mute(true)),
+        None if s == "unmute" => Ok(os::Event::Mute(false)),
+        _ => Err(format!(
+            "unknown event `{s}`, expected one of: suspend, resume, shutdown, focus, \
+             focus:<monitor>, press:<key>, release:<key>, raw:<key code>, mute, unmute"
+        )),
+    }
+}
+
+/// Parses where standby is sent: `all` broadcasts it, otherwise it's a logical
+/// address like `tv`.
+pub(crate) fn parse_standby_target(s: &str) -> Result<LogicalAddress, String> {
+    match s {
+        "all" => Ok(LogicalAddress::BROADCAST),
+        _ => parse_logical_address(s).map_err(|_| {
+            format!("unknown standby target `{s}`, expected tv, all, or a logical address")
+        }),
+    }
+}
+
+pub(crate) fn kebab_case(s: &str) -> String {
+    let mut kebab = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_ascii_uppercase() && i != 0 {
+            kebab.push('-');
+        }
+        kebab.push(c.to_ascii_lowercase());
+    }
+    kebab
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_version() {
+        assert!(VERSION.starts_with(env!("CARGO_PKG_VERSION")));
+        assert!(VERSION.contains(::cec::LIBCEC_VERSION));
+        assert!(VERSION.contains(&target_lexicon::HOST.to_string()));
+    }
+
+    #[test]
+    fn test_log_filter() -> Result<(), clap::Error> {
+        let filter = |args: &[&str]| {
+            Args::try_parse_from([&["owl"][..], args].concat()).map(|x| x.log_filter())
+        };
+        assert_eq!(filter(&[])?, None);
+        assert_eq!(filter(&["-q"])?.as_deref(), Some("owl=warn,libcec=warn"));
+        assert_eq!(filter(&["-v"])?.as_deref(), Some("owl=debug,libcec=warn"));
+        assert_eq!(filter(&["-vv"])?.as_deref(), Some("owl=trace,libcec=debug"));
+        assert_eq!(
+            filter(&["-vv", "--log-filter", "owl=info"])?.as_deref(),
+            Some("owl=info")
+        );
+        assert!(filter(&["-q", "-v"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_transmit_timeout() -> Result<(), clap::Error> {
+        let args = Args::try_parse_from(["owl", "--transmit-timeout-ms", "250"])?;
+        assert_eq!(
+            args.cec_config().transmit_timeout,
+            Duration::from_millis(250)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_transmit_timeout_default() -> Result<(), clap::Error> {
+        let args = Args::try_parse_from(["owl"])?;
+        assert_eq!(
+            args.cec_config().transmit_timeout,
+            cec::Config::default().transmit_timeout
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_args_override_file() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl", "--hdmi-port", "1"])?;
+        args.file = config::File::parse(
+            r#"
+            hdmi-port = 3
+            device-kind = "playback"
+            activate-source = false
+            "#,
+        )?;
+
+        let config = args.cec_config();
+        assert_eq!(config.hdmi_port, 1);
+        assert_eq!(config.device_kind, DeviceKind::PlaybackDevice);
+        assert!(!config.activate_source);
+        Ok(())
+    }
+
+    #[test]
+    fn test_release_buttons() -> Result<()> {
+        let args = Args::try_parse_from(["owl"])?;
+        assert_eq!(
+            args.cec_config().release_buttons,
+            cec::Config::default().release_buttons
+        );
+
+        let mut args = Args::try_parse_from(["owl", "--release-button", "volume-mute"])?;
+        args.file = config::File::parse("release-buttons = []")?;
+        assert_eq!(
+            args.cec_config().release_buttons,
+            [Button::VolumeMute].into()
+        );
+
+        args.release_buttons.clear();
+        assert!(args.cec_config().release_buttons.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bidirectional_volume() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert!(!args.bidirectional_volume());
+        args.file = config::File::parse("bidirectional-volume = true")?;
+        assert!(args.bidirectional_volume());
+        assert!(Args::try_parse_from(["owl", "--bidirectional-volume"])?.bidirectional_volume());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_active() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert_eq!(args.cec_config().lazy_active, None);
+        args.file = config::File::parse("lazy-active = true")?;
+        assert_eq!(
+            args.cec_config().lazy_active,
+            Some(cec::Config::LAZY_ACTIVE_IDLE)
+        );
+
+        let args = Args::try_parse_from(["owl", "--lazy-active", "--lazy-active-idle-ms", "500"])?;
+        assert_eq!(
+            args.cec_config().lazy_active,
+            Some(Duration::from_millis(500))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror_mute() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert!(!args.os_config(true).mirror_mute);
+        args.file = config::File::parse("mirror-mute = true")?;
+        assert!(args.os_config(true).mirror_mute);
+        assert!(Args::try_parse_from(["owl", "--mirror-mute"])?.mirror_mute());
+        Ok(())
+    }
+
+    #[test]
+    fn test_focus_monitors() -> Result<()> {
+        let focus = |args: &Args, monitor| {
+            !args
+                .translator()
+                .translate(os::Event::Focus(Some(os::Monitor(monitor))))
+                .is_empty()
+        };
+
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert!(focus(&args, 1) && focus(&args, 2));
+        args.file = config::File::parse("focus-monitors = [2]")?;
+        assert!(!focus(&args, 1) && focus(&args, 2));
+        let args = Args::try_parse_from(["owl", "--focus-monitor", "1"])?;
+        assert!(focus(&args, 1) && !focus(&args, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_power_mapping() -> Result<()> {
+        let translate = |args: &Args| {
+            let translator = args.translator();
+            (
+                translator.translate(os::Event::Suspend),
+                translator.translate(os::Event::Resume),
+            )
+        };
+        let off = || vec![cec::Command::PowerOff];
+        let on = || vec![cec::Command::PowerOn];
+
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert_eq!(translate(&args), (off(), on()));
+        args.file = config::File::parse("power-on-on-resume = false")?;
+        assert_eq!(translate(&args), (off(), vec![]));
+        args.file = config::File::parse("power-off-on-suspend = false")?;
+        assert_eq!(translate(&args), (vec![], on()));
+        args.file =
+            config::File::parse("power-off-on-suspend = false\npower-on-on-resume = false")?;
+        assert_eq!(translate(&args), (vec![], vec![]));
+
+        let args = Args::try_parse_from(["owl", "--no-power-on-on-resume"])?;
+        assert_eq!(translate(&args), (off(), vec![]));
+        let args = Args::try_parse_from(["owl", "--no-power-off-on-suspend"])?;
+        assert_eq!(translate(&args), (vec![], on()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_thread() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert!(!args.single_thread());
+        args.file = config::File::parse("single-thread = true")?;
+        assert!(args.single_thread());
+        assert!(Args::try_parse_from(["owl", "--single-thread"])?.single_thread());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_config() -> Result<()> {
+        let args = Args::try_parse_from(["owl", "--config-check"])?;
+        assert!(args.config_check);
+        assert_eq!(args.check_config().len(), 1);
+
+        let path = std::env::temp_dir().join(format!("owl-test-{}.toml", std::process::id()));
+        std::fs::write(&path, "hdmi-port = 0\nfocus-monitors = [0]\n")?;
+        let config = format!("--config={}", path.display());
+        let args = Args::try_parse_from(["owl", &config])?;
+        let problems = args.check_config();
+        std::fs::remove_file(&path)?;
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("`hdmi-port`"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert_eq!(
+            args.cec_config().retry_commands,
+            cec::Config::default().retry_commands
+        );
+
+        args.file = config::File::parse("retry-commands = [\"mute\"]\nretry-attempts = 5")?;
+        let config = args.cec_config();
+        assert_eq!(
+            config.retry_commands,
+            HashSet::from([cec::Command::SetMute(true)])
+        );
+        assert_eq!(config.retry_attempts, 5);
+
+        let args = Args::try_parse_from(["owl", "--retry-command", "power-off"])?;
+        assert_eq!(
+            args.cec_config().retry_commands,
+            HashSet::from([cec::Command::PowerOff])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_adapter() {
+        let adapter = |port: &str| Adapter {
+            port: port.to_owned(),
+            path: String::new(),
+            vendor_id: 0x2548,
+            product_id: 0x1002,
+            firmware_version: 12,
+            kind: ::cec::AdapterType::P8External,
+        };
+        let adapters = [adapter("/dev/ttyACM0"), adapter("/dev/ttyACM1")];
+        assert_eq!(choose(&adapters, "2\n"), Some(&adapters[1]));
+        assert_eq!(choose(&adapters, "/dev/ttyACM0"), Some(&adapters[0]));
+        assert_eq!(choose(&adapters, "0"), None);
+        assert_eq!(choose(&adapters, "3"), None);
+        assert_eq!(choose(&adapters, "COM3"), None);
+    }
+
+    #[test]
+    fn test_pidfile() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert_eq!(args.pidfile(), None);
+        args.file = config::File::parse("pidfile = \"/run/owl.pid\"")?;
+        assert_eq!(args.pidfile(), Some(Path::new("/run/owl.pid")));
+        args.pidfile = Some(PathBuf::from("owl.pid"));
+        assert_eq!(args.pidfile(), Some(Path::new("owl.pid")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_connects() -> Result<()> {
+        for (args, connects) in [
+            (vec!["owl"], true),
+            (vec!["owl", "topology"], true),
+            (vec!["owl", "send-raw", "40:04"], true),
+            (vec!["owl", "send-raw", "--dry-run", "40:04"], false),
+            (vec!["owl", "list-keys"], false),
+        ] {
+            assert_eq!(
+                Args::try_parse_from(&args)?.connects(),
+                connects,
+                "{args:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_power_on() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert_eq!(args.cec_config().power_on, None);
+
+        args.file = config::File::parse(r#"power-on = ["image-view-on"]"#)?;
+        assert_eq!(
+            args.cec_config().power_on,
+            Some(vec![PowerOnStep::ImageViewOn])
+        );
+
+        args.power_on = vec![PowerOnStep::TextViewOn];
+        assert_eq!(
+            args.cec_config().power_on,
+            Some(vec![PowerOnStep::TextViewOn])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_standby_target() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl"])?;
+        assert_eq!(args.cec_config().standby_target, LogicalAddress::Tv);
+
+        args.file = config::File::parse(r#"standby-target = "audiosystem""#)?;
+        assert_eq!(
+            args.cec_config().standby_target,
+            LogicalAddress::Audiosystem
+        );
+
+        let args = Args::try_parse_from(["owl", "--standby-target", "all"])?;
+        assert_eq!(args.cec_config().standby_target, LogicalAddress::BROADCAST);
+        assert!(Args::try_parse_from(["owl", "--standby-target", "everything"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_suppress_volume_keys() -> Result<()> {
+        let args = Args::try_parse_from(["owl"])?;
+        assert!(args.os_config(true).suppress_volume_keys);
+        assert!(!args.os_config(false).suppress_volume_keys);
+
+        let args = Args::try_parse_from(["owl", "--suppress-volume-keys", "true"])?;
+        assert!(args.os_config(false).suppress_volume_keys);
+
+        let mut args = Args::try_parse_from(["owl"])?;
+        args.file = config::File::parse("suppress-volume-keys = false")?;
+        assert!(!args.os_config(true).suppress_volume_keys);
+        Ok(())
+    }
+
+    #[test]
+    fn test_topology_command() -> Result<()> {
+        let args = Args::try_parse_from(["owl", "--hdmi-port", "1", "topology"])?;
+        assert_eq!(args.command, Some(Command::Topology));
+        assert_eq!(args.cec_config().hdmi_port, 1);
+        let args = Args::try_parse_from(["owl", "record", "--out", "session.owl"])?;
+        assert_eq!(
+            args.command,
+            Some(Command::Record {
+                out: "session.owl".into(),
+                filter: None,
+            })
+        );
+        let args = Args::try_parse_from(["owl", "record", "--out", "tv.owl", "--filter", "tv"])?;
+        assert_eq!(
+            args.command,
+            Some(Command::Record {
+                out: "tv.owl".into(),
+                filter: Some(LogicalAddress::Tv),
+            })
+        );
+        let args = Args::try_parse_from(["owl", "send-raw", "40:04"])?;
+        assert_eq!(
+            args.command,
+            Some(Command::SendRaw {
+                frame: Frame(vec![0x40, 0x04]),
+                dry_run: false,
+            })
+        );
+        let args = Args::try_parse_from(["owl", "send-raw", "--dry-run", "4f:82:20:00"])?;
+        assert_eq!(
+            args.command,
+            Some(Command::SendRaw {
+                frame: Frame(vec![0x4F, 0x82, 0x20, 0x00]),
+                dry_run: true,
+            })
+        );
+        assert_eq!(Args::try_parse_from(["owl"])?.command, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_test_event_command() -> Result<()> {
+        let args = Args::try_parse_from(["owl", "test-event", "suspend"])?;
+        assert_eq!(
+            args.command,
+            Some(Command::TestEvent {
+                event: os::Event::Suspend
+            })
+        );
+        let args = Args::try_parse_from(["owl", "test-event", "press:volume-up"])?;
+        assert_eq!(
+            args.command,
+            Some(Command::TestEvent {
+                event: os::Event::Press(os::Key::VolumeUp)
+            })
+        );
+        assert_eq!(
+            parse_event("raw:0x7C"),
+            Ok(os::Event::RawKey(os::KeyCode(0x7C)))
+        );
+        assert_eq!(
+            parse_event("focus:2"),
+            Ok(os::Event::Focus(Some(os::Monitor(2))))
+        );
+        for s in ["hibernate", "press:volume-sideways", "press", "focus:tv"] {
+            assert!(parse_event(s).is_err(), "{s}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_hdmi_port_auto() -> Result<()> {
+        let args = Args::try_parse_from(["owl", "--hdmi-port", "auto"])?;
+        assert_eq!(args.hdmi_port, Some(HdmiPort::Auto));
+        assert!(Args::try_parse_from(["owl", "--hdmi-port", "two"]).is_err());
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_hdmi_port_auto_fallback() -> Result<()> {
+        let mut args = Args::try_parse_from(["owl", "--hdmi-port", "auto"])?;
+        args.file = config::File::parse("hdmi-port = 3")?;
+        let config = args.cec_config();
+        assert_eq!(config.hdmi_port, 3);
+        assert_eq!(config.physical_address, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        assert_eq!(
+            "4f:82:20:00".parse(),
+            Ok(Frame(vec![0x4F, 0x82, 0x20, 0x00]))
+        );
+        assert!("".parse::<Frame>().is_err());
+        assert!("40:zz".parse::<Frame>().is_err());
+        assert!("4004".parse::<Frame>().is_err());
+        assert!("40::04".parse::<Frame>().is_err());
+        assert!("40: 04".parse::<Frame>().is_err());
+        assert_eq!(
+            Frame(vec![0x4F, 0x82, 0x20, 0x00]).to_string(),
+            "4f:82:20:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_control() {
+        assert_eq!(
+            parse_control("input-select"),
+            Ok(UserControlCode::InputSelect)
+        );
+        assert_eq!(parse_control("f1-blue"), Ok(UserControlCode::F1Blue));
+        assert!(parse_control("InputSelect").is_err());
+    }
+
+    #[test]
+    fn test_parse_logical_address() {
+        assert_eq!(parse_logical_address("tv"), Ok(LogicalAddress::Tv));
+        assert_eq!(
+            parse_logical_address("audiosystem"),
+            Ok(LogicalAddress::Audiosystem)
+        );
+        assert!(parse_logical_address("toaster").is_err());
+    }
+
+    #[test]
+    fn test_log_libcec_level() -> Result<()> {
+        let args = Args::try_parse_from(["owl", "--log-libcec-level", "debug"])?;
+        assert_eq!(args.log_libcec_level, Some(LevelFilter::DEBUG));
+        assert!(Args::try_parse_from(["owl", "--log-libcec-level", "loud"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_physical_address() -> Result<()> {
+        let args = Args::try_parse_from(["owl", "--physical-address", "1.2.0.0"])?;
+        assert_eq!(
+            args.cec_config().physical_address,
+            Some(PhysicalAddress(0x1200))
+        );
+        assert!(Args::try_parse_from(["owl", "--physical-address", "1.2"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,614 @@
+//! The optional TOML config file. Every setting is optional, and settings
+//! given on the command line take precedence.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use ::cec::{DeviceKind, LogicalAddress, PhysicalAddress, UserControlCode};
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    cec::{self, Button, Command, PowerOnStep},
+    cli,
+    os::KeyCode,
+    timer::Schedule,
+};
+
+/// Represents the owl config file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct File {
+    #[serde(deserialize_with = "device_kind")]
+    pub device_kind: Option<DeviceKind>,
+    #[serde(deserialize_with = "device_kind")]
+    pub active_source_kind: Option<DeviceKind>,
+    pub adapter: Option<String>,
+    pub hdmi_port: Option<u8>,
+    #[serde(deserialize_with = "from_str")]
+    pub physical_address: Option<PhysicalAddress>,
+    pub restore_volume: Option<bool>,
+    pub on_power_on_volume: Option<u8>,
+    pub unmute_on_focus: Option<bool>,
+    pub system_audio: Option<bool>,
+    pub activate_source: Option<bool>,
+    pub collapse_repeats: Option<bool>,
+    pub transmit_timeout_ms: Option<u64>,
+    #[serde(deserialize_with = "commands")]
+    pub retry_commands: Option<Vec<Command>>,
+    pub retry_attempts: Option<u8>,
+    pub retry_delay_ms: Option<u64>,
+    #[serde(deserialize_with = "buttons")]
+    pub release_buttons: Option<Vec<Button>>,
+    pub history_len: Option<usize>,
+    pub button_debounce_ms: Option<u64>,
+    #[serde(deserialize_with = "power_on_steps")]
+    pub power_on: Option<Vec<PowerOnStep>>,
+    #[serde(deserialize_with = "standby_target")]
+    pub standby_target: Option<LogicalAddress>,
+    pub mute_double_tap_ms: Option<u64>,
+    pub adapter_ping_interval_ms: Option<u64>,
+    pub lazy_active: Option<bool>,
+    pub lazy_active_idle_ms: Option<u64>,
+    pub display_off_delay_ms: Option<u64>,
+    pub heartbeat_interval_ms: Option<u64>,
+    pub suppress_volume_keys: Option<bool>,
+    pub focus_monitors: Option<Vec<u32>>,
+    pub bidirectional_volume: Option<bool>,
+    pub mirror_mute: Option<bool>,
+    pub power_off_on_suspend: Option<bool>,
+    pub power_on_on_resume: Option<bool>,
+    pub pidfile: Option<PathBuf>,
+    pub ipc_socket: Option<PathBuf>,
+    pub single_thread: Option<bool>,
+    pub keymap: Vec<Keymap>,
+    pub timer: Vec<Timer>,
+    pub tv_emulator: TvEmulator,
+}
+
+/// Maps an OS key to a HDMI-CEC keypress, like:
+///
+/// ```toml
+/// [[keymap]]
+/// key = "F13"
+/// control = "input-select"
+/// target = "tv"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keymap {
+    #[serde(deserialize_with = "key_code")]
+    pub key: KeyCode,
+    #[serde(deserialize_with = "control")]
+    pub control: UserControlCode,
+    #[serde(deserialize_with = "target")]
+    pub target: LogicalAddress,
+}
+
+/// Sends a command on a schedule, like:
+///
+/// ```toml
+/// [[timer]]
+/// schedule = "daily-utc 23:30"
+/// command = "power-off"
+/// ```
+///
+/// Daily times are in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Timer {
+    #[serde(deserialize_with = "parse")]
+    pub schedule: Schedule,
+    #[serde(deserialize_with = "command")]
+    pub command: Command,
+}
+
+/// The canned replies of `owl tv-emulator`, like:
+///
+/// ```toml
+/// [tv-emulator]
+/// name = "Test TV"
+/// vendor-id = 0x00E091
+/// standby = false
+/// menu-language = "eng"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TvEmulator {
+    /// The OSD name, printable ASCII truncated to the 14 bytes a frame fits.
+    pub name: String,
+    /// The IEEE OUI reported as the vendor, defaults to LG's.
+    pub vendor_id: u32,
+    /// Whether the TV reports being in standby rather than on.
+    pub standby: bool,
+    /// The ISO 639-2 menu language code.
+    pub menu_language: String,
+}
+
+impl Default for TvEmulator {
+    fn default() -> Self {
+        Self {
+            name: "owl tv".to_owned(),
+            vendor_id: 0x00_E091,
+            standby: false,
+            menu_language: "eng".to_owned(),
+        }
+    }
+}
+
+/// A problem with a config file which parsing doesn't catch, like a value out
+/// of range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// The offending field, like `hdmi-port` or `keymap[1].key`.
+    pub field: String,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", self.field, self.message)
+    }
+}
+
+impl File {
+    /// The longest any `-ms` setting may be, anything longer is likely a
+    /// setting given in seconds by mistake.
+    const MAX_DURATION_MS: u64 = 60 * 60 * 1000;
+
+    /// Reads and parses the config file at `path`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file `{}`", path.display()))?;
+        Self::parse(&s).with_context(|| format!("failed to parse config file `{}`", path.display()))
+    }
+
+    /// Parses a config file.
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Returns the problems with the settings which parse but can't work.
+    #[must_use]
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut problem = |field: &str, message: String| {
+            problems.push(Problem {
+                field: field.to_owned(),
+                message,
+            });
+        };
+
+        if let Some(port) = self.hdmi_port
+            && !::cec::HDMI_PORTS.contains(&port)
+        {
+            problem("hdmi-port", format!("{port} isn't between 1 and 15"));
+        }
+        if let Some(volume) = self.on_power_on_volume
+            && volume > 100
+        {
+            problem(
+                "on-power-on-volume",
+                format!("{volume} isn't between 0 and 100"),
+            );
+        }
+
+        if !cfg!(windows) {
+            if self.bidirectional_volume == Some(true) {
+                problem(
+                    "bidirectional-volume",
+                    "only supported on Windows so far".to_owned(),
+                );
+            }
+            if self.mirror_mute == Some(true) {
+                problem("mirror-mute", "only supported on Windows so far".to_owned());
+            }
+        }
+
+        if self.transmit_timeout_ms == Some(0) {
+            problem("transmit-timeout-ms", "must not be zero".to_owned());
+        }
+        if self.adapter_ping_interval_ms == Some(0) {
+            problem("adapter-ping-interval-ms", "must not be zero".to_owned());
+        }
+        for (field, ms) in [
+            ("transmit-timeout-ms", self.transmit_timeout_ms),
+            ("retry-delay-ms", self.retry_delay_ms),
+            ("button-debounce-ms", self.button_debounce_ms),
+            ("mute-double-tap-ms", self.mute_double_tap_ms),
+            ("adapter-ping-interval-ms", self.adapter_ping_interval_ms),
+            ("lazy-active-idle-ms", self.lazy_active_idle_ms),
+            ("display-off-delay-ms", self.display_off_delay_ms),
+            ("heartbeat-interval-ms", self.heartbeat_interval_ms),
+        ] {
+            if let Some(ms) = ms
+                && ms > Self::MAX_DURATION_MS
+            {
+                problem(
+                    field,
+                    format!("{ms}ms is over the most of {}ms", Self::MAX_DURATION_MS),
+                );
+            }
+        }
+
+        let debounce = self.button_debounce_ms.map_or_else(
+            || cec::Config::default().button_debounce,
+            Duration::from_millis,
+        );
+        if let Some(ms) = self.mute_double_tap_ms
+            && Duration::from_millis(ms) <= debounce
+        {
+            problem(
+                "mute-double-tap-ms",
+                format!(
+                    "must be longer than the {}ms button debounce, which drops the second press",
+                    debounce.as_millis()
+                ),
+            );
+        }
+
+        if self.focus_monitors.as_ref().is_some_and(|x| x.contains(&0)) {
+            problem("focus-monitors", "monitors are numbered from 1".to_owned());
+        }
+
+        let mut mapped = HashMap::new();
+        for (i, keymap) in self.keymap.iter().enumerate() {
+            if let Some(first) = mapped.insert(keymap.key, i) {
+                problem(
+                    &format!("keymap[{i}].key"),
+                    format!("the key is already mapped by `keymap[{first}]`"),
+                );
+            }
+        }
+
+        if let Err(e) = ::cec::to_cec_ascii(&self.tv_emulator.name, ::cec::OSD_NAME_LEN) {
+            problem("tv-emulator.name", e.to_string());
+        }
+
+        if self.tv_emulator.vendor_id > 0xFF_FFFF {
+            problem(
+                "tv-emulator.vendor-id",
+                format!(
+                    "{:#X} is over the three bytes a vendor id fits in",
+                    self.tv_emulator.vendor_id
+                ),
+            );
+        }
+        let language = &self.tv_emulator.menu_language;
+        if language.len() != 3 || !language.bytes().all(|x| x.is_ascii_lowercase()) {
+            problem(
+                "tv-emulator.menu-language",
+                format!("`{language}` isn't a three letter ISO 639-2 code"),
+            );
+        }
+
+        problems
+    }
+}
+
+fn device_kind<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DeviceKind>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| cli::parse_device_kind(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn buttons<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<Button>>, D::Error> {
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|x| {
+            x.iter()
+                .map(|s| cli::parse_button(s).map_err(serde::de::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+fn power_on_steps<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<PowerOnStep>>, D::Error> {
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|x| {
+            x.iter()
+                .map(|s| cli::parse_power_on_step(s).map_err(serde::de::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+fn standby_target<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<LogicalAddress>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| cli::parse_standby_target(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn key_code<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyCode, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+pub(crate) fn control<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<UserControlCode, D::Error> {
+    cli::parse_control(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+pub(crate) fn target<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<LogicalAddress, D::Error> {
+    cli::parse_logical_address(&String::deserialize(deserializer)?)
+        .map_err(serde::de::Error::custom)
+}
+
+fn command<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Command, D::Error> {
+    cli::parse_command(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn commands<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<Command>>, D::Error> {
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|x| {
+            x.iter()
+                .map(|s| cli::parse_command(s).map_err(serde::de::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+fn parse<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_windows_only() -> Result<()> {
+        let file = File::parse(
+            r"
+            bidirectional-volume = true
+            mirror-mute = true
+            ",
+        )?;
+        let fields: Vec<_> = file.problems().into_iter().map(|x| x.field).collect();
+        assert_eq!(fields, ["bidirectional-volume", "mirror-mute"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_problems() -> Result<()> {
+        assert_eq!(File::default().problems(), []);
+
+        let file = File::parse(
+            r#"
+            hdmi-port = 16
+            on-power-on-volume = 101
+            transmit-timeout-ms = 0
+            adapter-ping-interval-ms = 0
+            display-off-delay-ms = 7200000
+            mute-double-tap-ms = 150
+            focus-monitors = [0, 1]
+
+            [[keymap]]
+            key = "124"
+            control = "input-select"
+            target = "tv"
+
+            [[keymap]]
+            key = "0x7C"
+            control = "power"
+            target = "tv"
+
+            [tv-emulator]
+            name = "Télé"
+            vendor-id = 0x1000000
+            menu-language = "english"
+            "#,
+        )?;
+        let fields: Vec<_> = file.problems().into_iter().map(|x| x.field).collect();
+        assert_eq!(
+            fields,
+            [
+                "hdmi-port",
+                "on-power-on-volume",
+                "transmit-timeout-ms",
+                "adapter-ping-interval-ms",
+                "display-off-delay-ms",
+                "mute-double-tap-ms",
+                "focus-monitors",
+                "keymap[1].key",
+                "tv-emulator.name",
+                "tv-emulator.vendor-id",
+                "tv-emulator.menu-language",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let file = File::parse(
+            r#"
+            device-kind = "playback"
+            hdmi-port = 3
+            physical-address = "1.0.0.0"
+            restore-volume = true
+            "#,
+        )?;
+        assert_eq!(
+            file,
+            File {
+                device_kind: Some(DeviceKind::PlaybackDevice),
+                hdmi_port: Some(3),
+                physical_address: Some(PhysicalAddress(0x1000)),
+                restore_volume: Some(true),
+                ..File::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_empty() -> Result<()> {
+        assert_eq!(File::parse("")?, File::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unknown_field() {
+        assert!(File::parse("volume = 11").is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_device_kind() {
+        assert!(File::parse(r#"device-kind = "toaster""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_keymap() -> Result<()> {
+        let file = File::parse(
+            r#"
+            [[keymap]]
+            key = "0x7C"
+            control = "input-select"
+            target = "tv"
+
+            [[keymap]]
+            key = "0x7D"
+            control = "play"
+            target = "playbackdevice1"
+            "#,
+        )?;
+        assert_eq!(
+            file.keymap,
+            vec![
+                Keymap {
+                    key: KeyCode(0x7C),
+                    control: UserControlCode::InputSelect,
+                    target: LogicalAddress::Tv,
+                },
+                Keymap {
+                    key: KeyCode(0x7D),
+                    control: UserControlCode::Play,
+                    target: LogicalAddress::Playbackdevice1,
+                },
+            ]
+        );
+        assert!(File::parse(
+            r#"
+            [[keymap]]
+            key = "0x7C"
+            control = "toast"
+            target = "tv"
+            "#
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tv_emulator() -> Result<()> {
+        let file = File::parse(
+            r#"
+            [tv-emulator]
+            name = "Test TV"
+            standby = true
+            "#,
+        )?;
+        assert_eq!(
+            file.tv_emulator,
+            TvEmulator {
+                name: "Test TV".to_owned(),
+                standby: true,
+                ..TvEmulator::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_timer() -> Result<()> {
+        let file = File::parse(
+            r#"
+            [[timer]]
+            schedule = "daily-utc 23:30"
+            command = "power-off"
+            "#,
+        )?;
+        assert_eq!(
+            file.timer,
+            vec![Timer {
+                schedule: Schedule::Daily {
+                    hour: 23,
+                    minute: 30
+                },
+                command: Command::PowerOff,
+            }]
+        );
+        assert!(File::parse(
+            r#"
+            [[timer]]
+            schedule = "daily-utc 25:00"
+            command = "power-off"
+            "#
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_power_on() -> Result<()> {
+        let file = File::parse(r#"power-on = ["image-view-on", "active-source"]"#)?;
+        assert_eq!(
+            file.power_on,
+            Some(vec![PowerOnStep::ImageViewOn, PowerOnStep::ActiveSource])
+        );
+        assert!(File::parse(r#"power-on = ["jump"]"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_standby_target() -> Result<()> {
+        let file = File::parse(r#"standby-target = "all""#)?;
+        assert_eq!(file.standby_target, Some(LogicalAddress::BROADCAST));
+        assert!(File::parse(r#"standby-target = "neighbours""#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_release_buttons() -> Result<()> {
+        let file = File::parse(r#"release-buttons = ["volume-up", "volume-mute"]"#)?;
+        assert_eq!(
+            file.release_buttons,
+            Some(vec![Button::VolumeUp, Button::VolumeMute])
+        );
+        assert!(File::parse(r#"release-buttons = ["power"]"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Emulates a TV on the HDMI-CEC bus, replying to queries with canned
+//! responses, so other HDMI-CEC software can be tested without a real TV.
+//! libcec answers queries like the OSD name itself, see [`cec::connect_tv`].
+
+use ::cec::{Cmd, LogicalAddress, Opcode};
+use color_eyre::eyre::Result;
+use tokio::{signal, sync::mpsc};
+use tracing::{debug, info, warn};
+
+use crate::{cec, config::TvEmulator};
+
+/// Returns the reply to a cmd sent to the TV, `None` if it isn't a query the
+/// emulator answers, including those libcec already answers.
+#[must_use]
+pub fn reply(cmd: &Cmd, tv: &TvEmulator) -> Option<Cmd> {
+    if cmd.destination != LogicalAddress::Tv || cmd.initiator == LogicalAddress::Tv {
+        return None;
+    }
+
+    let to_initiator = |opcode, parameters: &[u8]| {
+        Some(Cmd::new(
+            LogicalAddress::Tv,
+            cmd.initiator,
+            opcode,
+            parameters,
+        ))
+    };
+    let broadcast = |opcode, parameters: &[u8]| {
+        Some(Cmd::new(
+            LogicalAddress::Tv,
+            LogicalAddress::BROADCAST,
+            opcode,
+            parameters,
+        ))
+    };
+
+    match cmd.opcode {
+        Opcode::GiveDevicePowerStatus => {
+            to_initiator(Opcode::ReportPowerStatus, &[u8::from(tv.standby)])
+        }
+        Opcode::GetMenuLanguage => broadcast(Opcode::SetMenuLanguage, tv.menu_language.as_bytes()),
+        _ => None,
+    }
+}
+
+/// Registers as the TV and replies to queries until Ctrl+C.
+pub async fn run(config: &cec::Config, tv: &TvEmulator) -> Result<()> {
+    let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel();
+    let connection = cec::connect_tv(config, tv, cmd_tx)?;
+    info!(
+        "emulating a tv named {:?}, press Ctrl+C to stop...",
+        tv.name
+    );
+
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        {
+            tokio::select! {
+                cmd = cmd_rx.recv() => {
+                    let Some(cmd) = cmd else { break };
+                    let Some(reply) = reply(&cmd, tv) else { continue };
+                    debug!("replying to {:?} with {:?}", cmd.opcode, reply.opcode);
+                    if let Err(e) = connection.transmit(reply) {
+                        warn!("{e}");
+                    }
+                },
+                _ = signal::ctrl_c() => {
+                    debug!("received CTRL+C");
+                    break;
+                },
+            }
+        }
+    }
+
+    info!("tv emulator stopped!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(opcode: Opcode) -> Cmd {
+        Cmd::new(
+            LogicalAddress::Playbackdevice1,
+            LogicalAddress::Tv,
+            opcode,
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_power_status() {
+        let reply = reply(
+            &query(Opcode::GiveDevicePowerStatus),
+            &TvEmulator::default(),
+        );
+        assert_eq!(reply.map(|x| x.to_frame()), Some(vec![0x04, 0x90, 0x00]));
+
+        let standby = TvEmulator {
+            standby: true,
+            ..TvEmulator::default()
+        };
+        let reply = super::reply(&query(Opcode::GiveDevicePowerStatus), &standby);
+        assert_eq!(reply.map(|x| x.to_frame()), Some(vec![0x04, 0x90, 0x01]));
+    }
+
+    #[test]
+    fn test_menu_language() {
+        let reply = reply(&query(Opcode::GetMenuLanguage), &TvEmulator::default());
+        assert_eq!(
+            reply.map(|x| x.to_frame()),
+            Some(vec![0x0F, 0x32, b'e', b'n', b'g'])
+        );
+    }
+
+    #[test]
+    fn test_ignored() {
+        let tv = TvEmulator::default();
+        assert!(reply(&query(Opcode::Standby), &tv).is_none());
+        // libcec answers these itself.
+        for opcode in [
+            Opcode::GiveOsdName,
+            Opcode::GivePhysicalAddress,
+            Opcode::GetCecVersion,
+            Opcode::GiveDeviceVendorId,
+        ] {
+            assert!(reply(&query(opcode), &tv).is_none(), "{opcode:?}");
+        }
+
+        let mut other = query(Opcode::GiveOsdName);
+        other.destination = LogicalAddress::Audiosystem;
+        assert!(reply(&other, &tv).is_none());
+    }
+}
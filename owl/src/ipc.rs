@@ -0,0 +1,310 @@
+//! A local control socket, letting other processes send owl HDMI-CEC commands
+//! without going through the OS, like
+//! `echo '{"PowerOff":null}' | nc -U /run/owl.sock`.
+//!
+//! On Unix it's a Unix domain socket, on Windows a named pipe, like
+//! `\\.\pipe\owl`. Each line a client sends is a JSON [`Command`], answered
+//! with `ok` or why it failed. Clients are served concurrently. Only the user
+//! running owl may connect: the socket is created with mode `0600`, and a
+//! named pipe's default security only lets its owner and administrators write
+//! to it.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    runtime,
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+use crate::{
+    cec::{Command, CommandTx},
+    job::{self, SpawnResult},
+    Spawn,
+};
+
+/// Represents the IPC job, relaying commands from local clients.
+pub struct Job;
+
+/// Represents the IPC job configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The socket's path, or on Windows the pipe's name.
+    pub path: PathBuf,
+    /// Where the commands are sent, see [`crate::cec::Job::command_tx`].
+    pub cmd_tx: CommandTx,
+}
+
+impl Spawn for Job {
+    type Config = Config;
+
+    /// Spawns a new IPC job. The job runs on a thread, serving each client on
+    /// its own task until the run token is cancelled.
+    async fn spawn(run_token: CancellationToken, config: Config) -> SpawnResult<Self> {
+        let Config { path, cmd_tx } = config;
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .context("failed to build ipc runtime")?;
+        // Binding up front fails owl's start on a bad path, rather than later.
+        let listener = {
+            let _guard = runtime.enter();
+            Listener::bind(&path)?
+        };
+        info!("listening for commands on `{}`", path.display());
+
+        debug!("spawning ipc job...");
+        let handle = job::spawn_thread("owl-ipc", move || {
+            runtime.block_on(serve(listener, cmd_tx, run_token));
+            debug!("stopping ipc job...");
+            Ok(())
+        })?;
+
+        Ok((handle, Self))
+    }
+}
+
+/// Accepts clients until the run token is cancelled, then waits for the
+/// connected ones to finish.
+async fn serve(mut listener: Listener, cmd_tx: CommandTx, run_token: CancellationToken) {
+    let mut clients = JoinSet::new();
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        let client = tokio::select! {
+            biased;
+            () = run_token.cancelled() => break,
+            client = listener.accept() => client,
+            // Reaps finished clients, so the set doesn't grow unbounded.
+            Some(_) = clients.join_next() => continue,
+        };
+
+        match client {
+            Ok(client) => {
+                debug!("ipc client connected");
+                let cmd_tx = cmd_tx.clone();
+                let run_token = run_token.clone();
+                clients.spawn(async move {
+                    if let Err(e) = serve_client(client, &cmd_tx, &run_token).await {
+                        debug!("ipc client dropped: {e:#}");
+                    }
+                });
+            }
+            Err(e) => error!("{e:#}"),
+        }
+    }
+
+    while clients.join_next().await.is_some() {}
+}
+
+/// Sends each command a client writes, until it disconnects.
+async fn serve_client<S>(client: S, cmd_tx: &CommandTx, run_token: &CancellationToken) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = io::split(client);
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        let line = tokio::select! {
+            biased;
+            () = run_token.cancelled() => return Ok(()),
+            line = lines.next_line() => line.context("failed to read from ipc client")?,
+        };
+        let Some(line) = line else { return Ok(()) };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match send_line(&line, cmd_tx).await {
+            Ok(()) => "ok".to_owned(),
+            Err(e) => format!("error: {e:#}"),
+        };
+        writer
+            .write_all(format!("{reply}\n").as_bytes())
+            .await
+            .context("failed to reply to ipc client")?;
+    }
+}
+
+async fn send_line(line: &str, cmd_tx: &CommandTx) -> Result<()> {
+    let cmd = parse_line(line)?;
+    info!("ipc client sent {cmd:?}");
+    cmd_tx
+        .send((cmd, None))
+        .await
+        .context("failed to send ipc command")
+}
+
+fn parse_line(line: &str) -> Result<Command> {
+    serde_json::from_str(line).with_context(|| format!("invalid command `{}`", line.trim()))
+}
+
+#[cfg(unix)]
+struct Listener {
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Listener {
+    /// Binds the socket at `path`, replacing one left over from a crash.
+    fn bind(path: &Path) -> Result<Self> {
+        use std::{fs, os::unix::fs::FileTypeExt};
+
+        if fs::symlink_metadata(path).is_ok_and(|x| x.file_type().is_socket()) {
+            fs::remove_file(path)
+                .with_context(|| format!("failed to remove stale socket `{}`", path.display()))?;
+        }
+        // The socket is created with mode `0600` rather than restricted after
+        // binding, which would leave a window for other users to connect.
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = tokio::net::UnixListener::bind(path);
+        unsafe { libc::umask(umask) };
+        let listener =
+            listener.with_context(|| format!("failed to bind socket `{}`", path.display()))?;
+
+        Ok(Self {
+            listener,
+            path: path.to_owned(),
+        })
+    }
+
+    async fn accept(&mut self) -> Result<tokio::net::UnixStream> {
+        let (stream, _) = self
+            .listener
+            .accept()
+            .await
+            .context("failed to accept ipc client")?;
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            error!("failed to remove socket `{}`: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(windows)]
+struct Listener {
+    server: tokio::net::windows::named_pipe::NamedPipeServer,
+    name: PathBuf,
+}
+
+#[cfg(windows)]
+impl Listener {
+    /// Creates the pipe `name`, failing if another process already has.
+    fn bind(name: &Path) -> Result<Self> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(name)
+            .with_context(|| format!("failed to create pipe `{}`", name.display()))?;
+
+        Ok(Self {
+            server,
+            name: name.to_owned(),
+        })
+    }
+
+    /// Waits for a client, then creates the next pipe instance for the one
+    /// after.
+    async fn accept(&mut self) -> Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        self.server
+            .connect()
+            .await
+            .context("failed to accept ipc client")?;
+        let next = ServerOptions::new()
+            .create(&self.name)
+            .with_context(|| format!("failed to create pipe `{}`", self.name.display()))?;
+        Ok(std::mem::replace(&mut self.server, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::cec::{LogicalAddress, UserControlCode};
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::cec::Button;
+
+    #[test]
+    fn test_parse_line() -> Result<()> {
+        assert_eq!(parse_line(r#"{"PowerOff":null}"#)?, Command::PowerOff);
+        assert_eq!(parse_line(r#" "Focus" "#)?, Command::Focus);
+        assert_eq!(
+            parse_line(r#"{"Press":"VolumeUp"}"#)?,
+            Command::Press(Button::VolumeUp)
+        );
+        assert_eq!(parse_line(r#"{"SetMute":true}"#)?, Command::SetMute(true));
+        assert_eq!(
+            parse_line(r#"{"Keypress":["tv","input-select"]}"#)?,
+            Command::Keypress(LogicalAddress::Tv, UserControlCode::InputSelect)
+        );
+
+        for line in [
+            "",
+            "PowerOff",
+            r#"{"Toast":null}"#,
+            r#"{"Keypress":["tv","x"]}"#,
+        ] {
+            assert!(parse_line(line).is_err(), "{line}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_serve_client() -> Result<()> {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(8);
+        let (client, server) = io::duplex(256);
+        let serve =
+            tokio::spawn(
+                async move { serve_client(server, &cmd_tx, &CancellationToken::new()).await },
+            );
+
+        let (reader, mut writer) = io::split(client);
+        writer
+            .write_all(b"{\"PowerOn\":null}\n\ntoast\n\"Focus\"\n")
+            .await?;
+        let mut replies = BufReader::new(reader).lines();
+        assert_eq!(replies.next_line().await?.as_deref(), Some("ok"));
+        assert!(replies
+            .next_line()
+            .await?
+            .is_some_and(|x| x.starts_with("error: invalid command `toast`")));
+        assert_eq!(replies.next_line().await?.as_deref(), Some("ok"));
+
+        // Hanging up ends the client.
+        drop((writer, replies));
+        serve.await??;
+        assert_eq!(cmd_rx.recv().await, Some((Command::PowerOn, None)));
+        assert_eq!(cmd_rx.recv().await, Some((Command::Focus, None)));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_mode() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("owl-test-{}.sock", std::process::id()));
+        let listener = Listener::bind(&path)?;
+        let mode = std::fs::metadata(&path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // The socket is removed once the listener is dropped.
+        drop(listener);
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -1,17 +1,32 @@
+//! owl's jobs, each talking to one side of the integration.
+//!
+//! Jobs which hook into the OS, like the Windows keyboard hook, get called
+//! back on their own thread, outside the async runtime. Hooks must relay
+//! values with non-blocking sends and never `block_on` a send: the OS waits
+//! on the hook, so blocking stalls input system-wide, and `block_on` deadlocks
+//! if the hook is ever called on a runtime thread.
+
 pub type SpawnResult<T> = Result<(JoinHandle<Result<()>>, T)>;
 
-use std::thread::JoinHandle;
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
 
-use color_eyre::{eyre::eyre, Result};
+use color_eyre::{eyre::Context, Report, Result};
 use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 
 #[allow(async_fn_in_trait)]
 pub trait Spawn {
+    /// The job's configuration.
+    type Config;
+
     /// Spawns a new owl job. Depending on the implementation the job may use
     /// tasks or threads.
-    async fn spawn(run_token: CancellationToken) -> SpawnResult<Self>
+    async fn spawn(run_token: CancellationToken, config: Self::Config) -> SpawnResult<Self>
     where
         Self: Sized;
 }
@@ -28,6 +43,29 @@ pub trait Send<T> {
     async fn send(&self, value: T) -> Result<()>;
 }
 
+/// Asserts, in debug builds, that the caller is an OS hook outside the async
+/// runtime, see the [module docs](self).
+pub fn debug_assert_hook_context() {
+    debug_assert!(
+        tokio::runtime::Handle::try_current().is_err(),
+        "os hooks must not run on the async runtime"
+    );
+}
+
+/// Spawns a job's thread named `name`, like `owl-cec`, so it can be told apart
+/// in a debugger or `top`.
+pub fn spawn_thread<F>(name: &str, func: F) -> Result<JoinHandle<Result<()>>>
+where
+    F: FnOnce() -> Result<()> + std::marker::Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(func)
+        .with_context(|| format!("failed to spawn thread `{name}`"))
+}
+
+/// Runs `func`, sending whether it succeeded to the task waiting for the job to
+/// start. On failure, both the job and the waiting task get the real error.
 pub fn send_ready_status<T, F>(ready_tx: oneshot::Sender<Result<()>>, func: F) -> Result<T>
 where
     T: std::fmt::Debug,
@@ -35,8 +73,10 @@ where
 {
     let (result, status) = match func() {
         Ok(x) => (Ok(x), Ok(())),
-        // This is only used to kill the job early, so the message doesn't matter.
-        Err(e) => (Err(eyre!("func failed")), Err(e)),
+        Err(e) => {
+            let e = StartError(Arc::new(e));
+            (Err(Report::new(e.clone())), Err(Report::new(e)))
+        }
     };
 
     if let Err(e) = ready_tx.send(status) {
@@ -45,3 +85,52 @@ where
 
     result
 }
+
+/// An error starting a job, shared between the job and the task waiting for
+/// it to start.
+#[derive(Debug, Clone)]
+struct StartError(Arc<Report>);
+
+impl Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for StartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::{eyre, WrapErr};
+
+    use super::*;
+
+    #[test]
+    fn test_error_survives() {
+        let (ready_tx, mut ready_rx) = oneshot::channel();
+        let result = send_ready_status::<(), _>(ready_tx, || {
+            Err(eyre!("adapter not found")).wrap_err("failed to connect")
+        });
+
+        let status = ready_rx.try_recv().expect("status wasn't sent");
+        for e in [
+            result.expect_err("job should fail"),
+            status.expect_err("status should fail"),
+        ] {
+            assert_eq!(format!("{e:#}"), "failed to connect: adapter not found");
+        }
+    }
+
+    #[test]
+    fn test_thread_name() -> Result<()> {
+        let handle = spawn_thread("owl-test", || {
+            assert_eq!(thread::current().name(), Some("owl-test"));
+            Ok(())
+        })?;
+        handle.join().expect("thread panicked")
+    }
+}
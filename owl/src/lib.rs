@@ -6,8 +6,24 @@
 )]
 
 pub mod cec;
+pub mod channel;
+pub mod cli;
+pub mod config;
+#[cfg(feature = "tv-emulator")]
+pub mod emulator;
+pub mod ipc;
 pub mod job;
+pub mod lock;
+pub mod metrics;
+pub mod notify;
 pub mod os;
+pub mod pipeline;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod timer;
+pub mod topology;
+pub mod trace;
+pub mod translate;
 pub mod prelude {
     pub use crate::job::{Recv, Send, Spawn};
 }
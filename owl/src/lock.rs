@@ -0,0 +1,172 @@
+//! Keeps owl to a single instance, since a second one would fight the first
+//! over the adapter and fail with a cryptic port busy error.
+//!
+//! On Unix the lock is an `flock`'d pidfile, on Windows a named mutex, with the
+//! pidfile only written for other tools to read.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use tracing::{debug, warn};
+
+#[cfg(windows)]
+mod win32 {
+    pub use windows::{
+        core::{Error, HSTRING},
+        Win32::{
+            Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, FALSE, HANDLE},
+            System::Threading::CreateMutexW,
+        },
+    };
+}
+
+/// The named mutex held by the running instance, shared by every session.
+#[cfg(windows)]
+const MUTEX_NAME: &str = r"Global\owl";
+
+/// Held for as long as owl runs, releasing the lock and removing the pidfile
+/// when dropped.
+#[derive(Debug)]
+pub struct Lock {
+    pidfile: Option<PathBuf>,
+    #[cfg(unix)]
+    _file: fs::File,
+    #[cfg(windows)]
+    mutex: win32::HANDLE,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(
+        "owl is already running{}, stop it first",
+        .pid.map_or_else(String::new, |x| format!(" as pid {x}"))
+    )]
+    AlreadyRunning { pid: Option<u32> },
+    #[error("failed to lock pidfile `{}`", .0.display())]
+    PidfileFailed(PathBuf, #[source] io::Error),
+    #[cfg(windows)]
+    #[error("failed to create instance mutex")]
+    MutexFailed(#[source] win32::Error),
+}
+
+/// Locks `pidfile`, defaulting to `owl.pid` in the runtime directory, then
+/// writes owl's pid to it.
+#[cfg(unix)]
+pub fn acquire(pidfile: Option<&Path>) -> Result<Lock, Error> {
+    use std::{io::Write, os::fd::AsRawFd};
+
+    let path = pidfile.map_or_else(default_pidfile, Path::to_owned);
+    let failed = |e| Error::PidfileFailed(path.clone(), e);
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(failed)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() == io::ErrorKind::WouldBlock {
+            return Err(Error::AlreadyRunning {
+                pid: read_pid(&path),
+            });
+        }
+        return Err(failed(e));
+    }
+
+    // Truncating only once locked keeps the running instance's pid readable.
+    file.set_len(0).map_err(failed)?;
+    writeln!(file, "{}", process::id()).map_err(failed)?;
+    debug!("locked pidfile `{}`", path.display());
+
+    Ok(Lock {
+        pidfile: Some(path),
+        _file: file,
+    })
+}
+
+/// Creates the instance mutex, then writes owl's pid to `pidfile` if given.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-createmutexw>
+#[cfg(windows)]
+pub fn acquire(pidfile: Option<&Path>) -> Result<Lock, Error> {
+    let mutex =
+        unsafe { win32::CreateMutexW(None, win32::FALSE, &win32::HSTRING::from(MUTEX_NAME)) }
+            .map_err(Error::MutexFailed)?;
+    // The mutex is opened rather than created if another instance holds it.
+    if unsafe { win32::GetLastError() } == win32::ERROR_ALREADY_EXISTS {
+        if let Err(e) = unsafe { win32::CloseHandle(mutex) } {
+            warn!("failed to close instance mutex: {e}");
+        }
+        return Err(Error::AlreadyRunning {
+            pid: pidfile.and_then(read_pid),
+        });
+    }
+
+    let lock = Lock {
+        pidfile: pidfile.map(Path::to_owned),
+        mutex,
+    };
+    if let Some(path) = pidfile {
+        fs::write(path, format!("{}\n", process::id()))
+            .map_err(|e| Error::PidfileFailed(path.to_owned(), e))?;
+    }
+    debug!("created instance mutex `{MUTEX_NAME}`");
+
+    Ok(lock)
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Removed while still locked, so a starting instance's pidfile isn't.
+        if let Some(path) = &self.pidfile
+            && let Err(e) = fs::remove_file(path)
+        {
+            warn!("failed to remove pidfile `{}`: {e}", path.display());
+        }
+
+        #[cfg(windows)]
+        if let Err(e) = unsafe { win32::CloseHandle(self.mutex) } {
+            warn!("failed to close instance mutex: {e}");
+        }
+    }
+}
+
+/// Returns `$XDG_RUNTIME_DIR/owl.pid`, or `owl.pid` in the temporary directory
+/// without one.
+#[cfg(unix)]
+fn default_pidfile() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map_or_else(std::env::temp_dir, PathBuf::from)
+        .join("owl.pid")
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_instance() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("owl-test-{}.pid", process::id()));
+        let lock = acquire(Some(&path))?;
+        assert_eq!(read_pid(&path), Some(process::id()));
+
+        let Err(Error::AlreadyRunning { pid }) = acquire(Some(&path)) else {
+            panic!("expected the second lock to fail");
+        };
+        assert_eq!(pid, Some(process::id()));
+
+        drop(lock);
+        assert!(!path.exists());
+        drop(acquire(Some(&path))?);
+        Ok(())
+    }
+}
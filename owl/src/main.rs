@@ -1,26 +1,191 @@
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
 use color_eyre::eyre::{eyre, Context, Result};
-use owl::{cec, os, Recv, Send, Spawn};
-use tokio::signal;
+use owl::{
+    cec,
+    cli::{Args, Command, Frame},
+    ipc, lock, os, pipeline, timer,
+    topology::Topology,
+    trace, Recv, Send, Spawn,
+};
+use tokio::{runtime, signal};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    init_tracing()?;
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    if args.config_check {
+        return Ok(check_config(&args));
+    }
+    let args = args.reload()?;
+    init_tracing(&args)?;
     color_eyre::install()?;
+    // Only one instance can hold the adapter, the commands which don't open it
+    // can run alongside owl.
+    let (_lock, args) = if args.connects() {
+        (Some(lock::acquire(args.pidfile())?), args.choose_adapter()?)
+    } else {
+        (None, args)
+    };
+
+    // The jobs run on their own threads, so the async side only relays
+    // between them and gets by on a single thread.
+    let runtime = if args.single_thread() {
+        runtime::Builder::new_current_thread()
+    } else {
+        runtime::Builder::new_multi_thread()
+    }
+    .enable_all()
+    .build()
+    .context("failed to build async runtime")?;
+
+    // Only owl itself hooks into the OS, the other commands just use the bus.
+    if args.command.is_none() {
+        if let Err(e) = os::preflight() {
+            error!("{e}");
+            return Ok(ExitCode::from(e.exit_code()));
+        }
+    }
+
+    runtime.block_on(run(args))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs the command given on the command line, by default owl itself.
+async fn run(args: Args) -> Result<()> {
+    match &args.command {
+        Some(Command::Topology) => {
+            let devices = cec::scan(&args.cec_config())?;
+            print!("{}", Topology::new(devices));
+            return Ok(());
+        }
+        Some(Command::Record { out, filter }) => {
+            trace::record(&args.cec_config(), out, *filter).await?;
+            return Ok(());
+        }
+        Some(Command::SendRaw {
+            frame,
+            dry_run: true,
+        }) => {
+            println!("would send {}", Frame(cec::dry_run_raw(&frame.0)?));
+            return Ok(());
+        }
+        Some(Command::SendRaw { frame, .. }) => {
+            cec::send_raw(&args.cec_config(), &frame.0)?;
+            info!("sent frame");
+            return Ok(());
+        }
+        Some(Command::TestEvent { event }) => return test_event(&args, *event).await,
+        Some(Command::ListKeys) => {
+            os::list_keys()?;
+            return Ok(());
+        }
+        #[cfg(feature = "tv-emulator")]
+        Some(Command::TvEmulator) => {
+            owl::emulator::run(&args.cec_config(), &args.file.tv_emulator).await?;
+            return Ok(());
+        }
+        // The prompt blocks on stdin, so it's kept off the runtime's threads.
+        #[cfg(feature = "repl")]
+        Some(Command::Repl) => {
+            let config = args.cec_config();
+            return tokio::task::spawn_blocking(move || owl::repl::run(&config))
+                .await
+                .context("repl panicked")?;
+        }
+        None => {}
+    }
+
+    #[cfg(feature = "notifications")]
+    if args.notify {
+        owl::notify::enable();
+    }
+
+    if let Some(path) = &args.trace_pipeline {
+        pipeline::enable(path)?;
+    }
+
+    if !cfg!(windows) {
+        if args.bidirectional_volume() {
+            return Err(eyre!(
+                "`bidirectional-volume` is only supported on Windows so far"
+            ));
+        }
+        if args.mirror_mute() {
+            warn!("`mirror-mute` is only supported on Windows so far, ignoring it");
+        }
+    }
 
     info!("starting owl...");
     let run_token = CancellationToken::new();
-    let (cec_handle, cec) = cec::Job::spawn(run_token.clone()).await?;
-    let (os_handle, mut os) = os::Job::spawn(run_token.clone()).await?;
+    let (cec_handle, mut cec) = cec::Job::spawn(run_token.clone(), args.cec_config()).await?;
+    if args.suppress_volume_keys().is_none() && !cec.has_audio_system() {
+        info!("no audio system found, not suppressing volume keys");
+    }
+    let (os_handle, mut os) =
+        os::Job::spawn(run_token.clone(), args.os_config(cec.has_audio_system())).await?;
+    #[cfg(feature = "metrics")]
+    let (metrics_handle, _) =
+        owl::metrics::Job::spawn(run_token.clone(), args.metrics_config()).await?;
+    let (timer_handle, _) =
+        timer::Job::spawn(run_token.clone(), args.timer_config(cec.command_tx())).await?;
+    let ipc_handle = match args.ipc_config(cec.command_tx()) {
+        Some(config) => Some(ipc::Job::spawn(run_token.clone(), config).await?.0),
+        None => None,
+    };
 
+    let mut translator = args.translator();
+    let mut hangup = Hangup::new()?;
+    let owl_token = run_token.clone();
     let owl_handle = tokio::spawn(async move {
+        let mut args = args;
         loop {
             let result: Result<()> = async {
-                let event = os.recv().await.context("failed to receive os event")?;
-                cec.send(event.into())
-                    .await
-                    .context("failed to send cec event")?;
+                #[allow(clippy::redundant_pub_crate)]
+                {
+                    tokio::select! {
+                        event = os.recv() => {
+                            let event = event.context("failed to receive os event")?;
+                            let id = pipeline::received(event);
+                            let cmds = translator.translate(event);
+                            pipeline::translated(id, &cmds);
+                            cec.send_all(cmds, id, &owl_token)
+                                .await
+                                .context("failed to send cec event")?;
+                        },
+                        event = cec.recv() => {
+                            // Only fails once the cec job exited, every later
+                            // receive would fail straight away too.
+                            let event = event
+                                .inspect_err(|_| owl_token.cancel())
+                                .context("failed to receive remote event")?;
+                            debug!("received remote event: {event:?}");
+                            // Only Windows can press keys on the PC, a reload
+                            // turning it on elsewhere is ignored.
+                            if cfg!(windows) && args.bidirectional_volume() {
+                                forward_remote_event(event)?;
+                            }
+                        },
+                        () = hangup.recv() => {
+                            info!("received SIGHUP, reloading config...");
+                            let reloaded = args.clone().reload()?;
+                            let has_audio_system = cec.has_audio_system();
+                            if reloaded.os_config(has_audio_system)
+                                != args.os_config(has_audio_system)
+                            {
+                                warn!("changing the os config requires a restart");
+                            }
+                            cec.reconfigure(reloaded.cec_config())?;
+                            translator = reloaded.translator();
+                            args = reloaded;
+                            info!("config reloaded!");
+                        },
+                    }
+                }
                 Result::Ok(())
             }
             .await;
@@ -31,9 +196,14 @@ async fn main() -> Result<()> {
                     error!("owl error: {e:?}");
                 }
             }
+            if owl_token.is_cancelled() {
+                break;
+            }
         }
     });
 
+    // Each job only spawns once it's live, the cec job connected to the
+    // adapter and the os job's hooks dispatching events.
     info!("owl ready!");
 
     #[allow(clippy::ignored_unit_patterns, clippy::redundant_pub_crate)]
@@ -49,25 +219,146 @@ async fn main() -> Result<()> {
     }
 
     info!("stopping owl...");
+    // The timer and ipc jobs hold command senders, join them first so the cec
+    // job sees the channel close.
+    timer_handle
+        .join()
+        .map_err(|e| eyre!("failed to join timer job: {e:?}"))??;
+    if let Some(handle) = ipc_handle {
+        handle
+            .join()
+            .map_err(|e| eyre!("failed to join ipc job: {e:?}"))??;
+    }
     cec_handle
         .join()
         .map_err(|e| eyre!("failed to join cec job: {e:?}"))??;
     os_handle
         .join()
         .map_err(|e| eyre!("failed to join os job: {e:?}"))??;
+    #[cfg(feature = "metrics")]
+    metrics_handle
+        .join()
+        .map_err(|e| eyre!("failed to join metrics job: {e:?}"))??;
 
     info!("owl stopped!");
     Ok(())
 }
 
-fn init_tracing() -> Result<()> {
+/// Sends the commands `event` translates into through a fresh HDMI-CEC job,
+/// as if the OS job had reported it, and prints how each went.
+async fn test_event(args: &Args, event: os::Event) -> Result<()> {
+    const DISPATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+    let cmds = args.translator().translate(event);
+    if cmds.is_empty() {
+        println!("{event:?} translates into no commands");
+        return Ok(());
+    }
+
+    let run_token = CancellationToken::new();
+    let (cec_handle, cec) = cec::Job::spawn(run_token.clone(), args.cec_config()).await?;
+    for cmd in &cmds {
+        cec.send(*cmd).await.context("failed to send cec command")?;
+    }
+
+    // The job dispatches commands in order, recording each in its history.
+    let deadline = Instant::now() + DISPATCH_TIMEOUT;
+    while cec.history().len() < cmds.len() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let history = cec.history();
+    run_token.cancel();
+    drop(cec);
+    cec_handle
+        .join()
+        .map_err(|e| eyre!("failed to join cec job: {e:?}"))??;
+
+    println!("{event:?} translates into:");
+    let mut failed = 0;
+    for (i, cmd) in cmds.iter().enumerate() {
+        if let Some(sent) = history.get(i) {
+            println!("    {sent}");
+            failed += usize::from(sent.error.is_some());
+        } else {
+            println!("    {cmd:?}: not sent");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(eyre!(
+            "failed to deliver {failed} of {} commands",
+            cmds.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Prints the config file's problems, exiting non-zero if there are any.
+fn check_config(args: &Args) -> ExitCode {
+    let problems = args.check_config();
+    if problems.is_empty() {
+        println!("config ok");
+        return ExitCode::SUCCESS;
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    ExitCode::FAILURE
+}
+
+/// Waits for `SIGHUP`, which asks owl to reload its config file. Never fires
+/// on platforms without it.
+struct Hangup {
+    #[cfg(unix)]
+    signal: signal::unix::Signal,
+}
+
+impl Hangup {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: signal::unix::signal(signal::unix::SignalKind::hangup())
+                .context("failed to listen for SIGHUP")?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.signal.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Presses the volume keys from the TV remote on the PC.
+fn forward_remote_event(event: os::Event) -> Result<()> {
+    let (key, pressed) = match event {
+        os::Event::Press(key) => (key, true),
+        os::Event::Release(key) => (key, false),
+        _ => return Ok(()),
+    };
+
+    os::send_key(key, pressed).context("failed to forward remote key to the pc")
+}
+
+/// libcec logs to its own target, kept quieter than owl by default since the
+/// bus traffic is very chatty. A filter given on the command line takes
+/// precedence over `RUST_LOG`.
+fn init_tracing(args: &Args) -> Result<()> {
     use tracing_error::ErrorLayer;
     use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
     let fmt_layer = fmt::layer();
-    let filter_layer =
-        EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("owl=trace"))?;
-    // .or_else(|_| EnvFilter::try_new("owl=trace,owl::os::windows=debug"))?;
+    let mut filter_layer = match args.log_filter() {
+        Some(filter) => EnvFilter::try_new(filter)?,
+        None => EnvFilter::try_from_default_env()
+            .or_else(|_| EnvFilter::try_new("owl=info,libcec=warn"))?,
+    };
+    if let Some(level) = args.log_libcec_level {
+        filter_layer = filter_layer.add_directive(format!("libcec={level}").parse()?);
+    }
 
     tracing_subscriber::registry()
         .with(filter_layer)
@@ -0,0 +1,222 @@
+//! Prometheus metrics, giving visibility into how often owl touches the
+//! HDMI-CEC bus. The recording functions are no-ops unless the `metrics`
+//! feature is enabled.
+
+#[cfg(feature = "metrics")]
+pub use self::exporter::{Config, Job};
+use crate::cec::Command;
+
+/// Records a command sent to the HDMI-CEC bus.
+#[allow(unused_variables)]
+pub fn command_sent(cmd: Command) {
+    #[cfg(feature = "metrics")]
+    {
+        let label = format!("{cmd:?}");
+        exporter::METRICS
+            .commands_sent
+            .with_label_values(&[label.as_str()])
+            .inc();
+
+        match cmd {
+            Command::PowerOn => exporter::METRICS.power_state.set(1),
+            Command::PowerOff => exporter::METRICS.power_state.set(0),
+            _ => {}
+        }
+    }
+}
+
+/// Records a command dropped by debouncing.
+pub fn command_debounced() {
+    #[cfg(feature = "metrics")]
+    exporter::METRICS.commands_debounced.inc();
+}
+
+/// Records an active source broadcast the TV didn't acknowledge.
+pub fn active_source_unacknowledged() {
+    #[cfg(feature = "metrics")]
+    exporter::METRICS.active_source_unacknowledged.inc();
+}
+
+/// Records a reconnection to the HDMI-CEC adapter, counted each time the cec
+/// job reopens it after losing it.
+pub fn cec_reconnected() {
+    #[cfg(feature = "metrics")]
+    exporter::METRICS.cec_reconnects.inc();
+}
+
+#[cfg(feature = "metrics")]
+mod exporter {
+    use std::{
+        io::{self, Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use color_eyre::eyre::{Context, Result};
+    use once_cell::sync::Lazy;
+    use prometheus::{Encoder, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+    use tokio_util::sync::CancellationToken;
+    use tracing::{debug, error, info, warn};
+
+    use crate::{
+        job::{self, SpawnResult},
+        Spawn,
+    };
+
+    pub(super) static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+    /// Represents the metrics exporter job, serving metrics over HTTP.
+    pub struct Job;
+
+    /// Represents the metrics exporter job configuration.
+    #[derive(Debug, Clone)]
+    pub struct Config {
+        /// The address to serve metrics on. Metrics aren't authenticated, so
+        /// this should be a loopback address.
+        pub addr: SocketAddr,
+    }
+
+    pub(super) struct Metrics {
+        registry: Registry,
+        pub commands_sent: IntCounterVec,
+        pub commands_debounced: IntCounter,
+        pub active_source_unacknowledged: IntCounter,
+        pub cec_reconnects: IntCounter,
+        pub power_state: IntGauge,
+    }
+
+    impl Metrics {
+        #[allow(clippy::expect_used)]
+        fn new() -> Self {
+            let registry = Registry::new();
+            let commands_sent = IntCounterVec::new(
+                Opts::new(
+                    "owl_commands_sent_total",
+                    "Commands sent to the HDMI-CEC bus.",
+                ),
+                &["command"],
+            )
+            .expect("metric is valid");
+            let commands_debounced = IntCounter::new(
+                "owl_commands_debounced_total",
+                "Commands dropped by debouncing.",
+            )
+            .expect("metric is valid");
+            let active_source_unacknowledged = IntCounter::new(
+                "owl_active_source_unacknowledged_total",
+                "Active source broadcasts the TV didn't acknowledge.",
+            )
+            .expect("metric is valid");
+            let cec_reconnects = IntCounter::new(
+                "owl_cec_reconnects_total",
+                "Reopens of the HDMI-CEC adapter after it was lost.",
+            )
+            .expect("metric is valid");
+            let power_state = IntGauge::new(
+                "owl_power_state",
+                "The last power state sent to the TV, 1 for on and 0 for off.",
+            )
+            .expect("metric is valid");
+
+            for metric in [
+                Box::new(commands_sent.clone()) as Box<dyn prometheus::core::Collector>,
+                Box::new(commands_debounced.clone()),
+                Box::new(active_source_unacknowledged.clone()),
+                Box::new(cec_reconnects.clone()),
+                Box::new(power_state.clone()),
+            ] {
+                registry.register(metric).expect("metric is unique");
+            }
+
+            Self {
+                registry,
+                commands_sent,
+                commands_debounced,
+                active_source_unacknowledged,
+                cec_reconnects,
+                power_state,
+            }
+        }
+
+        fn encode(&self) -> Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            TextEncoder::new()
+                .encode(&self.registry.gather(), &mut buf)
+                .context("failed to encode metrics")?;
+            Ok(buf)
+        }
+    }
+
+    impl Spawn for Job {
+        type Config = Config;
+
+        /// Spawns a new metrics exporter job. The job runs on a thread.
+        async fn spawn(run_token: CancellationToken, config: Config) -> SpawnResult<Self> {
+            if !config.addr.ip().is_loopback() {
+                warn!(
+                    "serving metrics on non-loopback address `{}`, they'll be visible to the \
+                     network",
+                    config.addr
+                );
+            }
+
+            let listener = TcpListener::bind(config.addr)
+                .context(format!("failed to bind metrics to `{}`", config.addr))?;
+            // Polling lets the job notice the run token being cancelled.
+            listener
+                .set_nonblocking(true)
+                .context("failed to set metrics listener to non-blocking")?;
+            info!("serving metrics on `http://{}/metrics`", config.addr);
+
+            debug!("spawning metrics job...");
+            let handle = job::spawn_thread("owl-metrics", move || {
+                loop {
+                    if run_token.is_cancelled() {
+                        debug!("stopping metrics job...");
+                        break;
+                    }
+
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = serve(stream) {
+                                error!("failed to serve metrics: {e:?}");
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(100));
+                        }
+                        Err(e) => error!("failed to accept metrics connection: {e}"),
+                    }
+                }
+
+                Ok(())
+            })?;
+
+            Ok((handle, Self))
+        }
+    }
+
+    fn serve(mut stream: TcpStream) -> Result<()> {
+        stream
+            .set_nonblocking(false)
+            .context("failed to set metrics stream to blocking")?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .context("failed to set metrics stream timeout")?;
+
+        // Every path serves the metrics, so the request itself doesn't matter.
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request);
+
+        let body = METRICS.encode()?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+             {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Desktop notifications for HDMI-CEC errors owl can't recover from by itself.
+//!
+//! They're seen without watching the logs. Notifying is a no-op unless the
+//! `notifications` feature is enabled and `--notify` given.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+/// The least time between notifications, so a flapping adapter doesn't flood
+/// the desktop.
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LAST_SHOWN: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Enables notifications, unless there's no desktop session to show them in,
+/// like when owl runs as a system service.
+pub fn enable() {
+    if has_desktop() {
+        ENABLED.store(true, Ordering::Relaxed);
+    } else {
+        debug!("no desktop session, not showing notifications");
+    }
+}
+
+/// Shows `message` as a notification, unless another was shown within
+/// [`MIN_INTERVAL`]. It's shown on its own thread, as this is called from
+/// libcec's callbacks, which showing it could hold up.
+#[allow(unused_variables)]
+pub fn error(message: &str) {
+    if !ENABLED.load(Ordering::Relaxed) || !should_show(&LAST_SHOWN, Instant::now()) {
+        return;
+    }
+
+    #[cfg(feature = "notifications")]
+    {
+        let message = message.to_owned();
+        let result = crate::job::spawn_thread("owl-notify", move || {
+            if let Err(e) = notify_rust::Notification::new()
+                .summary("owl")
+                .body(&message)
+                .show()
+            {
+                debug!("failed to show notification: {e}");
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            debug!("failed to show notification: {e:#}");
+        }
+    }
+}
+
+/// Returns whether a notification may be shown at `now`, noting it as shown
+/// if so.
+fn should_show(last_shown: &Mutex<Option<Instant>>, now: Instant) -> bool {
+    let Ok(mut last_shown) = last_shown.lock() else {
+        return false;
+    };
+    if last_shown.is_some_and(|x| now.saturating_duration_since(x) < MIN_INTERVAL) {
+        return false;
+    }
+
+    *last_shown = Some(now);
+    true
+}
+
+/// Whether there's a desktop session. Without a session bus on Linux, there's
+/// nothing to show notifications in.
+#[cfg(target_os = "linux")]
+fn has_desktop() -> bool {
+    std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+}
+
+/// Whether there's a desktop session. Services run in session 0, which has
+/// no desktop to show notifications in.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-processidtosessionid>
+#[cfg(windows)]
+fn has_desktop() -> bool {
+    use windows::Win32::System::{RemoteDesktop::ProcessIdToSessionId, Threading};
+
+    let mut session = 0;
+    unsafe { ProcessIdToSessionId(Threading::GetCurrentProcessId(), &mut session) }
+        .is_ok_and(|()| session != 0)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+const fn has_desktop() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limited() {
+        let last_shown = Mutex::new(None);
+        let now = Instant::now();
+        assert!(should_show(&last_shown, now));
+        assert!(!should_show(&last_shown, now + Duration::from_secs(1)));
+        assert!(!should_show(&last_shown, now + MIN_INTERVAL / 2));
+        assert!(should_show(&last_shown, now + MIN_INTERVAL));
+    }
+}
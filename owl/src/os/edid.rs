@@ -0,0 +1,95 @@
+//! Reads the HDMI-CEC physical address a display assigned to its source, from
+//! the display's EDID.
+//!
+//! See: <https://en.wikipedia.org/wiki/Extended_Display_Identification_Data>
+
+use ::cec::PhysicalAddress;
+
+/// The size of the base block and of each extension block.
+const BLOCK_LEN: usize = 128;
+/// The base block byte holding the number of extension blocks.
+const EXTENSION_COUNT: usize = 126;
+/// The tag of a CTA-861 extension block.
+const CTA_TAG: u8 = 0x02;
+/// The tag of a vendor specific data block.
+const VENDOR_TAG: u8 = 0x03;
+/// The HDMI Licensing IEEE OUI, least significant byte first.
+const HDMI_OUI: [u8; 3] = [0x03, 0x0C, 0x00];
+
+/// Returns the physical address from the HDMI vendor specific data block of
+/// the EDID's CTA-861 extensions, if any.
+#[must_use]
+pub fn physical_address(edid: &[u8]) -> Option<PhysicalAddress> {
+    let count = usize::from(*edid.get(EXTENSION_COUNT)?);
+    edid.chunks_exact(BLOCK_LEN)
+        .skip(1)
+        .take(count)
+        .filter(|x| x[0] == CTA_TAG)
+        .find_map(cta_physical_address)
+}
+
+fn cta_physical_address(block: &[u8]) -> Option<PhysicalAddress> {
+    // Data blocks sit between the header and the detailed timings, which
+    // start at the offset in byte 2.
+    let end = usize::from(block[2]).clamp(4, BLOCK_LEN);
+    let mut data = &block[4..end];
+    while let [header, rest @ ..] = data {
+        let len = usize::from(header & 0x1F);
+        let payload = rest.get(..len)?;
+        if header >> 5 == VENDOR_TAG
+            && let [a, b, c, hi, lo, ..] = *payload
+            && [a, b, c] == HDMI_OUI
+        {
+            return Some(PhysicalAddress::from_be_bytes([hi, lo]));
+        }
+        data = &rest[len..];
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edid(data_blocks: &[u8]) -> Vec<u8> {
+        let mut edid = vec![0; BLOCK_LEN * 2];
+        edid[EXTENSION_COUNT] = 1;
+        let cta = &mut edid[BLOCK_LEN..];
+        cta[0] = CTA_TAG;
+        cta[2] = u8::try_from(4 + data_blocks.len()).expect("data blocks fit");
+        cta[4..4 + data_blocks.len()].copy_from_slice(data_blocks);
+        edid
+    }
+
+    #[test]
+    fn test_physical_address() {
+        let edid = edid(&[
+            // An audio data block, skipped.
+            0x23, 0x09, 0x07, 0x07, //
+            // The HDMI vendor specific data block.
+            0x65, 0x03, 0x0C, 0x00, 0x20, 0x00,
+        ]);
+        assert_eq!(physical_address(&edid), Some(PhysicalAddress(0x2000)));
+    }
+
+    #[test]
+    fn test_other_vendor() {
+        let edid = edid(&[0x65, 0xD8, 0x5D, 0xC4, 0x20, 0x00]);
+        assert_eq!(physical_address(&edid), None);
+    }
+
+    #[test]
+    fn test_no_extensions() {
+        assert_eq!(physical_address(&[0; BLOCK_LEN]), None);
+        assert_eq!(physical_address(&[]), None);
+    }
+
+    #[test]
+    fn test_truncated_data_block() {
+        let mut edid = edid(&[0x65, 0x03, 0x0C]);
+        // Claim the data blocks run into the zero padding.
+        edid[BLOCK_LEN + 2] = 7;
+        assert_eq!(physical_address(&edid), None);
+    }
+}
@@ -1,23 +1,63 @@
+use ::cec::PhysicalAddress;
 use color_eyre::eyre::Result;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     job::{Recv, SpawnResult},
-    os::Event,
+    os::{self, Event},
     Spawn,
 };
 
 pub struct Job;
 
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("owl os backend not yet implemented for linux")]
+    Unimplemented,
+}
+
+/// Returns the key code for a key name. No key names are known yet.
+#[must_use]
+pub const fn key_code(_name: &str) -> Option<u16> {
+    None
+}
+
+/// Fails, as pressing keys on the PC isn't supported on this platform yet.
+pub const fn send_key(_key: os::Key, _pressed: bool) -> Result<(), Error> {
+    Err(Error::Unimplemented)
+}
+
+/// Fails, as capturing keys isn't supported on this platform yet.
+pub const fn list_keys() -> Result<(), Error> {
+    Err(Error::Unimplemented)
+}
+
+/// Checks the OS hooks can be installed. There's no Linux backend yet, so
+/// nothing needs checking; once there is, reading input devices will, see
+/// [`os::MissingPermission::InputDevices`].
+pub const fn preflight() -> Result<(), os::MissingPermission> {
+    Ok(())
+}
+
+/// Returns the physical address the HDMI display assigned to the PC. Not
+/// detected on this platform yet.
+#[must_use]
+pub const fn hdmi_address() -> Option<PhysicalAddress> {
+    None
+}
+
 impl Spawn for Job {
-    /// Spawns a new Linux job.
-    async fn spawn(_run_token: CancellationToken) -> SpawnResult<Self> {
-        unimplemented!()
+    type Config = os::Config;
+
+    /// Fails, as there's no Linux backend yet. Commands which only use the
+    /// HDMI-CEC bus still work.
+    async fn spawn(_run_token: CancellationToken, _config: os::Config) -> SpawnResult<Self> {
+        Err(Error::Unimplemented.into())
     }
 }
 
 impl Recv<Event> for Job {
     async fn recv(&mut self) -> Result<Event> {
-        unimplemented!()
+        Err(Error::Unimplemented.into())
     }
 }
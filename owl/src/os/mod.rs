@@ -1,24 +1,98 @@
 cfg_if::cfg_if! {
     if #[cfg(target_os = "windows")] {
         pub mod windows;
-        pub use windows::{Job, Error};
+        pub use windows::{Job, Error, key_code, hdmi_address, list_keys, preflight, send_key};
     } else if #[cfg(target_os = "macos")] {
         pub mod macos;
-        pub use macos::{Job, Error};
+        pub use macos::{Job, Error, key_code, hdmi_address, list_keys, preflight, send_key};
     } else if #[cfg(target_os = "linux")] {
         pub mod linux;
-        pub use linux::{Job, Error};
+        pub use linux::{Job, Error, key_code, hdmi_address, list_keys, preflight, send_key};
     } else {
         compile_error!("unsupported platform");
     }
 }
 
+pub mod edid;
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+    num::ParseIntError,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
 use tokio::sync::mpsc;
+use tracing::{error, trace};
+
+use crate::{channel, job};
 
 pub type EventTx = mpsc::UnboundedSender<Event>;
 pub type EventRx = mpsc::UnboundedReceiver<Event>;
-pub type ErrorTx = mpsc::UnboundedSender<Error>;
-pub type ErrorRx = mpsc::UnboundedReceiver<Error>;
+pub type ErrorTx = channel::Sender<Error>;
+pub type ErrorRx = channel::Receiver<Error>;
+
+// OS hooks send with these, so they must stay channels which never block, see
+// `job::debug_assert_hook_context`.
+const _: fn(EventTx) -> mpsc::UnboundedSender<Event> = |x| x;
+const _: fn(ErrorTx) -> channel::Sender<Error> = |x| x;
+
+/// Whether the cec job sends volume keys to the TV, as there's no audio
+/// system, see [`volume_to_tv`].
+static VOLUME_TO_TV: AtomicBool = AtomicBool::new(false);
+
+/// Notes where the cec job sends volume keys, as it polls for the audio
+/// system.
+pub fn set_volume_to_tv(to_tv: bool) {
+    VOLUME_TO_TV.store(to_tv, Ordering::Relaxed);
+}
+
+/// Whether the cec job sends volume keys to the TV. Those aren't suppressed
+/// even with [`Config::suppress_volume_keys`], so the PC's volume can still be
+/// changed without an audio system.
+#[must_use]
+pub fn volume_to_tv() -> bool {
+    VOLUME_TO_TV.load(Ordering::Relaxed)
+}
+
+/// Represents the OS job configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// How long the display must stay off before owl suspends. Some displays
+    /// bounce between off and on, which would otherwise flap the TV.
+    pub display_off_delay: Duration,
+    /// Whether volume keys are hidden from the rest of the system. Without an
+    /// audio system on the bus, suppressing them would leave no way to change
+    /// the volume, so they're only hidden while the cec job sends them to an
+    /// audio system, see [`volume_to_tv`].
+    pub suppress_volume_keys: bool,
+    /// The keys reported as [`Event::RawKey`] when pressed, so they can be
+    /// mapped to HDMI-CEC keypresses.
+    pub raw_keys: HashSet<KeyCode>,
+    /// How often the OS event loop is checked to still be responding, zero to
+    /// never check. Only used on Windows.
+    pub heartbeat_interval: Duration,
+    /// Whether changes to the system mute state are reported as
+    /// [`Event::Mute`], so muting in any app mutes the audio system. Only
+    /// changes are reported, as the new state rather than a toggle, so owl
+    /// muting the PC itself, like forwarding the remote's mute key, can't
+    /// bounce back and forth. Only supported on Windows.
+    pub mirror_mute: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            display_off_delay: Duration::from_secs(2),
+            suppress_volume_keys: true,
+            raw_keys: HashSet::new(),
+            heartbeat_interval: Duration::from_secs(10),
+            mirror_mute: false,
+        }
+    }
+}
 
 /// Represents a keyboard key targetted for HDMI-CEC integration.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -28,12 +102,207 @@ pub enum Key {
     VolumeMute,
 }
 
+/// A platform specific key code, a virtual-key code on Windows. Parsed from
+/// either a key name, like `F13`, or the code itself, like `0x7C`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCode(pub u16);
+
+/// A monitor, numbered like its `\\.\DISPLAYn` device name on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Monitor(pub u32);
+
 /// Represents an OS event targetted for HDMI-CEC integration.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Event {
     Suspend,
     Resume,
-    Focus,
+    /// The session is ending, owl is about to be killed.
+    Shutdown,
+    /// The user used the PC, on the given monitor if it's known.
+    Focus(Option<Monitor>),
     Press(Key),
     Release(Key),
+    /// A key from [`Config::raw_keys`] was pressed.
+    RawKey(KeyCode),
+    /// The system was muted or unmuted, see [`Config::mirror_mute`].
+    Mute(bool),
+}
+
+/// A permission the OS hooks need but weren't granted, found by [`preflight`]
+/// before any job starts, rather than keys silently doing nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MissingPermission {
+    #[error(
+        "owl needs Accessibility permission to see media keys, grant it in System Settings → \
+         Privacy & Security → Accessibility"
+    )]
+    Accessibility,
+    #[error(
+        "owl needs to read input devices to see media keys, add your user to the `input` group \
+         or run owl as root"
+    )]
+    InputDevices,
+}
+
+impl MissingPermission {
+    /// The code owl exits with, distinct per permission so wrappers can react.
+    #[must_use]
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Accessibility => 3,
+            Self::InputDevices => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseKeyCodeError {
+    #[error("unknown key `{0}`")]
+    UnknownKey(String),
+    #[error("invalid key code")]
+    InvalidKeyCode(#[from] ParseIntError),
+}
+
+/// Relays an error from an OS hook without blocking.
+pub fn send_err(err_tx: &ErrorTx, err: Error) {
+    job::debug_assert_hook_context();
+    trace!("relaying error: {err:?}");
+    err_tx.send(err);
+}
+
+/// Relays an event from an OS hook without blocking.
+pub fn send_event(event_tx: &EventTx, event: Event) {
+    job::debug_assert_hook_context();
+    trace!("relaying event: {event:?}");
+    if let Err(e) = event_tx.send(event) {
+        error!("failed to relay event: {event:?}: {e}");
+    };
+}
+
+impl Key {
+    /// Every key, in declaration order.
+    pub const ALL: [Self; 3] = [Self::VolumeUp, Self::VolumeDown, Self::VolumeMute];
+
+    /// Returns the key's name, like `volume-up`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::VolumeUp => "volume-up",
+            Self::VolumeDown => "volume-down",
+            Self::VolumeMute => "volume-mute",
+        }
+    }
+}
+
+impl Event {
+    /// Returns every event, for listing what owl can translate. Focus is
+    /// listed for any monitor, and raw keys aren't listed since they take a
+    /// free key code.
+    #[must_use]
+    pub fn all() -> Vec<Self> {
+        let mut all = vec![
+            Self::Suspend,
+            Self::Resume,
+            Self::Shutdown,
+            Self::Focus(None),
+        ];
+        all.extend(Key::ALL.map(Self::Press));
+        all.extend(Key::ALL.map(Self::Release));
+        all.extend([Self::Mute(true), Self::Mute(false)]);
+        all
+    }
+}
+
+/// Writes the event like it's given on the command line, like
+/// `press:volume-up`.
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Suspend => f.write_str("suspend"),
+            Self::Resume => f.write_str("resume"),
+            Self::Shutdown => f.write_str("shutdown"),
+            Self::Focus(None) => f.write_str("focus"),
+            Self::Focus(Some(Monitor(x))) => write!(f, "focus:{x}"),
+            Self::Press(key) => write!(f, "press:{}", key.name()),
+            Self::Release(key) => write!(f, "release:{}", key.name()),
+            Self::RawKey(KeyCode(x)) => write!(f, "raw:0x{x:X}"),
+            Self::Mute(true) => f.write_str("mute"),
+            Self::Mute(false) => f.write_str("unmute"),
+        }
+    }
+}
+
+impl FromStr for KeyCode {
+    type Err = ParseKeyCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(code) = key_code(s) {
+            return Ok(Self(code));
+        }
+
+        let code = match s.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16)?,
+            None if s.starts_with(|x: char| x.is_ascii_digit()) => s.parse()?,
+            None => return Err(ParseKeyCodeError::UnknownKey(s.to_owned())),
+        };
+
+        Ok(Self(code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_code() -> Result<(), ParseKeyCodeError> {
+        assert_eq!("0x7C".parse::<KeyCode>()?, KeyCode(0x7C));
+        assert_eq!("124".parse::<KeyCode>()?, KeyCode(124));
+        assert_eq!(
+            "toaster".parse::<KeyCode>(),
+            Err(ParseKeyCodeError::UnknownKey("toaster".to_owned()))
+        );
+        assert!("0xZZ".parse::<KeyCode>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_events() -> Result<(), String> {
+        // Fails to compile when a variant is added, so it isn't left out of
+        // `Event::all`.
+        let variant = |x: &Event| match x {
+            Event::Suspend => 0,
+            Event::Resume => 1,
+            Event::Shutdown => 2,
+            Event::Focus(_) => 3,
+            Event::Press(_) => 4,
+            Event::Release(_) => 5,
+            Event::RawKey(_) => 6,
+            Event::Mute(_) => 7,
+        };
+        let all = Event::all();
+        let variants: HashSet<_> = all.iter().map(variant).collect();
+        assert_eq!(variants, (0..8).filter(|x| *x != 6).collect());
+        assert_eq!(all.len(), 6 + 2 * Key::ALL.len());
+
+        for event in all
+            .into_iter()
+            .chain([Event::Focus(Some(Monitor(2))), Event::RawKey(KeyCode(0x7C))])
+        {
+            assert_eq!(crate::cli::parse_event(&event.to_string())?, event);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_codes_distinct() {
+        let codes = [
+            MissingPermission::Accessibility,
+            MissingPermission::InputDevices,
+        ]
+        .map(MissingPermission::exit_code);
+        // 1 is any other error, 2 an invalid command line.
+        assert!(codes.iter().all(|x| *x > 2));
+        assert_ne!(codes[0], codes[1]);
+    }
 }
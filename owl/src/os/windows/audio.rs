@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::{debug, error};
+
+use crate::os::{self, windows::send_event, Event};
+
+mod win32 {
+    pub use windows::{
+        core::{implement, Error, Result},
+        Win32::{
+            Media::Audio::{
+                eConsole, eRender,
+                Endpoints::{
+                    IAudioEndpointVolume, IAudioEndpointVolumeCallback,
+                    IAudioEndpointVolumeCallback_Impl,
+                },
+                IMMDeviceEnumerator, MMDeviceEnumerator, AUDIO_VOLUME_NOTIFICATION_DATA,
+            },
+            System::Com::{self, CLSCTX_ALL, COINIT_MULTITHREADED},
+        },
+    };
+}
+
+/// Watches the default output device's mute state, reporting changes as
+/// [`Event::Mute`].
+pub struct MuteWatcher {
+    /// See: <https://learn.microsoft.com/en-us/windows/win32/api/endpointvolume/nn-endpointvolume-iaudioendpointvolume>
+    endpoint: win32::IAudioEndpointVolume,
+    callback: win32::IAudioEndpointVolumeCallback,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to initialize com")]
+    ComInitFailed(win32::Error),
+
+    #[error("failed to get the default audio endpoint")]
+    EndpointFailed(win32::Error),
+
+    #[error("failed to register for mute changes")]
+    RegisterFailed(win32::Error),
+}
+
+/// Receives the endpoint's volume notifications, on a thread COM owns.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/endpointvolume/nn-endpointvolume-iaudioendpointvolumecallback>
+#[win32::implement(win32::IAudioEndpointVolumeCallback)]
+struct MuteCallback {
+    event_tx: os::EventTx,
+    /// The last mute state seen, as volume changes are notified too.
+    muted: AtomicBool,
+}
+
+impl MuteWatcher {
+    /// Starts watching the default output device, e.g. the HDMI display.
+    pub fn new(event_tx: os::EventTx) -> Result<Self, Error> {
+        unsafe {
+            // See: https://learn.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-coinitializeex
+            win32::Com::CoInitializeEx(None, win32::COINIT_MULTITHREADED)
+                .ok()
+                .map_err(Error::ComInitFailed)?;
+
+            let endpoint = default_endpoint().map_err(Error::EndpointFailed)?;
+            let muted = endpoint.GetMute().map_err(Error::EndpointFailed)?.as_bool();
+            let callback: win32::IAudioEndpointVolumeCallback = MuteCallback {
+                event_tx,
+                muted: AtomicBool::new(muted),
+            }
+            .into();
+            endpoint
+                .RegisterControlChangeNotify(&callback)
+                .map_err(Error::RegisterFailed)?;
+
+            debug!("watching mute state, currently muted: {muted}");
+            Ok(Self { endpoint, callback })
+        }
+    }
+}
+
+impl Drop for MuteWatcher {
+    fn drop(&mut self) {
+        debug!("dropping mute watcher...");
+        if let Err(e) = unsafe { self.endpoint.UnregisterControlChangeNotify(&self.callback) } {
+            error!("failed to unregister mute watcher: {e}");
+        }
+    }
+}
+
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/mmdeviceapi/nf-mmdeviceapi-immdeviceenumerator-getdefaultaudioendpoint>
+unsafe fn default_endpoint() -> win32::Result<win32::IAudioEndpointVolume> {
+    let enumerator: win32::IMMDeviceEnumerator =
+        win32::Com::CoCreateInstance(&win32::MMDeviceEnumerator, None, win32::CLSCTX_ALL)?;
+    let device = enumerator.GetDefaultAudioEndpoint(win32::eRender, win32::eConsole)?;
+    device.Activate(win32::CLSCTX_ALL, None)
+}
+
+impl win32::IAudioEndpointVolumeCallback_Impl for MuteCallback {
+    /// See: <https://learn.microsoft.com/en-us/windows/win32/api/endpointvolume/nf-endpointvolume-iaudioendpointvolumecallback-onnotify>
+    fn OnNotify(&self, data: *mut win32::AUDIO_VOLUME_NOTIFICATION_DATA) -> win32::Result<()> {
+        // Safety: the pointer is valid for the duration of the call.
+        let Some(data) = (unsafe { data.as_ref() }) else {
+            return Ok(());
+        };
+        let muted = data.bMuted.as_bool();
+        // Only changes are reported, so owl setting the state it was sent
+        // doesn't echo back.
+        if self.muted.swap(muted, Ordering::Relaxed) != muted {
+            send_event(&self.event_tx, Event::Mute(muted));
+        }
+        Ok(())
+    }
+}
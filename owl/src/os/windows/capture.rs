@@ -0,0 +1,85 @@
+//! Prints the keys pressed, for finding the key codes to map in the config.
+//! Runs its own keyboard hook, so owl needn't be running.
+
+use tracing::{debug, error};
+
+use crate::os::windows::{handlers, key, translate};
+
+mod win32 {
+    pub use windows::{
+        core::Error,
+        Win32::{
+            Foundation::{LPARAM, LRESULT, WPARAM},
+            System::LibraryLoader,
+            UI::{Input::KeyboardAndMouse::VK_ESCAPE, WindowsAndMessaging},
+        },
+    };
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to get module handle")]
+    GetModuleHandleFailed(win32::Error),
+
+    #[error("failed to initialize global hook")]
+    InitHookFailed(win32::Error),
+}
+
+/// Prints the virtual-key code and name of each key pressed, until Esc is
+/// pressed. Keys still reach the rest of the system.
+pub fn run() -> Result<(), Error> {
+    let hook = unsafe {
+        let module =
+            win32::LibraryLoader::GetModuleHandleW(None).map_err(Error::GetModuleHandleFailed)?;
+        win32::WindowsAndMessaging::SetWindowsHookExW(
+            win32::WindowsAndMessaging::WH_KEYBOARD_LL,
+            Some(handle_key_event),
+            module,
+            0,
+        )
+        .map_err(Error::InitHookFailed)?
+    };
+
+    println!("press keys to print their codes, esc to exit");
+    handlers::event_loop();
+
+    debug!("dropping key capture hook...");
+    if let Err(e) = unsafe { win32::WindowsAndMessaging::UnhookWindowsHookEx(hook) } {
+        error!("failed to drop key capture hook: {e}");
+    }
+    Ok(())
+}
+
+/// Prints each key press, quitting the message loop on Esc.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/winmsg/lowlevelkeyboardproc>
+extern "system" fn handle_key_event(
+    ncode: i32,
+    wparam: win32::WPARAM,
+    lparam: win32::LPARAM,
+) -> win32::LRESULT {
+    #[allow(clippy::cast_possible_wrap)]
+    const HC_ACTION: i32 = win32::WindowsAndMessaging::HC_ACTION as i32;
+
+    if ncode == HC_ACTION
+        && let Ok(event) = key::Event::try_from((wparam, lparam))
+        && *event.kind == win32::WindowsAndMessaging::WM_KEYDOWN
+    {
+        let code = event.code.0 .0;
+        if code == win32::VK_ESCAPE.0 {
+            unsafe { win32::WindowsAndMessaging::PostQuitMessage(0) };
+        } else {
+            println!("{}", describe(code));
+        }
+    }
+
+    unsafe { win32::WindowsAndMessaging::CallNextHookEx(None, ncode, wparam, lparam) }
+}
+
+/// Describes a key like `0x7C F13`, the code usable in the keymap either way.
+fn describe(code: u16) -> String {
+    match translate::key_name(code) {
+        Some(name) => format!("0x{code:02X} {name}"),
+        None => format!("0x{code:02X}"),
+    }
+}
@@ -0,0 +1,194 @@
+use std::mem;
+
+use ::cec::PhysicalAddress;
+use tracing::debug;
+
+use crate::os::{self, edid, windows::translate};
+
+mod win32 {
+    pub use windows::{
+        core::{Error, HSTRING},
+        Win32::{
+            Devices::Display::{
+                DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+                DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME, DISPLAYCONFIG_MODE_INFO,
+                DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI, DISPLAYCONFIG_PATH_INFO,
+                DISPLAYCONFIG_TARGET_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+            },
+            Foundation::WIN32_ERROR,
+            Graphics::Gdi::{
+                GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITORINFOEXW,
+                MONITOR_DEFAULTTONULL,
+            },
+            System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_BINARY},
+            UI::WindowsAndMessaging::GetForegroundWindow,
+        },
+    };
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to query display config")]
+    QueryFailed(win32::Error),
+    #[error("failed to get display name")]
+    TargetNameFailed(win32::Error),
+    #[error("unexpected display device path `{0}`")]
+    InvalidDevicePath(String),
+    #[error("failed to read display edid")]
+    EdidReadFailed(win32::Error),
+}
+
+/// Returns the physical address from the EDID of the first active display
+/// connected over HDMI which has one.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-querydisplayconfig>
+pub fn hdmi_address() -> Result<Option<PhysicalAddress>, Error> {
+    for path in active_paths()? {
+        let target = target_name(&path)?;
+        if target.outputTechnology != win32::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI {
+            continue;
+        }
+
+        let device_path = String::from_utf16_lossy(&target.monitorDevicePath)
+            .trim_end_matches('\0')
+            .to_owned();
+        debug!("reading edid of hdmi display `{device_path}`...");
+        if let Some(address) = edid::physical_address(&read_edid(&device_path)?) {
+            return Ok(Some(address));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the monitor showing the foreground window, `None` if there's no
+/// foreground window or it's off screen. Cheap enough to call from the
+/// keyboard hook.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-monitorfromwindow>
+pub fn foreground_monitor() -> Option<os::Monitor> {
+    let monitor = unsafe {
+        win32::MonitorFromWindow(win32::GetForegroundWindow(), win32::MONITOR_DEFAULTTONULL)
+    };
+    if monitor.is_invalid() {
+        return None;
+    }
+
+    let mut info = win32::MONITORINFOEXW::default();
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        info.monitorInfo.cbSize = mem::size_of::<win32::MONITORINFOEXW>() as u32;
+    }
+    let found = unsafe {
+        win32::GetMonitorInfoW(
+            monitor,
+            std::ptr::from_mut(&mut info).cast::<win32::MONITORINFO>(),
+        )
+    };
+    if !found.as_bool() {
+        return None;
+    }
+
+    let device = String::from_utf16_lossy(&info.szDevice);
+    translate::monitor(device.trim_end_matches('\0'))
+}
+
+fn active_paths() -> Result<Vec<win32::DISPLAYCONFIG_PATH_INFO>, Error> {
+    let (mut path_count, mut mode_count) = (0, 0);
+    unsafe {
+        win32::GetDisplayConfigBufferSizes(
+            win32::QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            &mut mode_count,
+        )
+    }
+    .ok()
+    .map_err(Error::QueryFailed)?;
+
+    let mut paths = vec![win32::DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+    let mut modes = vec![win32::DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+    unsafe {
+        win32::QueryDisplayConfig(
+            win32::QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        )
+    }
+    .ok()
+    .map_err(Error::QueryFailed)?;
+
+    paths.truncate(path_count as usize);
+    Ok(paths)
+}
+
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-displayconfig_target_device_name>
+fn target_name(
+    path: &win32::DISPLAYCONFIG_PATH_INFO,
+) -> Result<win32::DISPLAYCONFIG_TARGET_DEVICE_NAME, Error> {
+    let mut target = win32::DISPLAYCONFIG_TARGET_DEVICE_NAME::default();
+    target.header.r#type = win32::DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME;
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        target.header.size = mem::size_of::<win32::DISPLAYCONFIG_TARGET_DEVICE_NAME>() as u32;
+    }
+    target.header.adapterId = path.targetInfo.adapterId;
+    target.header.id = path.targetInfo.id;
+
+    #[allow(clippy::cast_sign_loss)]
+    let status = unsafe { win32::DisplayConfigGetDeviceInfo(&mut target.header) } as u32;
+    win32::WIN32_ERROR(status)
+        .ok()
+        .map_err(Error::TargetNameFailed)?;
+
+    Ok(target)
+}
+
+/// Reads a display's EDID from the registry, where the display driver caches
+/// it. The device path looks like
+/// `\\?\DISPLAY#GSM5B7F#5&1a2b3c&0&UID4352#{...}`, naming the registry key
+/// `DISPLAY\GSM5B7F\5&1a2b3c&0&UID4352`.
+fn read_edid(device_path: &str) -> Result<Vec<u8>, Error> {
+    let parts: Vec<_> = device_path.trim_start_matches(r"\\?\").split('#').collect();
+    let [class, model, instance, _] = parts[..] else {
+        return Err(Error::InvalidDevicePath(device_path.to_owned()));
+    };
+    let key = win32::HSTRING::from(format!(
+        r"SYSTEM\CurrentControlSet\Enum\{class}\{model}\{instance}\Device Parameters"
+    ));
+
+    let mut len = 0;
+    unsafe {
+        win32::RegGetValueW(
+            win32::HKEY_LOCAL_MACHINE,
+            &key,
+            &win32::HSTRING::from("EDID"),
+            win32::RRF_RT_REG_BINARY,
+            None,
+            None,
+            Some(&mut len),
+        )
+    }
+    .ok()
+    .map_err(Error::EdidReadFailed)?;
+
+    let mut edid = vec![0u8; len as usize];
+    unsafe {
+        win32::RegGetValueW(
+            win32::HKEY_LOCAL_MACHINE,
+            &key,
+            &win32::HSTRING::from("EDID"),
+            win32::RRF_RT_REG_BINARY,
+            None,
+            Some(edid.as_mut_ptr().cast()),
+            Some(&mut len),
+        )
+    }
+    .ok()
+    .map_err(Error::EdidReadFailed)?;
+
+    edid.truncate(len as usize);
+    Ok(edid)
+}
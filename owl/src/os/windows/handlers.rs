@@ -1,22 +1,34 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
 use tracing::{debug, error};
 
-use super::{get_owl_handle, power::Event, send_event, OwlHandle};
+use super::{
+    get_owl_handle,
+    power::{self, Event},
+    send_event, OwlHandle,
+};
 use crate::os::{
     self,
-    windows::{key, send_err, window},
+    windows::{display, key, send_err, translate, translate::DisplayAction, window},
 };
 
 mod win32 {
     pub use windows::Win32::{
         Foundation::{HWND, LPARAM, LRESULT, WPARAM},
-        System::SystemServices,
-        UI::{
-            Input::KeyboardAndMouse::{self},
-            WindowsAndMessaging::{self},
-        },
+        UI::WindowsAndMessaging::{self},
     };
 }
 
+/// The timer which fires once the display has been off long enough to suspend.
+const SUSPEND_TIMER_ID: usize = 1;
+/// The message the watchdog posts to check the message loop still responds.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/winmsg/wm-app>
+pub const WM_HEARTBEAT: u32 = win32::WindowsAndMessaging::WM_APP + 1;
+/// How long to hold off the session ending, so powering off reaches the bus
+/// before owl is killed. Windows allows a few seconds.
+const END_SESSION_GRACE: Duration = Duration::from_secs(2);
+
 pub fn event_loop() {
     let mut msg = win32::WindowsAndMessaging::MSG::default();
 
@@ -48,6 +60,9 @@ pub extern "system" fn handle_window_event(
     let OwlHandle {
         err_tx: error_tx,
         event_tx,
+        display_off_delay,
+        heartbeat,
+        ..
     } = get_owl_handle!(defer);
 
     match msg {
@@ -85,39 +100,101 @@ pub extern "system" fn handle_window_event(
             };
 
             match power_msg {
-                // The system is resuming from sleep.
-                // See: https://learn.microsoft.com/en-us/windows/win32/power/pbt-apmresumeautomatic
-                win32::WindowsAndMessaging::PBT_APMRESUMEAUTOMATIC => {
-                    send_event(&event_tx, os::Event::Resume);
-                }
-
-                // The system is about to sleep.
-                // See: https://learn.microsoft.com/en-us/windows/win32/power/pbt-apmsuspend
-                win32::WindowsAndMessaging::PBT_APMSUSPEND => {
-                    send_event(&event_tx, os::Event::Suspend);
-                }
-
                 // A power setting change occurred.
                 // See: https://learn.microsoft.com/en-us/windows/win32/power/pbt-powersettingchange
                 win32::WindowsAndMessaging::PBT_POWERSETTINGCHANGE => {
-                    if let Ok(power_event) = Event::try_from(lparam)
-                    // Check the current display is turning off.
-                    && power_event.target() == win32::SystemServices::GUID_CONSOLE_DISPLAY_STATE
-                    && power_event.state() == win32::SystemServices::PowerMonitorOff
-                    {
-                        send_event(&event_tx, os::Event::Suspend);
+                    // Check the current display state changed.
+                    let state = match Event::try_from(lparam).and_then(|x| x.display_state()) {
+                        Ok(Some(x)) => x,
+                        Ok(None) => return defer(),
+                        Err(e) => {
+                            send_err(&error_tx, power::Error::from(e).into());
+                            return defer();
+                        }
+                    };
+                    match translate::display_state(state.0) {
+                        Some(DisplayAction::ScheduleSuspend) if display_off_delay.is_zero() => {
+                            send_event(&event_tx, os::Event::Suspend);
+                        }
+                        Some(DisplayAction::ScheduleSuspend) => {
+                            schedule_suspend(window, display_off_delay);
+                        }
+                        Some(DisplayAction::CancelSuspend) => cancel_suspend(window),
+                        None => debug!("ignoring unknown display state {}", state.0),
+                    }
+                }
+
+                // The system is about to sleep or is resuming from sleep.
+                power_msg => {
+                    if let Some(owl_event) = translate::power_event(power_msg) {
+                        send_event(&event_tx, owl_event);
                     }
                 }
-                _ => {}
             };
         }
 
+        // The session is about to end, we're asked if that's ok.
+        // See: https://learn.microsoft.com/en-us/windows/win32/shutdown/wm-queryendsession
+        win32::WindowsAndMessaging::WM_QUERYENDSESSION => {
+            debug!("received `WM_QUERYENDSESSION` event, allowing session to end...");
+            return win32::LRESULT(1);
+        }
+
+        // The session is ending, we'll be killed once this returns.
+        // See: https://learn.microsoft.com/en-us/windows/win32/shutdown/wm-endsession
+        win32::WindowsAndMessaging::WM_ENDSESSION => {
+            if let Some(event) = translate::end_session(wparam.0) {
+                debug!("session ending, powering off...");
+                send_event(&event_tx, event);
+                // There's no acknowledgement from the cec job, so wait long
+                // enough for the command to be sent.
+                std::thread::sleep(END_SESSION_GRACE);
+            }
+            return ok();
+        }
+
+        // The watchdog checking we're still dispatching messages.
+        WM_HEARTBEAT => {
+            heartbeat.store(wparam.0, Ordering::Relaxed);
+            return ok();
+        }
+
+        // A timer has elapsed.
+        // See: https://learn.microsoft.com/en-us/windows/win32/winmsg/wm-timer
+        win32::WindowsAndMessaging::WM_TIMER if wparam.0 == SUSPEND_TIMER_ID => {
+            debug!("display stayed off, suspending...");
+            cancel_suspend(window);
+            send_event(&event_tx, os::Event::Suspend);
+            return ok();
+        }
+
         _ => {}
     };
 
     defer()
 }
 
+/// Suspends once the display has stayed off for `delay`, unless cancelled.
+/// Scheduling again restarts the timer.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-settimer>
+fn schedule_suspend(window: win32::HWND, delay: Duration) {
+    debug!("display off, suspending in {delay:?}...");
+    let millis = u32::try_from(delay.as_millis()).unwrap_or(u32::MAX);
+    if unsafe { win32::WindowsAndMessaging::SetTimer(window, SUSPEND_TIMER_ID, millis, None) } == 0
+    {
+        error!("failed to schedule suspend");
+    }
+}
+
+/// Cancels a pending suspend, if any.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-killtimer>
+fn cancel_suspend(window: win32::HWND) {
+    // Fails if no suspend is pending, which is fine.
+    let _ = unsafe { win32::WindowsAndMessaging::KillTimer(window, SUSPEND_TIMER_ID) };
+}
+
 /// Our low-level key event handler. As per the docs, it's important to do our
 /// work as quickly as possible to avoid impacting system performance. We need
 /// to use a low-level hook ([`WH_KEYBOARD_LL`]) as opposed to a normal hook
@@ -147,22 +224,33 @@ pub extern "system" fn handle_low_level_key_event(
         return defer();
     }
 
-    let OwlHandle { err_tx, event_tx } = get_owl_handle!(defer);
+    let OwlHandle {
+        err_tx,
+        event_tx,
+        suppress_volume_keys,
+        raw_keys,
+        ..
+    } = get_owl_handle!(defer);
     match key::Event::try_from((wparam, lparam)) {
-        Ok(key_event) => match key_event.to_owl_event() {
+        // Keys owl forwarded from the TV remote, relaying them would echo
+        // them back to the bus.
+        Ok(key_event) if key_event.is_injected() => defer(),
+        Ok(key_event) => match key_event.to_owl_event(&raw_keys) {
             // We got an event we care about!
             Some(owl_event) => {
+                let owl_event = match owl_event {
+                    os::Event::Focus(None) => os::Event::Focus(display::foreground_monitor()),
+                    x => x,
+                };
                 send_event(&event_tx, owl_event);
 
-                // Unless volume events are suppressed, they'll operate as normal. This isn't
-                // desirable since we're trying to replace software mixing with
-                // hardware mixing. The software mixer works by reducing audio
-                // bit-depth to make the audio quieter, at the expense of audio quality.
-                match *key_event.code {
-                    win32::KeyboardAndMouse::VK_VOLUME_DOWN
-                    | win32::KeyboardAndMouse::VK_VOLUME_UP
-                    | win32::KeyboardAndMouse::VK_VOLUME_MUTE => suppress(),
-                    _ => defer(),
+                if suppress_volume_keys
+                    && !os::volume_to_tv()
+                    && translate::is_suppressed(key_event.code.0 .0)
+                {
+                    suppress()
+                } else {
+                    defer()
                 }
             }
             None => defer(),
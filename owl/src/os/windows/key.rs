@@ -1,17 +1,27 @@
-use std::ptr;
+use std::{collections::HashSet, mem, ptr};
 
-use crate::os;
+use crate::os::{self, windows::translate};
 
 mod win32 {
-    pub use windows::Win32::{
-        Foundation::{LPARAM, WPARAM},
-        UI::{
-            Input::KeyboardAndMouse::{self, VIRTUAL_KEY},
-            WindowsAndMessaging::{self, KBDLLHOOKSTRUCT},
+    pub use windows::{
+        core::Error,
+        Win32::{
+            Foundation::{LPARAM, WPARAM},
+            UI::{
+                Input::KeyboardAndMouse::{
+                    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+                    KEYEVENTF_KEYUP, VIRTUAL_KEY,
+                },
+                WindowsAndMessaging::KBDLLHOOKSTRUCT,
+            },
         },
     };
 }
 
+/// Tags the input owl injects, so the keyboard hook lets it through instead of
+/// relaying it back to the HDMI-CEC bus.
+const INJECTED_TAG: usize = 0x006F_776C;
+
 /// See: <https://learn.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes>
 #[derive(Debug, Clone, Copy, derive_more::Deref)]
 pub struct Code(pub win32::VIRTUAL_KEY);
@@ -29,7 +39,6 @@ pub struct EventContext(pub win32::KBDLLHOOKSTRUCT);
 
 #[derive(Debug, Clone, Copy)]
 pub struct Event {
-    #[allow(dead_code)]
     pub context: EventContext,
     pub kind: EventKind,
     pub code: Code,
@@ -39,6 +48,8 @@ pub struct Event {
 pub enum Error {
     #[error("failed to parse power event")]
     ParseError(#[from] ParseError),
+    #[error("failed to send key")]
+    SendFailed(win32::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -71,21 +82,43 @@ impl TryFrom<(win32::WPARAM, win32::LPARAM)> for Event {
 }
 
 impl Event {
-    pub fn to_owl_event(self) -> Option<os::Event> {
-        let owl_event = match *self.kind {
-            win32::WindowsAndMessaging::WM_KEYDOWN => os::Event::Press,
-            win32::WindowsAndMessaging::WM_KEYUP => os::Event::Release,
-            _ => return None,
-        };
-
-        let result = match *self.code {
-            win32::KeyboardAndMouse::VK_VOLUME_DOWN => owl_event(os::Key::VolumeDown),
-            win32::KeyboardAndMouse::VK_VOLUME_UP => owl_event(os::Key::VolumeUp),
-            win32::KeyboardAndMouse::VK_VOLUME_MUTE => owl_event(os::Key::VolumeMute),
-            _ => os::Event::Focus,
-        };
-
-        Some(result)
+    pub fn to_owl_event(self, raw_keys: &HashSet<os::KeyCode>) -> Option<os::Event> {
+        translate::key_event(self.code.0 .0, *self.kind, raw_keys)
+    }
+
+    /// Whether owl injected the event with [`send`].
+    pub fn is_injected(&self) -> bool {
+        self.context.dwExtraInfo == INJECTED_TAG
+    }
+}
+
+/// Injects a press or release of the key with virtual-key code `code`.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-sendinput>
+pub fn send(code: u16, pressed: bool) -> Result<(), Error> {
+    let input = win32::INPUT {
+        r#type: win32::INPUT_KEYBOARD,
+        Anonymous: win32::INPUT_0 {
+            ki: win32::KEYBDINPUT {
+                wVk: win32::VIRTUAL_KEY(code),
+                wScan: 0,
+                dwFlags: if pressed {
+                    win32::KEYBD_EVENT_FLAGS(0)
+                } else {
+                    win32::KEYEVENTF_KEYUP
+                },
+                time: 0,
+                dwExtraInfo: INJECTED_TAG,
+            },
+        },
+    };
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let sent = unsafe { win32::SendInput(&[input], mem::size_of::<win32::INPUT>() as i32) };
+    if sent == 1 {
+        Ok(())
+    } else {
+        Err(Error::SendFailed(win32::Error::from_win32()))
     }
 }
 
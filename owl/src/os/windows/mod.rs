@@ -1,25 +1,51 @@
+mod audio;
+mod capture;
+mod display;
 mod handlers;
 mod key;
 mod power;
+mod translate;
 mod window;
 
-use std::{sync::OnceLock, thread};
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
 
-use color_eyre::eyre::{eyre, Context, Result};
-use tokio::sync::{mpsc, oneshot};
+use ::cec::PhysicalAddress;
+use color_eyre::eyre::{eyre, Context, Report, Result};
+use tokio::{
+    sync::{mpsc, oneshot, Notify},
+    time::MissedTickBehavior,
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    channel,
     job::{self, Recv, SpawnResult},
     os::{self, windows::window::Window, Event, EventRx},
     Spawn,
 };
 
+/// How many errors are kept before the oldest are dropped.
+const ERROR_CAPACITY: usize = 64;
+/// How often to log how many errors were dropped.
+const ERROR_SUMMARY_INTERVAL: Duration = Duration::from_secs(30);
+/// How long the message loop has to dispatch its first heartbeat, after which
+/// its hooks are assumed dead.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Represents a Windows job, responsible for sending and receiving Windows
 /// events.
 pub struct Job {
     event_rx: EventRx,
+    /// Notified each time the message loop stops responding to heartbeats.
+    stalled: Arc<Notify>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -30,11 +56,20 @@ pub enum Error {
     PowerError(#[from] power::Error),
     #[error("window error")]
     WindowError(#[from] window::Error),
+    #[error("audio error")]
+    AudioError(#[from] audio::Error),
+    #[error("key capture error")]
+    CaptureError(#[from] capture::Error),
 }
 
 pub(crate) struct OwlHandle {
     pub err_tx: os::ErrorTx,
     pub event_tx: os::EventTx,
+    pub display_off_delay: Duration,
+    pub suppress_volume_keys: bool,
+    pub raw_keys: Arc<HashSet<os::KeyCode>>,
+    /// The last heartbeat the message loop dispatched.
+    pub heartbeat: Arc<AtomicUsize>,
 }
 
 /// A handle to owl.
@@ -51,25 +86,46 @@ pub(crate) struct OwlHandle {
 pub(crate) static OWL_HANDLE: OnceLock<OwlHandle> = OnceLock::new();
 
 impl Spawn for Job {
+    type Config = os::Config;
+
     /// Spawns a new Windows job. The job runs on a thread.
-    async fn spawn(run_token: CancellationToken) -> SpawnResult<Self> {
-        let (err_tx, err_rx) = mpsc::unbounded_channel::<Error>();
+    async fn spawn(run_token: CancellationToken, config: os::Config) -> SpawnResult<Self> {
+        let (err_tx, err_rx) = channel::drop_oldest::<Error>(ERROR_CAPACITY);
         let (event_tx, event_rx) = mpsc::unbounded_channel::<Event>();
         let (window_tx, window_rx) = oneshot::channel::<Window>();
         let (ready_tx, ready_rx) = oneshot::channel::<Result<()>>();
+        let heartbeat = Arc::new(AtomicUsize::new(0));
+        let window_heartbeat = heartbeat.clone();
 
         let _err_logger = tokio::spawn(async move {
             let mut err_rx = err_rx;
+            let mut summary = tokio::time::interval(ERROR_SUMMARY_INTERVAL);
             loop {
-                match err_rx.recv().await.ok_or_else(|| eyre!("event rx closed")) {
-                    Ok(err) => error!("os error occurred: {err}"),
-                    Err(e) => error!("failed to receive os error: {e:?}"),
+                #[allow(clippy::redundant_pub_crate)]
+                {
+                    tokio::select! {
+                        err = err_rx.recv() => match err {
+                            // The causes tell e.g. a null power event from a
+                            // malformed one.
+                            Some(err) => error!("os error occurred: {:#}", Report::new(err)),
+                            None => {
+                                error!("failed to receive os error: error rx closed");
+                                break;
+                            }
+                        },
+                        _ = summary.tick() => {
+                            let dropped = err_rx.take_dropped();
+                            if dropped > 0 {
+                                warn!("{dropped} os errors suppressed");
+                            }
+                        },
+                    }
                 }
             }
         });
 
         debug!("spawning os job...");
-        let join_handle = thread::spawn(move || {
+        let join_handle = job::spawn_thread("owl-os", move || {
             debug!("os job starting...");
 
             // Windows will get mad if you try to use resources outside the thread that
@@ -77,7 +133,14 @@ impl Spawn for Job {
             // with message passing. So, create the window in the job thread
             // then send it back to async land.
             job::send_ready_status(ready_tx, || {
-                match Window::new(err_tx.clone(), event_tx.clone()) {
+                match Window::new(
+                    err_tx.clone(),
+                    event_tx.clone(),
+                    config.display_off_delay,
+                    config.suppress_volume_keys,
+                    config.raw_keys,
+                    window_heartbeat,
+                ) {
                     Ok(x) => {
                         debug!("sending window handle to task...");
                         window_tx
@@ -88,9 +151,19 @@ impl Spawn for Job {
                 }
             })?;
 
+            // COM calls the watcher back on its own threads, so it only has to
+            // live as long as the message loop.
+            let _mute_watcher = if config.mirror_mute {
+                audio::MuteWatcher::new(event_tx)
+                    .inspect_err(|e| error!("failed to mirror mute state: {e}"))
+                    .ok()
+            } else {
+                None
+            };
+
             self::handlers::event_loop();
             Result::Ok(())
-        });
+        })?;
 
         ready_rx
             .await
@@ -103,39 +176,141 @@ impl Spawn for Job {
             .context("failed to receive window handle from job")?;
         debug!("received window handle from job!");
 
+        // The hooks were installed, but their events only flow once the
+        // message loop is dispatching.
+        wait_listening(&window, &heartbeat)
+            .await
+            .context("os hooks never started listening")?;
+        debug!("os hooks listening!");
+
         // Dropping the `Window` will stop the event loop, saving us having to poll.
-        let _watchdog = tokio::spawn(async move {
-            run_token.cancelled().await;
-            drop(window);
-        });
+        let stalled = Arc::new(Notify::new());
+        let _watchdog = tokio::spawn(watchdog(
+            run_token,
+            window,
+            heartbeat,
+            config.heartbeat_interval,
+            stalled.clone(),
+        ));
 
-        Ok((join_handle, Self { event_rx }))
+        Ok((join_handle, Self { event_rx, stalled }))
     }
 }
 
 impl Recv<Event> for Job {
     async fn recv(&mut self) -> Result<Event> {
-        self.event_rx
-            .recv()
-            .await
-            .ok_or_else(|| eyre!("event rx closed"))
+        #[allow(clippy::redundant_pub_crate)]
+        {
+            tokio::select! {
+                event = self.event_rx.recv() => event.ok_or_else(|| eyre!("event rx closed")),
+                () = self.stalled.notified() => {
+                    Err(eyre!("os message loop stopped responding, events are no longer received"))
+                },
+            }
+        }
+    }
+}
+
+/// Waits for the message loop to dispatch a heartbeat, proving it's running.
+async fn wait_listening(window: &Window, heartbeat: &AtomicUsize) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    let seq = heartbeat.load(Ordering::Relaxed).wrapping_add(1);
+    window.post_heartbeat(seq)?;
+    tokio::time::timeout(LISTEN_TIMEOUT, async {
+        while heartbeat.load(Ordering::Relaxed) != seq {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .map_err(|_| eyre!("message loop didn't respond within {LISTEN_TIMEOUT:?}"))
+}
+
+/// Owns the window until owl stops, meanwhile posting heartbeats to the
+/// message loop. A heartbeat not dispatched by the next one means the loop is
+/// wedged, e.g. a handler is stuck, and events are no longer received. The
+/// watchdog keeps waiting for it, in case the loop recovers.
+async fn watchdog(
+    run_token: CancellationToken,
+    window: Window,
+    heartbeat: Arc<AtomicUsize>,
+    interval: Duration,
+    stalled: Arc<Notify>,
+) {
+    if !interval.is_zero() {
+        let mut ticks = tokio::time::interval(interval);
+        // Ticks missed while the PC slept would otherwise fire in a burst,
+        // before the loop had a chance to dispatch the heartbeat.
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // Picks up from the heartbeat `wait_listening` posted.
+        let mut sent = heartbeat.load(Ordering::Relaxed);
+        let mut is_stalled = false;
+        loop {
+            #[allow(clippy::redundant_pub_crate)]
+            {
+                tokio::select! {
+                    () = run_token.cancelled() => break,
+                    _ = ticks.tick() => {
+                        if heartbeat.load(Ordering::Relaxed) != sent {
+                            if !is_stalled {
+                                error!("os message loop missed a heartbeat, events are no longer received");
+                                stalled.notify_one();
+                                is_stalled = true;
+                            }
+                            continue;
+                        }
+                        if is_stalled {
+                            info!("os message loop responds again");
+                            is_stalled = false;
+                        }
+
+                        sent = sent.wrapping_add(1);
+                        if let Err(e) = window.post_heartbeat(sent) {
+                            warn!("failed to post heartbeat: {e}");
+                        }
+                    },
+                }
+            }
+        }
     }
+
+    run_token.cancelled().await;
+    drop(window);
+}
+
+/// Returns the virtual-key code for a key name, like `F13`.
+pub fn key_code(name: &str) -> Option<u16> {
+    translate::key_code(name)
+}
+
+/// Presses or releases a key on the PC, as if it were pressed on a keyboard.
+/// owl's own keyboard hook ignores it.
+pub fn send_key(key: os::Key, pressed: bool) -> Result<(), Error> {
+    key::send(translate::virtual_key(key), pressed).map_err(Error::from)
+}
+
+/// Prints the virtual-key code and name of each key pressed, until Esc.
+pub fn list_keys() -> Result<(), Error> {
+    capture::run().map_err(Error::from)
 }
 
-pub(crate) fn send_err(err_tx: &os::ErrorTx, err: os::Error) {
-    trace!("relaying error: {err:?}");
-    if let Err(e) = err_tx.send(err) {
-        error!("failed to relay err: {e}");
-    };
+/// Returns the physical address the HDMI display assigned to the PC, read
+/// from the display's EDID.
+pub fn hdmi_address() -> Option<PhysicalAddress> {
+    display::hdmi_address().unwrap_or_else(|e| {
+        warn!("failed to read the hdmi display's edid: {e}");
+        None
+    })
 }
 
-pub(crate) fn send_event(event_tx: &os::EventTx, event: os::Event) {
-    trace!("relaying event: {event:?}");
-    if let Err(e) = event_tx.send(event) {
-        error!("failed to relay event: {event:?}: {e}");
-    };
+/// Checks the OS hooks can be installed. Windows needs no permission for
+/// them.
+pub const fn preflight() -> Result<(), os::MissingPermission> {
+    Ok(())
 }
 
+pub(crate) use crate::os::{send_err, send_event};
+
 macro_rules! get_owl_handle {
     ($on_err:expr) => {{
         use tracing::error;
@@ -146,6 +321,10 @@ macro_rules! get_owl_handle {
             Some(x) => OwlHandle {
                 err_tx: x.err_tx.clone(),
                 event_tx: x.event_tx.clone(),
+                display_off_delay: x.display_off_delay,
+                suppress_volume_keys: x.suppress_volume_keys,
+                raw_keys: x.raw_keys.clone(),
+                heartbeat: x.heartbeat.clone(),
             },
             None => {
                 error!("owl state unset");
@@ -1,11 +1,14 @@
-use std::ptr;
+use std::{mem, ptr};
 
 mod win32 {
     pub use windows::{
         core::GUID,
         Win32::{
             Foundation::LPARAM,
-            System::{Power::POWERBROADCAST_SETTING, SystemServices::MONITOR_DISPLAY_STATE},
+            System::{
+                Power::POWERBROADCAST_SETTING,
+                SystemServices::{GUID_CONSOLE_DISPLAY_STATE, MONITOR_DISPLAY_STATE},
+            },
         },
     };
 }
@@ -20,16 +23,34 @@ pub enum Error {
     ParseError(#[from] ParseError),
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
 pub enum ParseError {
     #[error("power settings are null")]
     NullPowerSettings,
+    #[error("power setting carries no data")]
+    EmptyData,
+    #[error("power setting data is {0} bytes, expected {1}")]
+    UnexpectedDataLength(u32, u32),
 }
 
 impl Event {
+    /// Returns the console display state, `None` if the event is about
+    /// another setting.
+    ///
     /// See: <https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/ne-wdm-_monitor_display_state>
-    pub fn state(&self) -> win32::MONITOR_DISPLAY_STATE {
-        win32::MONITOR_DISPLAY_STATE(i32::from(self.Data[0]))
+    pub fn display_state(&self) -> Result<Option<win32::MONITOR_DISPLAY_STATE>, ParseError> {
+        // The state is a `DWORD`, whose low byte holds every known state.
+        #[allow(clippy::cast_possible_truncation)]
+        const STATE_LEN: u32 = mem::size_of::<u32>() as u32;
+
+        if self.target() != win32::GUID_CONSOLE_DISPLAY_STATE {
+            return Ok(None);
+        }
+        if self.DataLength != STATE_LEN {
+            return Err(ParseError::UnexpectedDataLength(self.DataLength, STATE_LEN));
+        }
+
+        Ok(Some(win32::MONITOR_DISPLAY_STATE(i32::from(self.Data[0]))))
     }
 
     /// See: <https://learn.microsoft.com/en-us/windows/win32/power/power-setting-guids>
@@ -46,10 +67,67 @@ impl TryFrom<win32::LPARAM> for Event {
         let power_settings =
             ptr::with_exposed_provenance::<win32::POWERBROADCAST_SETTING>(value.0 as usize);
 
-        if !power_settings.is_null() {
+        if power_settings.is_null() {
             return Err(ParseError::NullPowerSettings);
         }
 
-        Ok(Self(unsafe { *power_settings }))
+        let event = Self(unsafe { *power_settings });
+        if event.DataLength == 0 {
+            return Err(ParseError::EmptyData);
+        }
+
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setting(target: win32::GUID, len: u32, data: u8) -> win32::POWERBROADCAST_SETTING {
+        win32::POWERBROADCAST_SETTING {
+            PowerSetting: target,
+            DataLength: len,
+            Data: [data],
+        }
+    }
+
+    fn parse(setting: &win32::POWERBROADCAST_SETTING) -> Result<Event, ParseError> {
+        #[allow(clippy::cast_possible_wrap)]
+        let lparam = win32::LPARAM(ptr::from_ref(setting).expose_provenance() as isize);
+        Event::try_from(lparam)
+    }
+
+    #[test]
+    fn test_null() {
+        assert!(matches!(
+            Event::try_from(win32::LPARAM(0)),
+            Err(ParseError::NullPowerSettings)
+        ));
+    }
+
+    #[test]
+    fn test_empty_data() {
+        let setting = setting(win32::GUID_CONSOLE_DISPLAY_STATE, 0, 0);
+        assert!(matches!(parse(&setting), Err(ParseError::EmptyData)));
+    }
+
+    #[test]
+    fn test_display_state() -> Result<(), ParseError> {
+        let off = setting(win32::GUID_CONSOLE_DISPLAY_STATE, 4, 0);
+        assert_eq!(
+            parse(&off)?.display_state()?,
+            Some(win32::MONITOR_DISPLAY_STATE(0))
+        );
+
+        let short = setting(win32::GUID_CONSOLE_DISPLAY_STATE, 1, 0);
+        assert_eq!(
+            parse(&short)?.display_state(),
+            Err(ParseError::UnexpectedDataLength(1, 4))
+        );
+
+        let other = setting(win32::GUID::zeroed(), 1, 0);
+        assert_eq!(parse(&other)?.display_state()?, None);
+        Ok(())
     }
 }
@@ -0,0 +1,312 @@
+//! Translation of raw Windows events into owl events. These are plain
+//! functions of plain inputs so the mapping can be tested without any hooks.
+
+use std::collections::HashSet;
+
+use crate::os;
+
+mod win32 {
+    pub use windows::Win32::{
+        System::SystemServices,
+        UI::{Input::KeyboardAndMouse, WindowsAndMessaging},
+    };
+}
+
+/// Translates a key event into an owl event, given the virtual-key code and
+/// the event kind ([`WM_KEYDOWN`] or [`WM_KEYUP`]). Presses of `raw_keys`
+/// translate into [`os::Event::RawKey`] and their releases into nothing, keys
+/// other than the volume keys translate into [`os::Event::Focus`], the monitor
+/// left for the caller to find.
+///
+/// [`WM_KEYDOWN`]: https://learn.microsoft.com/en-us/windows/win32/inputdev/wm-keydown
+/// [`WM_KEYUP`]: https://learn.microsoft.com/en-us/windows/win32/inputdev/wm-keyup
+pub fn key_event(code: u16, kind: u32, raw_keys: &HashSet<os::KeyCode>) -> Option<os::Event> {
+    let owl_event = match kind {
+        win32::WindowsAndMessaging::WM_KEYDOWN => os::Event::Press,
+        win32::WindowsAndMessaging::WM_KEYUP => os::Event::Release,
+        _ => return None,
+    };
+
+    if raw_keys.contains(&os::KeyCode(code)) {
+        return (kind == win32::WindowsAndMessaging::WM_KEYDOWN)
+            .then_some(os::Event::RawKey(os::KeyCode(code)));
+    }
+
+    let event = match key(code) {
+        Some(key) => owl_event(key),
+        None => os::Event::Focus(None),
+    };
+
+    Some(event)
+}
+
+/// Translates a monitor's device name, like `\\.\DISPLAY2`, into an owl
+/// monitor.
+pub fn monitor(device: &str) -> Option<os::Monitor> {
+    device
+        .strip_prefix(r"\\.\DISPLAY")?
+        .parse()
+        .ok()
+        .map(os::Monitor)
+}
+
+/// Translates a virtual-key code into an owl key.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes>
+pub const fn key(code: u16) -> Option<os::Key> {
+    match win32::KeyboardAndMouse::VIRTUAL_KEY(code) {
+        win32::KeyboardAndMouse::VK_VOLUME_DOWN => Some(os::Key::VolumeDown),
+        win32::KeyboardAndMouse::VK_VOLUME_UP => Some(os::Key::VolumeUp),
+        win32::KeyboardAndMouse::VK_VOLUME_MUTE => Some(os::Key::VolumeMute),
+        _ => None,
+    }
+}
+
+/// Translates an owl key into its virtual-key code, the inverse of [`key`].
+pub const fn virtual_key(key: os::Key) -> u16 {
+    match key {
+        os::Key::VolumeDown => win32::KeyboardAndMouse::VK_VOLUME_DOWN.0,
+        os::Key::VolumeUp => win32::KeyboardAndMouse::VK_VOLUME_UP.0,
+        os::Key::VolumeMute => win32::KeyboardAndMouse::VK_VOLUME_MUTE.0,
+    }
+}
+
+/// The keys known by name besides `F1` to `F24`, see [`key_code`].
+const KEY_NAMES: [(&str, win32::KeyboardAndMouse::VIRTUAL_KEY); 8] = {
+    use win32::KeyboardAndMouse as vk;
+
+    [
+        ("media-play-pause", vk::VK_MEDIA_PLAY_PAUSE),
+        ("media-stop", vk::VK_MEDIA_STOP),
+        ("media-next-track", vk::VK_MEDIA_NEXT_TRACK),
+        ("media-prev-track", vk::VK_MEDIA_PREV_TRACK),
+        ("browser-back", vk::VK_BROWSER_BACK),
+        ("browser-forward", vk::VK_BROWSER_FORWARD),
+        ("browser-home", vk::VK_BROWSER_HOME),
+        ("launch-media-select", vk::VK_LAUNCH_MEDIA_SELECT),
+    ]
+};
+
+/// Translates a key name, like `F13` or `media-play-pause`, into a
+/// virtual-key code. Names are case insensitive.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes>
+pub fn key_code(name: &str) -> Option<u16> {
+    use win32::KeyboardAndMouse as vk;
+
+    let name = name.to_ascii_lowercase();
+    if let Some(n) = name.strip_prefix('f').and_then(|x| x.parse::<u16>().ok())
+        && (1..=24).contains(&n)
+    {
+        return Some(vk::VK_F1.0 + n - 1);
+    }
+
+    KEY_NAMES
+        .iter()
+        .find(|(x, _)| *x == name)
+        .map(|(_, code)| code.0)
+}
+
+/// Translates a virtual-key code into the name [`key_code`] takes for it,
+/// `None` if it has none.
+pub fn key_name(code: u16) -> Option<String> {
+    use win32::KeyboardAndMouse as vk;
+
+    if (vk::VK_F1.0..=vk::VK_F24.0).contains(&code) {
+        return Some(format!("F{}", code - vk::VK_F1.0 + 1));
+    }
+
+    KEY_NAMES
+        .iter()
+        .find(|(_, x)| x.0 == code)
+        .map(|(name, _)| (*name).to_owned())
+}
+
+/// Returns whether the key should be hidden from the rest of the system.
+///
+/// Unless volume events are suppressed, they'll operate as normal. This isn't
+/// desirable since we're trying to replace software mixing with hardware
+/// mixing. The software mixer works by reducing audio bit-depth to make the
+/// audio quieter, at the expense of audio quality.
+pub const fn is_suppressed(code: u16) -> bool {
+    key(code).is_some()
+}
+
+/// Translates a power-management event into an owl event.
+///
+/// See: <https://learn.microsoft.com/en-us/windows/win32/power/wm-powerbroadcast>
+pub const fn power_event(msg: u32) -> Option<os::Event> {
+    match msg {
+        // See: https://learn.microsoft.com/en-us/windows/win32/power/pbt-apmresumeautomatic
+        win32::WindowsAndMessaging::PBT_APMRESUMEAUTOMATIC => Some(os::Event::Resume),
+        // See: https://learn.microsoft.com/en-us/windows/win32/power/pbt-apmsuspend
+        win32::WindowsAndMessaging::PBT_APMSUSPEND => Some(os::Event::Suspend),
+        _ => None,
+    }
+}
+
+/// Translates [`WM_ENDSESSION`] into an owl event, given whether the session
+/// is actually ending. Another application may cancel the shutdown after
+/// [`WM_QUERYENDSESSION`], so only the former is acted on.
+///
+/// [`WM_ENDSESSION`]: https://learn.microsoft.com/en-us/windows/win32/shutdown/wm-endsession
+/// [`WM_QUERYENDSESSION`]: https://learn.microsoft.com/en-us/windows/win32/shutdown/wm-queryendsession
+pub const fn end_session(ending: usize) -> Option<os::Event> {
+    match ending {
+        0 => None,
+        _ => Some(os::Event::Shutdown),
+    }
+}
+
+/// What to do in response to a console display state change. Suspending is
+/// deferred until the display has stayed off for a while, since some displays
+/// bounce between off and on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayAction {
+    ScheduleSuspend,
+    CancelSuspend,
+}
+
+/// Translates a console display state change into an action.
+///
+/// See: <https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/ne-wdm-_monitor_display_state>
+pub const fn display_state(state: i32) -> Option<DisplayAction> {
+    match win32::SystemServices::MONITOR_DISPLAY_STATE(state) {
+        win32::SystemServices::PowerMonitorOff => Some(DisplayAction::ScheduleSuspend),
+        win32::SystemServices::PowerMonitorOn => Some(DisplayAction::CancelSuspend),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VK_VOLUME_UP: u16 = win32::KeyboardAndMouse::VK_VOLUME_UP.0;
+    const VK_VOLUME_DOWN: u16 = win32::KeyboardAndMouse::VK_VOLUME_DOWN.0;
+    const VK_VOLUME_MUTE: u16 = win32::KeyboardAndMouse::VK_VOLUME_MUTE.0;
+    const VK_A: u16 = win32::KeyboardAndMouse::VK_A.0;
+    const WM_KEYDOWN: u32 = win32::WindowsAndMessaging::WM_KEYDOWN;
+    const WM_KEYUP: u32 = win32::WindowsAndMessaging::WM_KEYUP;
+    const WM_SYSKEYDOWN: u32 = win32::WindowsAndMessaging::WM_SYSKEYDOWN;
+
+    #[test]
+    fn test_volume_keys() {
+        for (code, key) in [
+            (VK_VOLUME_UP, os::Key::VolumeUp),
+            (VK_VOLUME_DOWN, os::Key::VolumeDown),
+            (VK_VOLUME_MUTE, os::Key::VolumeMute),
+        ] {
+            assert_eq!(
+                key_event(code, WM_KEYDOWN, &HashSet::new()),
+                Some(os::Event::Press(key))
+            );
+            assert_eq!(
+                key_event(code, WM_KEYUP, &HashSet::new()),
+                Some(os::Event::Release(key))
+            );
+            assert!(is_suppressed(code));
+            assert_eq!(virtual_key(key), code);
+        }
+    }
+
+    #[test]
+    fn test_other_keys_focus() {
+        assert_eq!(
+            key_event(VK_A, WM_KEYDOWN, &HashSet::new()),
+            Some(os::Event::Focus(None))
+        );
+        assert_eq!(
+            key_event(VK_A, WM_KEYUP, &HashSet::new()),
+            Some(os::Event::Focus(None))
+        );
+        assert!(!is_suppressed(VK_A));
+    }
+
+    #[test]
+    fn test_raw_keys() {
+        let raw_keys = HashSet::from([os::KeyCode(VK_A)]);
+        assert_eq!(
+            key_event(VK_A, WM_KEYDOWN, &raw_keys),
+            Some(os::Event::RawKey(os::KeyCode(VK_A)))
+        );
+        assert_eq!(key_event(VK_A, WM_KEYUP, &raw_keys), None);
+    }
+
+    #[test]
+    fn test_key_code() {
+        assert_eq!(key_code("F13"), Some(win32::KeyboardAndMouse::VK_F13.0));
+        assert_eq!(key_code("f1"), Some(win32::KeyboardAndMouse::VK_F1.0));
+        assert_eq!(
+            key_code("media-play-pause"),
+            Some(win32::KeyboardAndMouse::VK_MEDIA_PLAY_PAUSE.0)
+        );
+        assert_eq!(key_code("f25"), None);
+        assert_eq!(key_code("toaster"), None);
+    }
+
+    #[test]
+    fn test_key_name() {
+        assert_eq!(key_name(0x7C).as_deref(), Some("F13"));
+        assert_eq!(key_name(VK_A), None);
+        for code in (0..=u16::from(u8::MAX)).filter(|x| key_name(*x).is_some()) {
+            assert_eq!(key_name(code).and_then(|x| key_code(&x)), Some(code));
+        }
+        for (name, code) in KEY_NAMES {
+            assert_eq!(key_name(code.0).as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn test_unknown_key_kind() {
+        assert_eq!(
+            key_event(VK_VOLUME_UP, WM_SYSKEYDOWN, &HashSet::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_power_events() {
+        assert_eq!(
+            power_event(win32::WindowsAndMessaging::PBT_APMSUSPEND),
+            Some(os::Event::Suspend)
+        );
+        assert_eq!(
+            power_event(win32::WindowsAndMessaging::PBT_APMRESUMEAUTOMATIC),
+            Some(os::Event::Resume)
+        );
+        assert_eq!(
+            power_event(win32::WindowsAndMessaging::PBT_POWERSETTINGCHANGE),
+            None
+        );
+    }
+
+    #[test]
+    fn test_monitor() {
+        assert_eq!(monitor(r"\\.\DISPLAY2"), Some(os::Monitor(2)));
+        assert_eq!(monitor(r"\\.\DISPLAY"), None);
+        assert_eq!(monitor("DISPLAY2"), None);
+    }
+
+    #[test]
+    fn test_end_session() {
+        assert_eq!(end_session(1), Some(os::Event::Shutdown));
+        assert_eq!(end_session(0), None);
+    }
+
+    #[test]
+    fn test_display_state() {
+        assert_eq!(
+            display_state(win32::SystemServices::PowerMonitorOff.0),
+            Some(DisplayAction::ScheduleSuspend)
+        );
+        assert_eq!(
+            display_state(win32::SystemServices::PowerMonitorOn.0),
+            Some(DisplayAction::CancelSuspend)
+        );
+        assert_eq!(
+            display_state(win32::SystemServices::PowerMonitorDim.0),
+            None
+        );
+    }
+}
@@ -1,13 +1,18 @@
-use std::ptr;
+use std::{
+    collections::HashSet,
+    ptr,
+    sync::{atomic::AtomicUsize, Arc},
+    time::Duration,
+};
 
-use tracing::debug;
+use tracing::{debug, error};
 
 use crate::os::{
     self,
     windows::{
         get_owl_handle,
-        handlers::{handle_low_level_key_event, handle_window_event},
-        send_err, OwlHandle, OWL_HANDLE,
+        handlers::{handle_low_level_key_event, handle_window_event, WM_HEARTBEAT},
+        OwlHandle, OWL_HANDLE,
     },
 };
 
@@ -34,6 +39,16 @@ pub struct Window {
     key_hook: win32::HHOOK,
     /// See: <https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-registerpowersettingnotification>
     power_notify: win32::HPOWERNOTIFY,
+    /// The resources already released, so none is released twice.
+    released: Vec<Resource>,
+}
+
+/// A resource the window holds, released in [`Resource::RELEASE_ORDER`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resource {
+    KeyHook,
+    PowerNotify,
+    Window,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -61,6 +76,9 @@ pub enum Error {
     #[error("failed to initialize global hook")]
     InitHookFailed(win32::Error),
 
+    #[error("{0} handle is invalid")]
+    InvalidHandle(&'static str),
+
     #[error("failed to send message to window")]
     PostWindowFailed(win32::Error),
 
@@ -74,9 +92,23 @@ pub enum Error {
 impl Window {
     const WINDOW_CLASS: win32::PCWSTR = win32::w!("window");
 
-    pub fn new(err_tx: os::ErrorTx, event_tx: os::EventTx) -> Result<Self, Error> {
+    pub fn new(
+        err_tx: os::ErrorTx,
+        event_tx: os::EventTx,
+        display_off_delay: Duration,
+        suppress_volume_keys: bool,
+        raw_keys: HashSet<os::KeyCode>,
+        heartbeat: Arc<AtomicUsize>,
+    ) -> Result<Self, Error> {
         OWL_HANDLE
-            .set(OwlHandle { err_tx, event_tx })
+            .set(OwlHandle {
+                err_tx,
+                event_tx,
+                display_off_delay,
+                suppress_volume_keys,
+                raw_keys: Arc::new(raw_keys),
+                heartbeat,
+            })
             .map_err(|_| Error::OwlHandleInitFailed)?;
 
         debug!("creating window...");
@@ -85,15 +117,100 @@ impl Window {
         let window = Self::new_window(module)?;
         let key_hook = Self::new_key_hook(module)?;
         let power_notify = Self::new_power_notify(window)?;
+        // Either being null would leave owl deaf to events without an error.
+        let invalid = if key_hook.is_invalid() {
+            Some("key hook")
+        } else if power_notify.is_invalid() {
+            Some("power notification")
+        } else {
+            None
+        };
+        if let Some(handle) = invalid {
+            Self::abandon(window, key_hook, power_notify);
+            return Err(Error::InvalidHandle(handle));
+        }
         debug!("window created!");
 
         Ok(Self {
             handle: window,
             key_hook,
             power_notify,
+            released: Vec::new(),
+        })
+    }
+
+    /// Releases the resources not yet released, in
+    /// [`Resource::RELEASE_ORDER`]. Every resource is attempted even if an
+    /// earlier one fails, returning the first error.
+    fn release(&mut self) -> Result<(), Error> {
+        let (handle, key_hook, power_notify) = (self.handle, self.key_hook, self.power_notify);
+        release(&mut self.released, |resource| match resource {
+            // See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-unhookwindowshookex
+            Resource::KeyHook => {
+                debug!("unregistering key hook...");
+                unsafe { win32::WindowsAndMessaging::UnhookWindowsHookEx(key_hook) }
+                    .map_err(Error::DropHookFailed)
+            }
+            // See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-unregisterpowersettingnotification
+            Resource::PowerNotify => {
+                debug!("unregistering power notifications...");
+                unsafe { win32::Power::UnregisterPowerSettingNotification(power_notify) }
+                    .map_err(Error::DropPowerSettingNotificationFailed)
+            }
+            // See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postmessagew
+            Resource::Window => {
+                debug!("requesting the window be closed...");
+                unsafe {
+                    win32::WindowsAndMessaging::PostMessageW(
+                        handle,
+                        win32::WindowsAndMessaging::WM_CLOSE,
+                        win32::WPARAM::default(),
+                        win32::LPARAM::default(),
+                    )
+                }
+                .map_err(Error::PostWindowFailed)
+            }
         })
     }
 
+    /// Releases what [`Window::new`] acquired when it can't go on. The message
+    /// loop isn't running yet, so the window is destroyed directly rather than
+    /// closed. Errors are only logged, the caller is already failing.
+    fn abandon(window: win32::HWND, key_hook: win32::HHOOK, power_notify: win32::HPOWERNOTIFY) {
+        debug!("abandoning window...");
+        unsafe {
+            if !key_hook.is_invalid()
+                && let Err(e) = win32::WindowsAndMessaging::UnhookWindowsHookEx(key_hook)
+            {
+                error!("failed to unregister key hook: {e}");
+            }
+            if !power_notify.is_invalid()
+                && let Err(e) = win32::Power::UnregisterPowerSettingNotification(power_notify)
+            {
+                error!("failed to unregister power notifications: {e}");
+            }
+            if let Err(e) = win32::WindowsAndMessaging::DestroyWindow(window) {
+                error!("failed to destroy window: {e}");
+            }
+        }
+    }
+
+    /// Posts heartbeat `seq` to the message loop, which records it once
+    /// dispatched.
+    ///
+    /// See: <https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postmessagew>
+    pub fn post_heartbeat(&self, seq: usize) -> Result<(), Error> {
+        unsafe {
+            win32::WindowsAndMessaging::PostMessageW(
+                self.handle,
+                WM_HEARTBEAT,
+                win32::WPARAM(seq),
+                win32::LPARAM::default(),
+            )
+        }
+        .map_err(Error::PostWindowFailed)
+    }
+
     /// See: <https://learn.microsoft.com/en-us/windows/win32/api/libloaderapi/nf-libloaderapi-getmodulehandlew>
     fn module_handle() -> Result<win32::HMODULE, Error> {
         debug!("getting module handle...");
@@ -187,46 +304,103 @@ impl Window {
     }
 }
 
-impl Drop for Window {
-    fn drop(&mut self) {
-        let inner = |window: &mut Self| -> Result<(), Error> {
-            // See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postmessagew
-            debug!("requesting the window be closed...");
-            unsafe {
-                win32::WindowsAndMessaging::PostMessageW(
-                    window.handle,
-                    win32::WindowsAndMessaging::WM_CLOSE,
-                    win32::WPARAM::default(),
-                    win32::LPARAM::default(),
-                )
-                .map_err(Error::PostWindowFailed)?;
-            };
+impl Resource {
+    /// The order resources are released in:
+    ///
+    /// 1. The key hook, first and regardless of later failures, since a global
+    ///    hook left behind slows every keypress on the system.
+    /// 2. The power notification, which is registered against the window so
+    ///    must go while the window still exists.
+    /// 3. The window, closed last. Closing it stops the message loop, and with
+    ///    it the hook and notification handlers.
+    const RELEASE_ORDER: [Self; 3] = [Self::KeyHook, Self::PowerNotify, Self::Window];
+}
 
-            // See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-unregisterpowersettingnotification
-            debug!("unregistering power notifications...");
-            unsafe {
-                win32::Power::UnregisterPowerSettingNotification(window.power_notify)
-                    .map_err(Error::DropPowerSettingNotificationFailed)?;
-            };
+/// Releases each resource not in `released` with `release_one`, in
+/// [`Resource::RELEASE_ORDER`], marking it released even if that failed, as
+/// retrying a failed release isn't any more likely to succeed. Returns the
+/// first error, logging any later ones.
+fn release(
+    released: &mut Vec<Resource>,
+    mut release_one: impl FnMut(Resource) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut result = Ok(());
+    for resource in Resource::RELEASE_ORDER {
+        if released.contains(&resource) {
+            continue;
+        }
 
-            // See: https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-unhookwindowshookex
-            debug!("unregistering key hook...");
-            unsafe {
-                win32::WindowsAndMessaging::UnhookWindowsHookEx(window.key_hook)
-                    .map_err(Error::DropHookFailed)?;
-            };
-            Ok(())
-        };
+        released.push(resource);
+        if let Err(e) = release_one(resource) {
+            if result.is_ok() {
+                result = Err(e);
+            } else {
+                error!("failed to release {resource:?}: {e}");
+            }
+        }
+    }
 
+    result
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
         debug!("dropping window...");
-        if let Err(e) = inner(self) {
-            let OwlHandle {
-                err_tx,
-                event_tx: _,
-            } = get_owl_handle!(|| {});
-            send_err(&err_tx, e.into());
+        if let Err(e) = self.release() {
+            // Dropped from the watchdog task rather than a hook, the channel
+            // never blocks anyway.
+            let OwlHandle { err_tx, .. } = get_owl_handle!(|| {});
+            err_tx.send(e.into());
         }
     }
 }
 
 unsafe impl Send for Window {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_order() {
+        let mut released = Vec::new();
+        let mut calls = Vec::new();
+        let result = release(&mut released, |x| {
+            calls.push(x);
+            Ok(())
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(calls, Resource::RELEASE_ORDER);
+    }
+
+    #[test]
+    fn test_release_continues_after_failure() {
+        let mut released = Vec::new();
+        let mut calls = Vec::new();
+        let result = release(&mut released, |x| {
+            calls.push(x);
+            match x {
+                Resource::KeyHook => Err(Error::InitFailed),
+                _ => Ok(()),
+            }
+        });
+
+        assert!(matches!(result, Err(Error::InitFailed)));
+        assert_eq!(calls, Resource::RELEASE_ORDER);
+    }
+
+    #[test]
+    fn test_release_idempotent() {
+        let mut released = vec![Resource::KeyHook];
+        let mut calls = Vec::new();
+        let mut release_one = |x: Resource| -> Result<(), Error> {
+            calls.push(x);
+            Ok(())
+        };
+
+        assert!(release(&mut released, &mut release_one).is_ok());
+        assert!(release(&mut released, &mut release_one).is_ok());
+        assert_eq!(calls, [Resource::PowerNotify, Resource::Window]);
+    }
+}
@@ -0,0 +1,161 @@
+//! A JSON log of every stage an OS event passes on its way to the HDMI-CEC
+//! bus.
+//!
+//! It's for following a single keypress end to end, like when a volume press
+//! never reaches the audio system. Logging is a no-op unless enabled with
+//! `--trace-pipeline`.
+//!
+//! Each line is a record, like
+//! `{"id":3,"time-ms":1700000000000,"stage":"sent","command":"press:volume-up",
+//! ...}`. Records caused by the same OS event share its `id`, commands owl
+//! sends by itself, like timers, have none.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{LineWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{Context, Result};
+use serde::Serialize;
+use tracing::error;
+
+use crate::{cec::Command, os::Event};
+
+static LOG: OnceLock<Mutex<LineWriter<File>>> = OnceLock::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A stage of the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "stage", rename_all = "kebab-case")]
+pub enum Stage {
+    /// The OS job reported an event.
+    Received { event: String },
+    /// The event translated into commands, none if it isn't mapped.
+    Translated { commands: Vec<String> },
+    /// The HDMI-CEC job took a command, dropping it if it repeated another
+    /// too soon.
+    Debounce { command: String, dropped: bool },
+    /// The HDMI-CEC job dispatched a command.
+    Sent {
+        command: String,
+        /// Why dispatching failed, `None` if it succeeded.
+        error: Option<String>,
+        attempts: u8,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Record<'a> {
+    id: Option<u64>,
+    time_ms: u64,
+    #[serde(flatten)]
+    stage: &'a Stage,
+}
+
+/// Starts appending records to the log at `path`.
+pub fn enable(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open pipeline log `{}`", path.display()))?;
+    if LOG.set(Mutex::new(LineWriter::new(file))).is_err() {
+        error!("pipeline log already enabled");
+    }
+
+    Ok(())
+}
+
+/// Records the OS reporting `event`, returning the id its later records
+/// share, `None` if logging is disabled.
+pub fn received(event: Event) -> Option<u64> {
+    LOG.get()?;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    record(
+        Some(id),
+        &Stage::Received {
+            event: event.to_string(),
+        },
+    );
+    Some(id)
+}
+
+/// Records the event `id` translating into `cmds`, which will be sent to the
+/// HDMI-CEC job along with `id`, see [`crate::cec::CommandTx`].
+pub fn translated(id: Option<u64>, cmds: &[Command]) {
+    let Some(id) = id else { return };
+    record(
+        Some(id),
+        &Stage::Translated {
+            commands: cmds.iter().map(ToString::to_string).collect(),
+        },
+    );
+}
+
+/// Appends a record of `stage` to the log, if logging is enabled.
+pub(crate) fn record(id: Option<u64>, stage: &Stage) {
+    let Some(log) = LOG.get() else { return };
+    let line = match to_line(id, SystemTime::now(), stage) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("failed to serialize pipeline record: {e}");
+            return;
+        }
+    };
+
+    match log.lock() {
+        Ok(mut log) => {
+            if let Err(e) = writeln!(log, "{line}") {
+                error!("failed to write pipeline log: {e}");
+            }
+        }
+        Err(e) => error!("failed to lock pipeline log: {e}"),
+    }
+}
+
+/// Returns whether logging is enabled, so callers can skip building records.
+pub(crate) fn enabled() -> bool {
+    LOG.get().is_some()
+}
+
+fn to_line(id: Option<u64>, time: SystemTime, stage: &Stage) -> serde_json::Result<String> {
+    let time_ms = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    serde_json::to_string(&Record {
+        id,
+        time_ms: u64::try_from(time_ms).unwrap_or(u64::MAX),
+        stage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::cec::Button;
+
+    #[test]
+    fn test_to_line() -> serde_json::Result<()> {
+        let time = UNIX_EPOCH + Duration::from_millis(1532);
+        let stage = Stage::Sent {
+            command: Command::Press(Button::VolumeUp).to_string(),
+            error: None,
+            attempts: 1,
+        };
+        assert_eq!(
+            to_line(Some(3), time, &stage)?,
+            r#"{"id":3,"time-ms":1532,"stage":"sent","command":"press:volume-up","error":null,"attempts":1}"#
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! An interactive prompt sending commands over one HDMI-CEC connection, for
+//! exploring how a TV behaves.
+
+use color_eyre::eyre::{Context, Result};
+use rustyline::{error::ReadlineError, DefaultEditor};
+use tracing::warn;
+
+use crate::{
+    cec::{self, Button, Command},
+    cli::Frame,
+    topology::Topology,
+};
+
+const HELP: &str = "\
+commands:
+    on, off       power the tv on or off
+    focus         switch the tv to the pc
+    vol+, vol-    press and release a volume key
+    mute, unmute  mute or unmute the audio system
+    scan          print the devices on the bus
+    osd <text>    show text on the tv
+    raw <frame>   send a raw frame, like `40:04`
+    help          print this
+    quit          exit";
+
+/// A line entered at the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Input {
+    /// Commands sent in turn, like a press then its release.
+    Commands(Vec<Command>),
+    Scan,
+    Osd(String),
+    Raw(Frame),
+    Help,
+    Quit,
+}
+
+/// Connects to the HDMI-CEC bus, then runs commands entered at the prompt
+/// until `quit`, Ctrl+C or Ctrl+D.
+pub fn run(config: &cec::Config) -> Result<()> {
+    let mut session = cec::connect_session(config)?;
+    let mut editor = DefaultEditor::new().context("failed to start line editor")?;
+    println!("connected, type `help` for commands");
+
+    loop {
+        let line = match editor.readline("owl> ") {
+            Ok(x) => x,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e).context("failed to read line"),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Err(e) = editor.add_history_entry(line.as_str()) {
+            warn!("failed to add line to history: {e}");
+        }
+
+        let input = match parse_line(&line) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("{e}");
+                continue;
+            }
+        };
+        let result = match input {
+            Input::Commands(cmds) => cmds.into_iter().try_for_each(|x| session.send(x)),
+            Input::Scan => session.scan().map(|x| print!("{}", Topology::new(x))),
+            Input::Osd(text) => session.set_osd_string(&text),
+            Input::Raw(frame) => session.transmit_raw(&frame.0),
+            Input::Help => {
+                println!("{HELP}");
+                Ok(())
+            }
+            Input::Quit => break,
+        };
+
+        match result {
+            Ok(()) => println!("ok"),
+            Err(e) => println!("{e:#}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Result<Input, String> {
+    let press = |button| Input::Commands(vec![Command::Press(button), Command::Release(button)]);
+    let (word, rest) = line
+        .trim()
+        .split_once(' ')
+        .map_or((line.trim(), ""), |(x, rest)| (x, rest.trim()));
+
+    let input = match (word, rest) {
+        ("on", "") => Input::Commands(vec![Command::PowerOn]),
+        ("off", "") => Input::Commands(vec![Command::PowerOff]),
+        ("focus", "") => Input::Commands(vec![Command::Focus]),
+        ("vol+", "") => press(Button::VolumeUp),
+        ("vol-", "") => press(Button::VolumeDown),
+        ("mute", "") => Input::Commands(vec![Command::SetMute(true)]),
+        ("unmute", "") => Input::Commands(vec![Command::SetMute(false)]),
+        ("scan", "") => Input::Scan,
+        ("osd", text) if !text.is_empty() => Input::Osd(text.to_owned()),
+        ("raw", frame) if !frame.is_empty() => Input::Raw(frame.parse()?),
+        ("help", "") => Input::Help,
+        ("quit" | "exit", "") => Input::Quit,
+        _ => {
+            return Err(format!(
+                "unknown command `{}`, type `help` for commands",
+                line.trim()
+            ))
+        }
+    };
+
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() -> Result<(), String> {
+        assert_eq!(
+            parse_line(" vol+ ")?,
+            Input::Commands(vec![
+                Command::Press(Button::VolumeUp),
+                Command::Release(Button::VolumeUp)
+            ])
+        );
+        assert_eq!(
+            parse_line("osd hello tv")?,
+            Input::Osd("hello tv".to_owned())
+        );
+        assert_eq!(
+            parse_line("raw 40:04")?,
+            Input::Raw(Frame(vec![0x40, 0x04]))
+        );
+        assert_eq!(parse_line("quit")?, Input::Quit);
+
+        for line in ["", "osd", "raw", "raw zz", "on now", "toaster"] {
+            assert!(parse_line(line).is_err(), "{line}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Sends HDMI-CEC commands on a schedule, like a keep-alive or putting the TV
+//! in standby at night.
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::Result;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    cec::CommandTx,
+    config,
+    job::{self, SpawnResult},
+    Spawn,
+};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Represents the timer job, sending commands on a schedule.
+pub struct Job;
+
+/// Represents the timer job configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub timers: Vec<config::Timer>,
+    /// Where the commands are sent, see [`crate::cec::Job::command_tx`].
+    pub cmd_tx: CommandTx,
+}
+
+/// When a timer fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Repeatedly, like `every 30m`. The first fire is one period after owl
+    /// starts.
+    Every(Duration),
+    /// Once a day at a UTC time, like `daily-utc 23:30`.
+    Daily { hour: u8, minute: u8 },
+}
+
+impl Schedule {
+    /// Returns when the schedule next fires after `now`, or `None` if that's
+    /// too far off for the system clock to represent.
+    #[must_use]
+    pub fn next_after(self, now: SystemTime) -> Option<SystemTime> {
+        match self {
+            Self::Every(period) => now.checked_add(period),
+            Self::Daily { hour, minute } => {
+                let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                let at = now / DAY * DAY + u64::from(hour) * 3600 + u64::from(minute) * 60;
+                Some(UNIX_EPOCH + Duration::from_secs(if at <= now { at + DAY } else { at }))
+            }
+        }
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Every(period) => {
+                let secs = period.as_secs();
+                if secs % 3600 == 0 {
+                    write!(f, "every {}h", secs / 3600)
+                } else if secs % 60 == 0 {
+                    write!(f, "every {}m", secs / 60)
+                } else {
+                    write!(f, "every {secs}s")
+                }
+            }
+            Self::Daily { hour, minute } => write!(f, "daily-utc {hour:02}:{minute:02}"),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("invalid schedule `{s}`, expected e.g. `every 30m` or `daily-utc 23:30`");
+        match s.split_once(' ').ok_or_else(invalid)? {
+            ("every", period) => {
+                let (at, unit) = period.char_indices().last().ok_or_else(invalid)?;
+                let count = &period[..at];
+                let unit = match unit {
+                    's' => 1,
+                    'm' => 60,
+                    'h' => 3600,
+                    _ => return Err(invalid()),
+                };
+                let count: u64 = count.parse().map_err(|_| invalid())?;
+                if count == 0 {
+                    return Err(format!(
+                        "invalid schedule `{s}`, the period must not be zero"
+                    ));
+                }
+                let secs = count.checked_mul(unit).ok_or_else(invalid)?;
+                Ok(Self::Every(Duration::from_secs(secs)))
+            }
+            ("daily-utc", time) => {
+                let (hour, minute) = time.split_once(':').ok_or_else(invalid)?;
+                let hour = hour.parse().ok().filter(|x| *x < 24).ok_or_else(invalid)?;
+                let minute = minute
+                    .parse()
+                    .ok()
+                    .filter(|x| *x < 60)
+                    .ok_or_else(invalid)?;
+                Ok(Self::Daily { hour, minute })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Spawn for Job {
+    type Config = Config;
+
+    /// Spawns a new timer job. The job runs on a thread.
+    async fn spawn(run_token: CancellationToken, config: Config) -> SpawnResult<Self> {
+        // Polling lets the job notice the run token being cancelled.
+        const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+        let Config { timers, cmd_tx } = config;
+        let now = SystemTime::now();
+        let mut next: Vec<_> = timers.iter().map(|x| x.schedule.next_after(now)).collect();
+        for (timer, at) in timers.iter().zip(&next) {
+            let Some(at) = at else {
+                warn!("timer `{}` is too far off to ever fire", timer.schedule);
+                continue;
+            };
+            let wait = at.duration_since(now).unwrap_or_default();
+            info!(
+                "timer `{}` sends {:?}, next in {}s",
+                timer.schedule,
+                timer.command,
+                wait.as_secs()
+            );
+        }
+
+        debug!("spawning timer job...");
+        let handle = job::spawn_thread("owl-timer", move || {
+            while !timers.is_empty() && !run_token.is_cancelled() {
+                let now = SystemTime::now();
+                for (timer, at) in timers.iter().zip(&mut next) {
+                    if !at.is_some_and(|x| now >= x) {
+                        continue;
+                    }
+
+                    info!(
+                        "timer `{}` fired, sending {:?}",
+                        timer.schedule, timer.command
+                    );
+                    if let Err(e) = cmd_tx.blocking_send((timer.command, None)) {
+                        error!("failed to send timed command: {e}");
+                    }
+                    *at = timer.schedule.next_after(now);
+                }
+
+                thread::sleep(POLL_INTERVAL);
+            }
+
+            debug!("stopping timer job...");
+            Ok(())
+        })?;
+
+        Ok((handle, Self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "every 30m".parse(),
+            Ok(Schedule::Every(Duration::from_secs(30 * 60)))
+        );
+        assert_eq!(
+            "daily-utc 23:05".parse(),
+            Ok(Schedule::Daily {
+                hour: 23,
+                minute: 5
+            })
+        );
+        for s in [
+            "every 0s",
+            "every 5d",
+            "every m",
+            "every 5é",
+            "every 9999999999999999h",
+            "every ",
+            "daily 23:30",
+            "daily-utc 24:00",
+            "daily-utc 12",
+            "hourly 5",
+        ] {
+            assert!(s.parse::<Schedule>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() -> Result<(), String> {
+        for s in [
+            "every 90s",
+            "every 5m",
+            "every 2h",
+            "daily-utc 00:00",
+            "daily-utc 07:30",
+        ] {
+            assert_eq!(s.parse::<Schedule>()?.to_string(), s);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 1970-01-02 10:00 UTC.
+        let now = UNIX_EPOCH + Duration::from_secs(DAY + 10 * 3600);
+        let every = Schedule::Every(Duration::from_secs(60));
+        assert_eq!(every.next_after(now), Some(now + Duration::from_secs(60)));
+        assert_eq!(Schedule::Every(Duration::MAX).next_after(now), None);
+
+        let later_today = Schedule::Daily {
+            hour: 23,
+            minute: 30,
+        };
+        assert_eq!(
+            later_today.next_after(now),
+            Some(UNIX_EPOCH + Duration::from_secs(DAY + 23 * 3600 + 30 * 60))
+        );
+
+        let earlier_today = Schedule::Daily { hour: 9, minute: 0 };
+        assert_eq!(
+            earlier_today.next_after(now),
+            Some(UNIX_EPOCH + Duration::from_secs(2 * DAY + 9 * 3600))
+        );
+
+        let right_now = Schedule::Daily {
+            hour: 10,
+            minute: 0,
+        };
+        assert_eq!(
+            right_now.next_after(now),
+            Some(UNIX_EPOCH + Duration::from_secs(2 * DAY + 10 * 3600))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Renders the HDMI-CEC bus as a tree, to help users find which port each
+//! device is connected to.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+};
+
+use ::cec::{DeviceInfo, PhysicalAddress};
+
+/// The HDMI-CEC bus as a tree, derived from each device's physical address.
+/// Devices are nested under the nearest device found on the path to the TV,
+/// devices without a valid address are listed separately.
+#[derive(Debug, Clone)]
+pub struct Topology {
+    devices: Vec<DeviceInfo>,
+    /// The physical addresses of the tree's nodes, always including the TV.
+    nodes: BTreeSet<u16>,
+}
+
+impl Topology {
+    #[must_use]
+    pub fn new(devices: Vec<DeviceInfo>) -> Self {
+        let nodes = devices
+            .iter()
+            .map(|x| x.physical_address)
+            .filter(|x| x.is_valid())
+            .chain([PhysicalAddress::ROOT])
+            .map(u16::from)
+            .collect();
+
+        Self { devices, nodes }
+    }
+
+    /// Returns the node `address` is nested under, `None` for the TV.
+    fn parent(&self, address: PhysicalAddress) -> Option<PhysicalAddress> {
+        let mut parent = address.parent()?;
+        while !self.nodes.contains(&parent.0) {
+            // The TV is always a node, so this terminates.
+            parent = parent.parent()?;
+        }
+
+        Some(parent)
+    }
+
+    fn children(&self, address: PhysicalAddress) -> Vec<PhysicalAddress> {
+        self.nodes
+            .iter()
+            .map(|x| PhysicalAddress(*x))
+            .filter(|x| self.parent(*x) == Some(address))
+            .collect()
+    }
+
+    fn fmt_node(&self, f: &mut fmt::Formatter<'_>, address: PhysicalAddress) -> fmt::Result {
+        write!(f, "{address}")?;
+        if let Some(port) = address.port() {
+            write!(f, " (port {port})")?;
+        }
+
+        let labels: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|x| x.physical_address == address)
+            .map(label)
+            .collect();
+        if labels.is_empty() {
+            writeln!(f, ": not found")
+        } else {
+            writeln!(f, ": {}", labels.join("; "))
+        }
+    }
+
+    fn fmt_children(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        address: PhysicalAddress,
+        prefix: &str,
+    ) -> fmt::Result {
+        let children = self.children(address);
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            write!(f, "{prefix}{}", if last { "└── " } else { "├── " })?;
+            self.fmt_node(f, *child)?;
+            self.fmt_children(
+                f,
+                *child,
+                &format!("{prefix}{}", if last { "    " } else { "│   " }),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_node(f, PhysicalAddress::ROOT)?;
+        self.fmt_children(f, PhysicalAddress::ROOT, "")?;
+
+        let unknown: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|x| !x.physical_address.is_valid())
+            .collect();
+        if !unknown.is_empty() {
+            writeln!(f, "unknown address:")?;
+            for device in unknown {
+                writeln!(f, "    {}: {}", device.physical_address, label(device))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn label(device: &DeviceInfo) -> String {
+    let name = if device.name.is_empty() {
+        "no name".to_owned()
+    } else {
+        format!("{:?}", device.name)
+    };
+    let vendor = device
+        .vendor_id
+        .map_or_else(|| "unknown vendor".to_owned(), |x| format!("{x:?}"));
+
+    format!(
+        "{:?} {name}, {vendor}, {:?}",
+        device.address, device.power_status
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ::cec::{LogicalAddress, PowerStatus, VendorId};
+
+    use super::*;
+
+    fn device(address: LogicalAddress, physical_address: u16, name: &str) -> DeviceInfo {
+        DeviceInfo {
+            address,
+            physical_address: PhysicalAddress(physical_address),
+            vendor_id: Some(VendorId::Lg),
+            name: name.to_owned(),
+            power_status: PowerStatus::On,
+        }
+    }
+
+    #[test]
+    fn test_nested() {
+        let topology = Topology::new(vec![
+            device(LogicalAddress::Tv, 0x0000, "TV"),
+            device(LogicalAddress::Audiosystem, 0x1000, "AVR"),
+            device(LogicalAddress::Playbackdevice1, 0x1100, "Player"),
+            device(LogicalAddress::Recordingdevice1, 0x2000, "owl"),
+        ]);
+        assert_eq!(
+            topology.to_string(),
+            "\
+0.0.0.0: Tv \"TV\", Lg, On
+├── 1.0.0.0 (port 1): Audiosystem \"AVR\", Lg, On
+│   └── 1.1.0.0 (port 1): Playbackdevice1 \"Player\", Lg, On
+└── 2.0.0.0 (port 2): Recordingdevice1 \"owl\", Lg, On
+"
+        );
+    }
+
+    #[test]
+    fn test_missing_parent() {
+        let mut player = device(LogicalAddress::Playbackdevice1, 0x1200, "");
+        player.vendor_id = None;
+        let topology = Topology::new(vec![player]);
+        assert_eq!(
+            topology.to_string(),
+            "\
+0.0.0.0: not found
+└── 1.2.0.0 (port 2): Playbackdevice1 no name, unknown vendor, On
+"
+        );
+    }
+
+    #[test]
+    fn test_unknown_address() {
+        let topology = Topology::new(vec![
+            device(LogicalAddress::Tv, 0x0000, "TV"),
+            device(LogicalAddress::Tuner1, 0xFFFF, "Tuner"),
+        ]);
+        assert_eq!(
+            topology.to_string(),
+            "\
+0.0.0.0: Tv \"TV\", Lg, On
+unknown address:
+    f.f.f.f: Tuner1 \"Tuner\", Lg, On
+"
+        );
+    }
+}
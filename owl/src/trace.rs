@@ -0,0 +1,128 @@
+//! Traces of the frames seen on the HDMI-CEC bus, to capture a TV's behaviour
+//! for bug reports and replay it later.
+//!
+//! A trace is a text file with a frame per line: the milliseconds since
+//! recording started, then the frame as colon separated hex bytes, like
+//! `1532 40:04`.
+
+use std::{
+    fmt::{self, Display},
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use ::cec::LogicalAddress;
+use color_eyre::eyre::{Context, Result};
+use tokio::{signal, sync::mpsc};
+use tracing::{debug, info};
+
+use crate::{cec, cli::Frame};
+
+/// How often the trace is flushed, so a crash loses at most this much.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A frame seen on the bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// When the frame was seen, relative to the start of the trace.
+    pub time: Duration,
+    pub frame: Frame,
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.time.as_millis(), self.frame)
+    }
+}
+
+impl FromStr for Entry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (time, frame) = s
+            .split_once(' ')
+            .ok_or_else(|| format!("invalid entry `{s}`, expected a time and a frame"))?;
+        let time = time
+            .parse()
+            .map_err(|e| format!("invalid time `{time}`: {e}"))?;
+
+        Ok(Self {
+            time: Duration::from_millis(time),
+            frame: frame.parse()?,
+        })
+    }
+}
+
+/// Appends every frame seen on the bus to the trace at `path` until Ctrl+C,
+/// only those involving `filter` if given. owl connects in monitor mode, so it
+/// doesn't take part in the bus.
+pub async fn record(
+    config: &cec::Config,
+    path: &Path,
+    filter: Option<LogicalAddress>,
+) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open trace file `{}`", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel();
+    let _monitor = cec::monitor(config, filter, cmd_tx)?;
+    let start = Instant::now();
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    info!("recording to `{}`, press Ctrl+C to stop...", path.display());
+
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        {
+            tokio::select! {
+                cmd = cmd_rx.recv() => {
+                    let Some((time, cmd)) = cmd else { break };
+                    let entry = Entry {
+                        time: time.saturating_duration_since(start),
+                        frame: Frame(cmd.to_frame()),
+                    };
+                    debug!("recorded: {entry}");
+                    writeln!(writer, "{entry}").context("failed to write trace")?;
+                },
+                _ = flush.tick() => writer.flush().context("failed to flush trace")?,
+                _ = signal::ctrl_c() => {
+                    debug!("received CTRL+C");
+                    break;
+                },
+            }
+        }
+    }
+
+    writer.flush().context("failed to flush trace")?;
+    info!("recording stopped!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_round_trip() -> Result<(), String> {
+        let entry = Entry {
+            time: Duration::from_millis(1532),
+            frame: Frame(vec![0x4F, 0x82, 0x20, 0x00]),
+        };
+        assert_eq!(entry.to_string(), "1532 4f:82:20:00");
+        assert_eq!(entry.to_string().parse::<Entry>()?, entry);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_entry() {
+        assert!("1532".parse::<Entry>().is_err());
+        assert!("soon 40:04".parse::<Entry>().is_err());
+        assert!("1532 40:zz".parse::<Entry>().is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    cec::{Button, Command},
+    config::Keymap,
+    os::{Event, Key, Monitor},
+};
+
+/// Translates OS events into HDMI-CEC commands. Each event maps to zero or
+/// more commands, sent in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translator {
+    commands_by_event: HashMap<Event, Vec<Command>>,
+    /// The monitors whose focus is translated, empty for any.
+    focus_monitors: HashSet<Monitor>,
+}
+
+impl Translator {
+    /// Creates a translator which ignores every event.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            commands_by_event: HashMap::new(),
+            focus_monitors: HashSet::new(),
+        }
+    }
+
+    /// Sets the commands an event translates into, replacing any existing
+    /// mapping. An empty list ignores the event. Focus is mapped as
+    /// `Event::Focus(None)`, for every monitor.
+    #[must_use]
+    pub fn with(mut self, event: Event, commands: Vec<Command>) -> Self {
+        self.commands_by_event.insert(event, commands);
+        self
+    }
+
+    /// Maps each key in `keymap` to its HDMI-CEC keypress, replacing any
+    /// existing mapping.
+    #[must_use]
+    pub fn with_keymap(self, keymap: &[Keymap]) -> Self {
+        keymap.iter().fold(self, |translator, x| {
+            translator.with(
+                Event::RawKey(x.key),
+                vec![Command::Keypress(x.target, x.control)],
+            )
+        })
+    }
+
+    /// Only translates focus on `monitors`, so using the PC on another screen
+    /// doesn't switch the TV. Empty translates focus on any monitor.
+    #[must_use]
+    pub fn with_focus_monitors(mut self, monitors: impl IntoIterator<Item = Monitor>) -> Self {
+        self.focus_monitors = monitors.into_iter().collect();
+        self
+    }
+
+    /// Translates an event into the commands to send. Focus on an unknown
+    /// monitor is translated, rather than dropping every focus when the
+    /// monitor can't be found.
+    #[must_use]
+    pub fn translate(&self, event: Event) -> Vec<Command> {
+        let event = match event {
+            Event::Focus(Some(monitor))
+                if !self.focus_monitors.is_empty() && !self.focus_monitors.contains(&monitor) =>
+            {
+                return Vec::new();
+            }
+            Event::Focus(_) => Event::Focus(None),
+            x => x,
+        };
+
+        self.commands_by_event
+            .get(&event)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Translator {
+    /// Creates the default translator, powering the TV with the PC and
+    /// forwarding the volume keys and mute state.
+    fn default() -> Self {
+        let translator = Self::empty()
+            .with(Event::Suspend, vec![Command::PowerOff])
+            .with(Event::Shutdown, vec![Command::PowerOff])
+            .with(Event::Resume, vec![Command::PowerOn])
+            .with(Event::Focus(None), vec![Command::Focus])
+            .with(Event::Mute(true), vec![Command::SetMute(true)])
+            .with(Event::Mute(false), vec![Command::SetMute(false)]);
+
+        Key::ALL.into_iter().fold(translator, |translator, key| {
+            translator
+                .with(Event::Press(key), vec![Command::Press(Button::from(key))])
+                .with(
+                    Event::Release(key),
+                    vec![Command::Release(Button::from(key))],
+                )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::cec::{LogicalAddress, UserControlCode};
+
+    use super::*;
+    use crate::os::KeyCode;
+
+    #[test]
+    fn test_default() {
+        let translator = Translator::default();
+        assert_eq!(
+            translator.translate(Event::Suspend),
+            vec![Command::PowerOff]
+        );
+        assert_eq!(
+            translator.translate(Event::Shutdown),
+            vec![Command::PowerOff]
+        );
+        assert_eq!(translator.translate(Event::Resume), vec![Command::PowerOn]);
+        assert_eq!(
+            translator.translate(Event::Focus(None)),
+            vec![Command::Focus]
+        );
+        assert_eq!(
+            translator.translate(Event::Mute(true)),
+            vec![Command::SetMute(true)]
+        );
+        assert_eq!(
+            translator.translate(Event::Press(Key::VolumeUp)),
+            vec![Command::Press(Button::VolumeUp)]
+        );
+        assert_eq!(
+            translator.translate(Event::Release(Key::VolumeMute)),
+            vec![Command::Release(Button::VolumeMute)]
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        let translator = Translator::default()
+            .with(Event::Suspend, vec![Command::Focus, Command::PowerOff])
+            .with(Event::Focus(None), vec![]);
+        assert_eq!(
+            translator.translate(Event::Suspend),
+            vec![Command::Focus, Command::PowerOff]
+        );
+        assert!(translator.translate(Event::Focus(None)).is_empty());
+        assert!(translator
+            .translate(Event::Focus(Some(Monitor(1))))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_keymap() {
+        let keymap = Keymap {
+            key: KeyCode(0x7C),
+            control: UserControlCode::InputSelect,
+            target: LogicalAddress::Tv,
+        };
+        let translator = Translator::default().with_keymap(&[keymap]);
+        assert_eq!(
+            translator.translate(Event::RawKey(KeyCode(0x7C))),
+            vec![Command::Keypress(
+                LogicalAddress::Tv,
+                UserControlCode::InputSelect
+            )]
+        );
+        assert!(translator
+            .translate(Event::RawKey(KeyCode(0x7D)))
+            .is_empty());
+        assert_eq!(
+            translator.translate(Event::Press(Key::VolumeUp)),
+            vec![Command::Press(Button::VolumeUp)]
+        );
+    }
+
+    #[test]
+    fn test_focus_monitors() {
+        let any = Translator::default();
+        assert_eq!(
+            any.translate(Event::Focus(Some(Monitor(2)))),
+            vec![Command::Focus]
+        );
+
+        let first = Translator::default().with_focus_monitors([Monitor(1)]);
+        assert_eq!(
+            first.translate(Event::Focus(Some(Monitor(1)))),
+            vec![Command::Focus]
+        );
+        assert!(first.translate(Event::Focus(Some(Monitor(2)))).is_empty());
+        assert_eq!(first.translate(Event::Focus(None)), vec![Command::Focus]);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(Translator::empty().translate(Event::Resume).is_empty());
+    }
+}